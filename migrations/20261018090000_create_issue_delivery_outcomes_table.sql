@@ -0,0 +1,8 @@
+CREATE TABLE `issue_delivery_outcomes` (
+  `newsletter_issue_id` UUID NOT NULL,
+  `subscriber_email` VARCHAR(319) NOT NULL,
+  `outcome` VARCHAR(25) NOT NULL,
+  `error_message` TEXT DEFAULT NULL,
+  `attempted_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`newsletter_issue_id`,`subscriber_email`)
+);
@@ -0,0 +1,3 @@
+-- Existing users keep full access
+ALTER TABLE `users` ADD COLUMN `role` VARCHAR(25) NOT NULL DEFAULT 'owner';
+ALTER TABLE `users` ADD COLUMN `status` VARCHAR(25) NOT NULL DEFAULT 'active';
@@ -0,0 +1,3 @@
+-- Invited users have no password until they accept their invitation
+ALTER TABLE `users` ADD COLUMN `email` VARCHAR(319) NULL;
+ALTER TABLE `users` MODIFY `password_hash` TEXT NULL;
@@ -0,0 +1,7 @@
+CREATE TABLE `password_reset_tokens` (
+  `token_hash` CHAR(64) NOT NULL PRIMARY KEY,
+  `user_id` UUID NOT NULL REFERENCES `users`(`id`),
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `expires_at` TIMESTAMP NOT NULL,
+  `used_at` TIMESTAMP NULL DEFAULT NULL
+);
@@ -0,0 +1,9 @@
+-- The secret is encrypted by the application, the last step prevents replaying a code
+ALTER TABLE `users` ADD COLUMN `totp_secret` VARCHAR(255) NULL;
+ALTER TABLE `users` ADD COLUMN `totp_last_step` BIGINT UNSIGNED NULL;
+CREATE TABLE `user_recovery_codes` (
+  `user_id` UUID NOT NULL REFERENCES `users`(`id`),
+  `code_hash` CHAR(64) NOT NULL,
+  `used_at` TIMESTAMP NULL DEFAULT NULL,
+  PRIMARY KEY (`user_id`, `code_hash`)
+);
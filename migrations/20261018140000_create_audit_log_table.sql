@@ -0,0 +1,7 @@
+CREATE TABLE `audit_log` (
+  `id` BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  `event` VARCHAR(50) NOT NULL,
+  `subject` VARCHAR(255) NOT NULL,
+  `details` TEXT NULL DEFAULT NULL,
+  `occurred_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+CREATE TABLE `user_sessions` (
+  `id` UUID NOT NULL PRIMARY KEY,
+  `user_id` UUID NOT NULL REFERENCES `users`(`id`),
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `last_seen_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `ip` VARCHAR(45) NULL DEFAULT NULL,
+  `user_agent` VARCHAR(512) NULL DEFAULT NULL,
+  `revoked_at` TIMESTAMP NULL DEFAULT NULL,
+  INDEX (`user_id`)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE `api_tokens` (
+  `id` UUID NOT NULL PRIMARY KEY,
+  `user_id` UUID NOT NULL REFERENCES `users`(`id`),
+  `name` VARCHAR(100) NOT NULL,
+  `token_hash` CHAR(64) NOT NULL UNIQUE,
+  `scopes` VARCHAR(255) NOT NULL,
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `last_used_at` TIMESTAMP NULL DEFAULT NULL,
+  `revoked_at` TIMESTAMP NULL DEFAULT NULL,
+  INDEX (`user_id`)
+);
@@ -0,0 +1,5 @@
+-- Issues can now be stored as drafts, which have not been published yet
+ALTER TABLE `newsletter_issues`
+  ADD COLUMN `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  MODIFY `published_at` TIMESTAMP NULL DEFAULT NULL;
+UPDATE `newsletter_issues` SET `created_at` = `published_at`;
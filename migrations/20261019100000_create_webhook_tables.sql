@@ -0,0 +1,30 @@
+-- Lifecycle events, recorded in the same transaction as the change they describe
+CREATE TABLE `webhook_events` (
+  `event_id` UUID NOT NULL PRIMARY KEY,
+  `event` VARCHAR(50) NOT NULL,
+  `payload` TEXT NOT NULL,
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `dispatched_at` TIMESTAMP NULL DEFAULT NULL,
+  INDEX (`dispatched_at`)
+);
+-- One row per event and configured endpoint, doubling as the retry queue while `pending`
+CREATE TABLE `webhook_deliveries` (
+  `delivery_id` UUID NOT NULL PRIMARY KEY,
+  `event_id` UUID NOT NULL REFERENCES `webhook_events`(`event_id`),
+  `endpoint_url` VARCHAR(2048) NOT NULL,
+  `status` VARCHAR(25) NOT NULL DEFAULT 'pending',
+  `n_attempts` INT NOT NULL DEFAULT 0,
+  `last_error` TEXT DEFAULT NULL,
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `next_attempt_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `last_attempted_at` TIMESTAMP NULL DEFAULT NULL,
+  INDEX (`status`, `next_attempt_at`),
+  INDEX (`endpoint_url`(255), `status`, `next_attempt_at`),
+  INDEX (`created_at`)
+);
+-- Set once no delivery of a published issue is left in the queue
+ALTER TABLE `newsletter_issues`
+  ADD COLUMN `sent_at` TIMESTAMP NULL DEFAULT NULL;
+UPDATE `newsletter_issues` SET `sent_at` = `published_at`
+ WHERE `published_at` IS NOT NULL
+   AND `newsletter_issue_id` NOT IN (SELECT `newsletter_issue_id` FROM `issue_delivery_queue`);
@@ -0,0 +1,10 @@
+-- Confirmation emails are queued with the subscription, then sent by the background worker
+CREATE TABLE `confirmation_email_outbox` (
+  `subscription_token` VARCHAR(25) NOT NULL PRIMARY KEY,
+  `subscriber_id` UUID NOT NULL,
+  `n_attempts` INT NOT NULL DEFAULT 0,
+  `last_error` TEXT DEFAULT NULL,
+  `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `next_attempt_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  INDEX (`next_attempt_at`)
+);
@@ -0,0 +1,61 @@
+use anyhow::Context;
+use sqlx::MySqlPool;
+use std::net::IpAddr;
+
+/// Security relevant events that are kept in the `audit_log` table.
+#[derive(Debug)]
+pub enum AuditEvent<'a> {
+    AccountLockedOut {
+        account: &'a str,
+        ip: Option<IpAddr>,
+    },
+    AddressLockedOut {
+        account: &'a str,
+        ip: IpAddr,
+    },
+}
+
+impl AuditEvent<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::AccountLockedOut { .. } => "account_locked_out",
+            Self::AddressLockedOut { .. } => "address_locked_out",
+        }
+    }
+
+    fn subject(&self) -> String {
+        match self {
+            Self::AccountLockedOut { account, .. } => account.to_string(),
+            Self::AddressLockedOut { ip, .. } => ip.to_string(),
+        }
+    }
+
+    fn details(&self) -> Option<String> {
+        match self {
+            Self::AccountLockedOut { ip, .. } => {
+                ip.map(|ip| format!("Last failed attempt from {}", ip))
+            }
+            Self::AddressLockedOut { account, .. } => {
+                Some(format!("Last failed attempt for account {}", account))
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "Record audit event", skip(db_pool))]
+pub async fn record_audit_event(
+    event: AuditEvent<'_>,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO `audit_log` (`event`, `subject`, `details`) VALUES (?, ?, ?)"#,
+        event.name(),
+        event.subject(),
+        event.details(),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to record an audit event.")?;
+
+    Ok(())
+}
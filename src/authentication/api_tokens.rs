@@ -0,0 +1,220 @@
+use super::{Permission, Role};
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+const API_TOKEN_PREFIX: &str = "z2p_";
+const LAST_USED_AT_PRECISION_SECONDS: i64 = 60;
+
+pub struct ApiToken {
+    pub token_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Permission>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// The permissions granted to the API token a request has been authenticated with.
+#[derive(Clone, Debug)]
+pub struct ApiTokenScopes(pub Vec<Permission>);
+
+pub struct AuthenticatedApiToken {
+    pub user_id: Uuid,
+    pub role: Role,
+    pub scopes: ApiTokenScopes,
+}
+
+fn generate_api_token() -> String {
+    let rng = thread_rng();
+    let secret: String = rng
+        .sample_iter(Alphanumeric)
+        .map(char::from)
+        .take(40)
+        .collect();
+
+    format!("{}{}", API_TOKEN_PREFIX, secret)
+}
+
+// Tokens are long random strings, so a plain hash is enough to keep a database leak harmless.
+fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn format_scopes(scopes: &[Permission]) -> String {
+    scopes
+        .iter()
+        .map(Permission::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_scopes(scopes: &str) -> Result<Vec<Permission>, anyhow::Error> {
+    scopes
+        .split(',')
+        .filter(|scope| !scope.is_empty())
+        .map(|scope| Permission::parse(scope).map_err(anyhow::Error::msg))
+        .collect()
+}
+
+/// Creates a token and returns its plain text value, which is never stored and cannot be shown again.
+#[tracing::instrument(name = "Create API token", skip(db_pool))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[Permission],
+    db_pool: &MySqlPool,
+) -> Result<String, anyhow::Error> {
+    let token = generate_api_token();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO `api_tokens` (`id`, `user_id`, `name`, `token_hash`, `scopes`)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash_api_token(&token),
+        format_scopes(scopes),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to create an API token.")?;
+
+    Ok(token)
+}
+
+#[tracing::instrument(name = "List API tokens", skip(db_pool))]
+pub async fn list_api_tokens(
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT `id`, `name`, `scopes`, `created_at`, `last_used_at`
+          FROM `api_tokens`
+         WHERE `user_id` = ? AND `revoked_at` IS NULL
+         ORDER BY `created_at` DESC
+        "#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to list API tokens.")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ApiToken {
+                token_id: Uuid::parse_str(&row.id)
+                    .context("Failed to parse API token UUID loaded from the database.")?,
+                name: row.name,
+                scopes: parse_scopes(&row.scopes)?,
+                created_at: row.created_at,
+                last_used_at: row.last_used_at,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Revoke API token", skip(db_pool))]
+pub async fn revoke_api_token(
+    token_id: Uuid,
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE `api_tokens`
+           SET `revoked_at` = ?
+         WHERE `id` = ? AND `user_id` = ? AND `revoked_at` IS NULL
+        "#,
+        Utc::now(),
+        token_id,
+        user_id,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to revoke an API token.")?;
+
+    Ok(())
+}
+
+/// Looks up an active token owned by an active user and records that it has been used, at most
+/// once a minute.
+#[tracing::instrument(name = "Authenticate API token", skip_all)]
+pub async fn authenticate_api_token(
+    token: &str,
+    db_pool: &MySqlPool,
+) -> Result<Option<AuthenticatedApiToken>, anyhow::Error> {
+    let token_hash = hash_api_token(token);
+    let row = sqlx::query!(
+        r#"
+        SELECT `t`.`id`, `t`.`user_id`, `t`.`scopes`, `u`.`role`
+          FROM `api_tokens` `t`
+          JOIN `users` `u` ON `u`.`id` = `t`.`user_id`
+         WHERE `t`.`token_hash` = ? AND `t`.`revoked_at` IS NULL AND `u`.`status` = 'active'
+        "#,
+        token_hash
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve an API token.")?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    // Only a rough idea of when a token was last used is needed, so busy clients do not turn
+    // every request into a write
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        UPDATE `api_tokens`
+           SET `last_used_at` = ?
+         WHERE `id` = ? AND (`last_used_at` IS NULL OR `last_used_at` < ?)
+        "#,
+        now,
+        row.id,
+        now - Duration::seconds(LAST_USED_AT_PRECISION_SECONDS),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to record API token usage.")?;
+
+    Ok(Some(AuthenticatedApiToken {
+        user_id: Uuid::parse_str(&row.user_id)
+            .context("Failed to parse user UUID loaded from the database.")?,
+        role: Role::parse(&row.role).map_err(anyhow::Error::msg)?,
+        scopes: ApiTokenScopes(parse_scopes(&row.scopes)?),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_scopes, generate_api_token, parse_scopes, Permission};
+    use claims::assert_ok_eq;
+
+    #[test]
+    fn generated_tokens_are_prefixed_and_unique() {
+        let token = generate_api_token();
+
+        assert!(token.starts_with("z2p_"));
+        assert_ne!(token, generate_api_token());
+    }
+
+    #[test]
+    fn scopes_round_trip_through_their_stored_form() {
+        let scopes = vec![Permission::PublishNewsletters, Permission::ManageUsers];
+
+        assert_ok_eq!(parse_scopes(&format_scopes(&scopes)), scopes);
+    }
+
+    #[test]
+    fn empty_scopes_are_parsed_as_no_permissions() {
+        assert_ok_eq!(parse_scopes(""), vec![]);
+    }
+}
@@ -0,0 +1,160 @@
+use super::ApiTokenScopes;
+use crate::{session_state::TypedSession, utils::internal_server_error};
+use actix_http::BoxedPayloadStream;
+use actix_multipart::Multipart;
+use actix_web::{
+    body::MessageBody,
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error::InternalError,
+    web, FromRequest, HttpMessage, HttpResponse,
+};
+use actix_web_lab::middleware::Next;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{cell::RefCell, rc::Rc, task::Poll};
+
+/// Generated tokens are shorter, anything longer is not worth reading.
+const MAX_CSRF_TOKEN_LENGTH: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    fn generate() -> Self {
+        let rng = thread_rng();
+        Self(
+            rng.sample_iter(Alphanumeric)
+                .map(char::from)
+                .take(32)
+                .collect(),
+        )
+    }
+
+    /// Hidden form field carrying the token, to be embedded into every form that POSTs back to us.
+    pub fn form_field(&self) -> String {
+        format!(
+            r#"<input type="hidden" name="csrf_token" value="{}" />"#,
+            self.0
+        )
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CsrfForm {
+    csrf_token: Option<String>,
+}
+
+pub async fn reject_invalid_csrf_tokens(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // API tokens are sent explicitly by the client rather than attached by the browser, so there
+    // is nothing to check and no session to keep the token in
+    if req.extensions().contains::<ApiTokenScopes>() {
+        req.extensions_mut().insert(CsrfToken::generate());
+        return next.call(req).await;
+    }
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let stored_token = session.get_csrf_token().map_err(internal_server_error)?;
+
+    if !req.method().is_safe() {
+        let submitted_token = if is_multipart(&req) {
+            // Uploads are streamed by their handler instead of being buffered here, so the token
+            // has to come first in multipart forms
+            multipart_csrf_token(&mut req).await
+        } else {
+            // The form body has to be read to find the token, so it is put back afterwards for
+            // the handler to extract it again.
+            let body = req.extract::<web::Bytes>().await?;
+            let submitted_token = serde_urlencoded::from_bytes::<CsrfForm>(&body)
+                .ok()
+                .and_then(|form| form.csrf_token);
+            req.set_payload(bytes_to_payload(body));
+            submitted_token
+        };
+
+        match (&stored_token, submitted_token) {
+            (Some(stored_token), Some(submitted_token)) if *stored_token == submitted_token => {}
+            _ => {
+                let response = HttpResponse::Forbidden().finish();
+                let e = anyhow::anyhow!("The CSRF token is missing or invalid");
+                return Err(InternalError::from_response(e, response).into());
+            }
+        }
+    }
+
+    let csrf_token = match stored_token {
+        Some(token) => CsrfToken(token),
+        None => {
+            let token = CsrfToken::generate();
+            session
+                .insert_csrf_token(&token.0)
+                .map_err(internal_server_error)?;
+            token
+        }
+    };
+    req.extensions_mut().insert(csrf_token);
+
+    next.call(req).await
+}
+
+fn is_multipart(req: &ServiceRequest) -> bool {
+    req.mime_type()
+        .ok()
+        .flatten()
+        .is_some_and(|mime| mime.type_() == mime::MULTIPART)
+}
+
+/// Reads the token from the first field of a multipart body, then puts back what has been read
+/// in front of the rest of the body for the handler to parse it again.
+async fn multipart_csrf_token(req: &mut ServiceRequest) -> Option<String> {
+    let payload = Rc::new(RefCell::new(req.take_payload()));
+    let read_chunks = Rc::new(RefCell::new(Vec::new()));
+    let recorded_payload = {
+        let payload = payload.clone();
+        let read_chunks = read_chunks.clone();
+        stream::poll_fn(move |cx| {
+            let item = payload.borrow_mut().poll_next_unpin(cx);
+            if let Poll::Ready(Some(Ok(chunk))) = &item {
+                read_chunks.borrow_mut().push(chunk.clone());
+            }
+            item
+        })
+    };
+
+    let mut multipart = Multipart::new(req.headers(), recorded_payload);
+    let mut submitted_token = None;
+    if let Ok(Some(mut field)) = multipart.try_next().await {
+        if field.name() == Some("csrf_token") {
+            let mut value = Vec::new();
+            while let Ok(Some(chunk)) = field.try_next().await {
+                value.extend_from_slice(&chunk);
+                if value.len() > MAX_CSRF_TOKEN_LENGTH {
+                    break;
+                }
+            }
+            submitted_token = String::from_utf8(value).ok();
+        }
+    }
+    drop(multipart);
+
+    let read_chunks = std::mem::take(&mut *read_chunks.borrow_mut());
+    let remaining_payload = stream::poll_fn(move |cx| payload.borrow_mut().poll_next_unpin(cx));
+    let replayed_payload: BoxedPayloadStream =
+        Box::pin(stream::iter(read_chunks.into_iter().map(Ok)).chain(remaining_payload));
+    req.set_payload(Payload::Stream {
+        payload: replayed_payload,
+    });
+
+    submitted_token
+}
+
+fn bytes_to_payload(body: web::Bytes) -> Payload {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(body);
+    Payload::from(payload)
+}
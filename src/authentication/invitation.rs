@@ -0,0 +1,127 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+const INVITATION_VALIDITY_DAYS: i64 = 7;
+
+/// A setup link token for an invited user.
+///
+/// The token carries the user id and an expiry timestamp, signed with the application HMAC
+/// secret. It can only be redeemed once, because accepting the invitation moves the user out
+/// of the `pending` status.
+#[derive(Debug)]
+pub struct InvitationToken {
+    pub user_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+impl InvitationToken {
+    pub fn new(user_id: Uuid) -> Self {
+        Self {
+            user_id,
+            expires_at: Utc::now() + Duration::days(INVITATION_VALIDITY_DAYS),
+        }
+    }
+
+    pub fn sign(&self, secret: &Secret<String>) -> String {
+        let payload = self.payload();
+        let tag = mac(&payload, secret).finalize().into_bytes();
+
+        format!("{}.{}", payload, hex::encode(tag))
+    }
+
+    pub fn verify(token: &str, secret: &Secret<String>) -> Result<Self, anyhow::Error> {
+        let (payload, tag) = token
+            .rsplit_once('.')
+            .context("The invitation token is malformed.")?;
+        let tag = hex::decode(tag).context("The invitation token signature is not valid hex.")?;
+        mac(payload, secret)
+            .verify_slice(&tag)
+            .context("The invitation token signature is invalid.")?;
+
+        let (user_id, expires_at) = payload
+            .split_once('.')
+            .context("The invitation token is malformed.")?;
+        let user_id = Uuid::parse_str(user_id).context("The invitation user id is invalid.")?;
+        let expires_at = expires_at
+            .parse()
+            .ok()
+            .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+            .context("The invitation expiry timestamp is invalid.")?;
+
+        if expires_at < Utc::now() {
+            anyhow::bail!("The invitation has expired.");
+        }
+
+        Ok(Self {
+            user_id,
+            expires_at,
+        })
+    }
+
+    fn payload(&self) -> String {
+        format!("{}.{}", self.user_id, self.expires_at.timestamp())
+    }
+}
+
+fn mac(payload: &str, secret: &Secret<String>) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(payload.as_bytes());
+
+    mac
+}
+
+#[cfg(test)]
+mod test {
+    use super::InvitationToken;
+    use chrono::{Duration, Utc};
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-long-and-very-secret-key".into())
+    }
+
+    #[test]
+    fn signed_token_can_be_verified() {
+        let token = InvitationToken::new(Uuid::new_v4());
+
+        let verified = assert_ok!(InvitationToken::verify(&token.sign(&secret()), &secret()));
+        assert_eq!(verified.user_id, token.user_id);
+        assert_eq!(
+            verified.expires_at.timestamp(),
+            token.expires_at.timestamp()
+        );
+    }
+
+    #[test]
+    fn token_signed_with_another_secret_is_rejected() {
+        let token = InvitationToken::new(Uuid::new_v4()).sign(&Secret::new("another".into()));
+
+        assert_err!(InvitationToken::verify(&token, &secret()));
+    }
+
+    #[test]
+    fn tampered_token_is_rejected() {
+        let token = InvitationToken::new(Uuid::new_v4()).sign(&secret());
+        let tampered = format!("{}{}", Uuid::new_v4(), &token[36..]);
+
+        assert_err!(InvitationToken::verify(&tampered, &secret()));
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let token = InvitationToken {
+            user_id: Uuid::new_v4(),
+            expires_at: Utc::now() - Duration::seconds(1),
+        }
+        .sign(&secret());
+
+        assert_err!(InvitationToken::verify(&token, &secret()));
+    }
+}
@@ -1,14 +1,25 @@
+use super::{
+    authenticate_api_token, revoke_user_session, touch_user_session, ApiTokenScopes, Permission,
+    Role, SessionId,
+};
 use crate::{
+    configuration::SessionSettings,
+    errors::error_chain_fmt,
     session_state::TypedSession,
     utils::{internal_server_error, see_other},
 };
 use actix_web::{
-    body::MessageBody,
+    body::{EitherBody, MessageBody},
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
-    FromRequest, HttpMessage,
+    http::header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    web, FromRequest, HttpMessage, HttpResponse,
 };
+use actix_web_flash_messages::FlashMessage;
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::MySqlPool;
 use uuid::Uuid;
 
 #[derive(Copy, Clone, Debug)]
@@ -20,6 +31,12 @@ impl std::fmt::Display for UserId {
     }
 }
 
+impl From<Uuid> for UserId {
+    fn from(user_id: Uuid) -> Self {
+        Self(user_id)
+    }
+}
+
 impl std::ops::Deref for UserId {
     type Target = Uuid;
 
@@ -28,25 +45,234 @@ impl std::ops::Deref for UserId {
     }
 }
 
+/// Why a request could not be authenticated with a bearer token.
+#[derive(thiserror::Error)]
+pub enum BearerAuthError {
+    #[error("The request does not carry an API token")]
+    MissingToken,
+    #[error("The API token is invalid or has been revoked")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for BearerAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Attaches the identity of the API token found in the `Authorization` header to the request.
+///
+/// Requests without a token are passed through untouched unless `token_required` is set, while
+/// failures are turned into a response by `error_response`, so that the admin pages and the JSON
+/// API can each answer in their own format.
+pub async fn authenticate_bearer_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+    token_required: bool,
+    error_response: fn(BearerAuthError) -> actix_web::Error,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None if token_required => return Err(error_response(BearerAuthError::MissingToken)),
+        None => return next.call(req).await,
+    };
+
+    let db_pool = req
+        .app_data::<web::Data<MySqlPool>>()
+        .cloned()
+        .context("The database pool is not configured")
+        .map_err(|e| error_response(e.into()))?;
+    let api_token = authenticate_api_token(&token, &db_pool)
+        .await
+        .map_err(|e| error_response(e.into()))?
+        .ok_or_else(|| error_response(BearerAuthError::InvalidToken))?;
+    req.extensions_mut().insert(UserId(api_token.user_id));
+    req.extensions_mut().insert(api_token.role);
+    req.extensions_mut().insert(api_token.scopes);
+
+    next.call(req).await
+}
+
+pub async fn authenticate_api_tokens(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    authenticate_bearer_token(req, next, false, |e| match e {
+        BearerAuthError::UnexpectedError(e) => internal_server_error(e),
+        e => {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((WWW_AUTHENTICATE, "Bearer"))
+                .finish();
+            InternalError::from_response(e, response).into()
+        }
+    })
+    .await
+}
+
+fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_owned())
+}
+
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
-    next: Next<impl MessageBody>,
+    next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // Already authenticated with an API token, there is no session to look at
+    if req.extensions().contains::<ApiTokenScopes>() {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
     let user_id = session.get_user_id().map_err(internal_server_error)?;
-    match user_id {
-        Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
-        }
+    let user_id = match user_id {
+        Some(user_id) => user_id,
         None => {
             let response = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in");
-            Err(InternalError::from_response(e, response).into())
+            return Err(InternalError::from_response(e, response).into());
+        }
+    };
+
+    let db_pool = req
+        .app_data::<web::Data<MySqlPool>>()
+        .cloned()
+        .ok_or_else(|| internal_server_error("The database pool is not configured"))?;
+    let session_settings = req
+        .app_data::<web::Data<SessionSettings>>()
+        .cloned()
+        .ok_or_else(|| internal_server_error("The session settings are not configured"))?;
+    let session_id = session.get_session_id().map_err(internal_server_error)?;
+    let now = Utc::now().timestamp();
+    if has_session_expired(&session, &session_settings, now).map_err(internal_server_error)? {
+        if let Some(session_id) = session_id {
+            revoke_user_session(session_id, user_id, &db_pool)
+                .await
+                .map_err(internal_server_error)?;
+        }
+        session.purge();
+        FlashMessage::info("Your session has expired, please log in again").send();
+        return Ok(redirect_to_login(req));
+    }
+    session
+        .insert_last_active_at(now)
+        .map_err(internal_server_error)?;
+
+    let role = match session_id {
+        Some(session_id) => touch_user_session(session_id, user_id, &db_pool)
+            .await
+            .map_err(internal_server_error)?,
+        None => None,
+    };
+    match (session_id, role) {
+        (Some(session_id), Some(role)) => {
+            req.extensions_mut().insert(UserId(user_id));
+            req.extensions_mut().insert(SessionId(session_id));
+            req.extensions_mut().insert(role);
+            next.call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        }
+        _ => {
+            session.purge();
+            Ok(redirect_to_login(req))
         }
     }
 }
+
+/// Sends the user back to the login page with a successful response, since the session and flash
+/// message middlewares drop the changes made while handling a request that failed.
+fn redirect_to_login<B>(req: ServiceRequest) -> ServiceResponse<EitherBody<B>> {
+    req.into_response(see_other("/login")).map_into_right_body()
+}
+
+fn has_session_expired(
+    session: &TypedSession,
+    settings: &SessionSettings,
+    now: i64,
+) -> Result<bool, anyhow::Error> {
+    let logged_in_at = session.get_logged_in_at()?;
+    let last_active_at = session.get_last_active_at()?;
+    let (logged_in_at, last_active_at) = match (logged_in_at, last_active_at) {
+        (Some(logged_in_at), Some(last_active_at)) => (logged_in_at, last_active_at),
+        _ => return Ok(true),
+    };
+
+    Ok(now - last_active_at > settings.idle_timeout_seconds as i64
+        || now - logged_in_at > settings.max_lifetime_seconds as i64)
+}
+
+/// Keeps API tokens away from the pages that only make sense for a browser session, such as the
+/// ones managing passwords, sessions and the tokens themselves.
+pub async fn reject_api_tokens(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.extensions().contains::<ApiTokenScopes>() {
+        let response = HttpResponse::Forbidden().finish();
+        let e = anyhow::anyhow!("The page cannot be accessed with an API token");
+        return Err(InternalError::from_response(e, response).into());
+    }
+
+    next.call(req).await
+}
+
+pub async fn reject_non_publishers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require_permission(req, next, Permission::PublishNewsletters).await
+}
+
+pub async fn reject_non_user_managers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require_permission(req, next, Permission::ManageUsers).await
+}
+
+pub async fn reject_non_subscriber_managers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require_permission(req, next, Permission::ManageSubscribers).await
+}
+
+async fn require_permission(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+    permission: Permission,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if is_permitted(&req, permission) {
+        next.call(req).await
+    } else {
+        let response = HttpResponse::Forbidden().finish();
+        let e = anyhow::anyhow!("The user is not allowed to {}", permission);
+        Err(InternalError::from_response(e, response).into())
+    }
+}
+
+/// Checks the role of the authenticated user and, for requests made with an API token, the
+/// scopes granted to the token.
+pub fn is_permitted(req: &ServiceRequest, permission: Permission) -> bool {
+    let role = req.extensions().get::<Role>().copied();
+    let is_in_scope = match req.extensions().get::<ApiTokenScopes>() {
+        Some(scopes) => scopes.0.contains(&permission),
+        None => true,
+    };
+
+    matches!(role, Some(role) if role.has_permission(permission) && is_in_scope)
+}
@@ -1,5 +1,44 @@
+mod api_tokens;
+mod csrf;
+mod invitation;
 mod middleware;
 mod password;
+mod password_reset;
+mod role;
+mod sessions;
+mod throttle;
+mod two_factor;
+mod users;
 
-pub use middleware::{reject_anonymous_users, UserId};
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use api_tokens::{
+    authenticate_api_token, create_api_token, list_api_tokens, revoke_api_token, ApiToken,
+    ApiTokenScopes, AuthenticatedApiToken,
+};
+pub use csrf::{reject_invalid_csrf_tokens, CsrfToken};
+pub use invitation::InvitationToken;
+pub use middleware::{
+    authenticate_api_tokens, authenticate_bearer_token, is_permitted, reject_anonymous_users,
+    reject_api_tokens, reject_non_publishers, reject_non_subscriber_managers,
+    reject_non_user_managers, BearerAuthError, UserId,
+};
+pub use password::{
+    activate_invited_user, change_password, create_user, get_user_id, validate_credentials,
+    validate_password_length, AuthError, Credentials, DummyPasswordHash,
+};
+pub use password_reset::{
+    generate_reset_token, is_reset_token_valid, reset_password_with_token, store_reset_token,
+};
+pub use role::{Permission, Role};
+pub use sessions::{
+    create_user_session, list_user_sessions, revoke_user_session, revoke_user_sessions,
+    touch_user_session, SessionId, UserSession,
+};
+pub use throttle::{lockout_message, Lockout, LoginThrottle, ThrottleDecision};
+pub use two_factor::{
+    disable_two_factor, enable_two_factor, generate_totp_secret, is_two_factor_enabled, totp,
+    verify_second_factor, verify_totp_code, TotpCipher,
+};
+pub use users::{
+    create_pending_user, deactivate_user, delete_pending_user, get_active_user_email,
+    get_pending_username, list_users, set_user_role, User, UserStatus,
+};
@@ -1,13 +1,15 @@
 use anyhow::Context;
 use argon2::{
-    password_hash::SaltString, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier,
-    Version,
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
 };
 use secrecy::{ExposeSecret, Secret};
 use sqlx::MySqlPool;
 use uuid::Uuid;
 
-use crate::telemetry::spawn_blocking_with_tracing;
+use super::{revoke_user_sessions, Role};
+use crate::{configuration::PasswordHashSettings, telemetry::spawn_blocking_with_tracing};
+use tracing::Instrument;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -22,16 +24,47 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Load stored credentials", skip(username, db_pool))]
+pub fn validate_password_length(password: &Secret<String>) -> Result<(), String> {
+    let length = password.expose_secret().len();
+    if !(12..=128).contains(&length) {
+        return Err("Password must be between 12 and 128 characters long".to_owned());
+    }
+
+    Ok(())
+}
+
+/// The hash of a random password, computed with the configured parameters.
+///
+/// It is verified against when the username is unknown, so that the attempt takes as long as for
+/// an existing user and does not give away which usernames exist.
+#[derive(Clone)]
+pub struct DummyPasswordHash(Secret<String>);
+
+impl DummyPasswordHash {
+    pub fn new(password_hash_settings: &PasswordHashSettings) -> Result<Self, anyhow::Error> {
+        let params = password_hash_settings
+            .params()
+            .context("The configured password hashing parameters are invalid.")?;
+        let password = Secret::new(Uuid::new_v4().to_string());
+
+        compute_password_hash(password, params).map(Self)
+    }
+}
+
+#[tracing::instrument(
+    name = "Load stored credentials",
+    skip(username, dummy_password_hash, db_pool)
+)]
 async fn load_stored_credentials(
     username: &str,
+    dummy_password_hash: &DummyPasswordHash,
     db_pool: &MySqlPool,
 ) -> Result<(Option<Uuid>, Secret<String>), anyhow::Error> {
     let result = sqlx::query!(
         r#"
             SELECT `id`, `password_hash`
               FROM `users`
-             WHERE `username`=?
+             WHERE `username`=? AND `status`='active'
         "#,
         username,
     )
@@ -39,33 +72,29 @@ async fn load_stored_credentials(
     .await
     .context("Load stored credentials query failed.")?;
 
-    match result {
-        Some(row) => {
-            let uuid = Uuid::parse_str(&row.id)
+    match result.and_then(|row| {
+        row.password_hash
+            .map(|password_hash| (row.id, password_hash))
+    }) {
+        Some((id, password_hash)) => {
+            let uuid = Uuid::parse_str(&id)
                 .context("Failed to parse user UUID loaded from the database.")?;
-            let password = Secret::new(row.password_hash);
+            let password = Secret::new(password_hash);
             Ok((Some(uuid), password))
         }
-        _ => Ok((
-            None,
-            Secret::new(
-                "$argon2id$v=19$m=15000,t=2,p=1$\
-                gZiV/M1gPc22ElAH/Jh1Hw$\
-                CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-                    .to_string(),
-            ),
-        )),
+        _ => Ok((None, dummy_password_hash.0.clone())),
     }
 }
 
 #[tracing::instrument(
     name = "Verify password hash",
-    skip(expected_password_hash, password_candidate)
+    skip(expected_password_hash, password_candidate, params)
 )]
 fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
-) -> Result<(), AuthError> {
+    params: &Params,
+) -> Result<bool, AuthError> {
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse the PHC format string hash.")
         .map_err(AuthError::UnexpectedError)?;
@@ -76,37 +105,142 @@ fn verify_password_hash(
             &expected_password_hash,
         )
         .context("Invalid password")
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    Ok(is_weaker_than(&expected_password_hash, params))
+}
+
+/// Whether a stored hash was computed with a different algorithm or cheaper parameters than
+/// the ones currently configured.
+fn is_weaker_than(password_hash: &PasswordHash, params: &Params) -> bool {
+    if password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13 as u32)
+    {
+        return true;
+    }
+
+    match Params::try_from(password_hash) {
+        Ok(stored) => {
+            stored.m_cost() < params.m_cost()
+                || stored.t_cost() < params.t_cost()
+                || stored.p_cost() < params.p_cost()
+        }
+        Err(_) => true,
+    }
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, db_pool))]
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(credentials, password_hash_settings, dummy_password_hash, db_pool)
+)]
 pub async fn validate_credentials(
     credentials: Credentials,
+    password_hash_settings: &PasswordHashSettings,
+    dummy_password_hash: &DummyPasswordHash,
     db_pool: &MySqlPool,
 ) -> Result<Uuid, AuthError> {
-    let (user_id, expected_password_hash) = load_stored_credentials(&credentials.username, db_pool)
-        .await
-        .map_err(AuthError::UnexpectedError)?;
+    let (user_id, expected_password_hash) =
+        load_stored_credentials(&credentials.username, dummy_password_hash, db_pool)
+            .await
+            .map_err(AuthError::UnexpectedError)?;
+    let params = password_hash_settings
+        .params()
+        .context("The configured password hashing parameters are invalid.")?;
 
-    spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+    let password = credentials.password;
+    let password_candidate = password.clone();
+    let stored_password_hash = expected_password_hash.clone();
+    let verify_params = params.clone();
+    let needs_rehash = spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, password_candidate, &verify_params)
     })
     .await
     .context("Failed to spawn a blocking task for password hashing")
     .map_err(AuthError::InvalidCredentials)??;
 
-    user_id.ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))
+    let user_id = user_id
+        .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))?;
+
+    // Logging in must not wait for, nor fail because of, the upgrade
+    if needs_rehash {
+        tokio::spawn(
+            upgrade_password_hash(
+                user_id,
+                password,
+                stored_password_hash,
+                params,
+                db_pool.clone(),
+            )
+            .in_current_span(),
+        );
+    }
+
+    Ok(user_id)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, db_pool))]
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(password, stored_password_hash, params, db_pool)
+)]
+async fn upgrade_password_hash(
+    user_id: Uuid,
+    password: Secret<String>,
+    stored_password_hash: Secret<String>,
+    params: Params,
+    db_pool: MySqlPool,
+) {
+    let outcome: Result<(), anyhow::Error> = async {
+        let password_hash =
+            spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+                .await?
+                .context("Failed to hash password")?;
+
+        // The password may have been changed meanwhile, in which case the new hash wins
+        sqlx::query!(
+            r#"
+            UPDATE `users`
+               SET `password_hash` = ?
+             WHERE `id` = ? AND `password_hash` = ?
+            "#,
+            password_hash.expose_secret(),
+            user_id,
+            stored_password_hash.expose_secret(),
+        )
+        .execute(&db_pool)
+        .await
+        .context("Failed to execute query to upgrade the password hash.")?;
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = outcome {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to upgrade a password hash",
+        );
+    }
+}
+
+/// Changes the password of a user and revokes their sessions, except for the one to keep if any,
+/// since whoever knew the previous password may have logged in with it.
+#[tracing::instrument(
+    name = "Change password",
+    skip(password, password_hash_settings, db_pool)
+)]
 pub async fn change_password(
     user_id: Uuid,
     password: Secret<String>,
+    keep_session: Option<Uuid>,
+    password_hash_settings: &PasswordHashSettings,
     db_pool: &MySqlPool,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    let password_hash = hash_password(password, password_hash_settings).await?;
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
 
     sqlx::query!(
         r#"
@@ -117,22 +251,175 @@ pub async fn change_password(
         password_hash.expose_secret(),
         user_id,
     )
-    .execute(db_pool)
+    .execute(&mut transaction)
     .await
     .context("Failed to execute query to change the password.")?;
+    revoke_user_sessions(user_id, keep_session, &mut transaction).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the password change transaction.")?;
 
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
-    let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
+#[tracing::instrument(
+    name = "Activate invited user",
+    skip(password, password_hash_settings, db_pool)
+)]
+pub async fn activate_invited_user(
+    user_id: Uuid,
+    password: Secret<String>,
+    password_hash_settings: &PasswordHashSettings,
+    db_pool: &MySqlPool,
+) -> Result<bool, anyhow::Error> {
+    let params = password_hash_settings.params()?;
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE `users`
+           SET `password_hash` = ?, `status` = 'active'
+         WHERE `id` = ? AND `status` = 'pending'
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to activate an invited user.")?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[tracing::instrument(name = "Create user", skip(password, password_hash_settings, db_pool))]
+pub async fn create_user(
+    username: &str,
+    password: Secret<String>,
+    role: Role,
+    password_hash_settings: &PasswordHashSettings,
+    db_pool: &MySqlPool,
+) -> Result<Uuid, anyhow::Error> {
+    let params = password_hash_settings.params()?;
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
+    let user_id = Uuid::new_v4();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO `users` (`id`, `username`, `password_hash`, `role`, `status`)
+        VALUES (?, ?, ?, ?, 'active')
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        role.as_str(),
     )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to create a new user.")?;
+
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Get user id", skip(db_pool))]
+pub async fn get_user_id(
+    username: &str,
+    db_pool: &MySqlPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT `id` FROM `users` WHERE `username` = ?"#, username)
+        .fetch_optional(db_pool)
+        .await
+        .context("Failed to perform a query to retrieve a user id.")?;
+
+    row.map(|row| {
+        Uuid::parse_str(&row.id).context("Failed to parse user UUID loaded from the database.")
+    })
+    .transpose()
+}
+
+/// Hashes a password with the configured parameters, without blocking the async executor.
+pub(super) async fn hash_password(
+    password: Secret<String>,
+    password_hash_settings: &PasswordHashSettings,
+) -> Result<Secret<String>, anyhow::Error> {
+    let params = password_hash_settings.params()?;
+
+    spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+        .await?
+        .context("Failed to hash password")
+}
+
+fn compute_password_hash(
+    password: Secret<String>,
+    params: Params,
+) -> Result<Secret<String>, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
 
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod test {
+    use super::{is_weaker_than, DummyPasswordHash};
+    use crate::configuration::PasswordHashSettings;
+    use argon2::{
+        password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher, Version,
+    };
+    use secrecy::ExposeSecret;
+
+    fn hash_with(algorithm: Algorithm, params: Params) -> String {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        Argon2::new(algorithm, Version::V0x13, params)
+            .hash_password(b"password", &salt)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn dummy_hash_uses_the_configured_params() {
+        let settings = PasswordHashSettings {
+            memory_size_kib: 2048,
+            iterations: 3,
+            parallelism: 1,
+        };
+        let dummy_hash = DummyPasswordHash::new(&settings).unwrap();
+
+        let dummy_hash = PasswordHash::new(dummy_hash.0.expose_secret()).unwrap();
+        assert!(!is_weaker_than(&dummy_hash, &settings.params().unwrap()));
+        assert_eq!(2048, Params::try_from(&dummy_hash).unwrap().m_cost());
+    }
+
+    #[test]
+    fn hash_with_current_params_is_not_weaker() {
+        let params = Params::new(1024, 2, 1, None).unwrap();
+        let hash = hash_with(Algorithm::Argon2id, params.clone());
+
+        assert!(!is_weaker_than(&PasswordHash::new(&hash).unwrap(), &params));
+    }
+
+    #[test]
+    fn hash_with_cheaper_params_is_weaker() {
+        let params = Params::new(2048, 2, 1, None).unwrap();
+        let hash = hash_with(Algorithm::Argon2id, Params::new(1024, 2, 1, None).unwrap());
+
+        assert!(is_weaker_than(&PasswordHash::new(&hash).unwrap(), &params));
+    }
+
+    #[test]
+    fn hash_with_another_algorithm_is_weaker() {
+        let params = Params::new(1024, 2, 1, None).unwrap();
+        let hash = hash_with(Algorithm::Argon2i, params.clone());
+
+        assert!(is_weaker_than(&PasswordHash::new(&hash).unwrap(), &params));
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+use super::{password::hash_password, revoke_user_sessions};
+use crate::configuration::PasswordHashSettings;
+
+const RESET_TOKEN_VALIDITY_MINUTES: i64 = 60;
+
+pub fn generate_reset_token() -> String {
+    let rng = thread_rng();
+    rng.sample_iter(Alphanumeric)
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+// Only a hash of the token is stored, so a database leak doesn't hand out working reset links.
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[tracing::instrument(name = "Store password reset token", skip(token, db_pool))]
+pub async fn store_reset_token(
+    user_id: Uuid,
+    token: &str,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO `password_reset_tokens` (`token_hash`, `user_id`, `expires_at`)
+        VALUES (?, ?, ?)
+        "#,
+        hash_reset_token(token),
+        user_id,
+        Utc::now() + Duration::minutes(RESET_TOKEN_VALIDITY_MINUTES),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to store a password reset token.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Check password reset token", skip(token, db_pool))]
+pub async fn is_reset_token_valid(token: &str, db_pool: &MySqlPool) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT `user_id`
+          FROM `password_reset_tokens`
+         WHERE `token_hash` = ? AND `used_at` IS NULL AND `expires_at` > ?
+        "#,
+        hash_reset_token(token),
+        Utc::now(),
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to check a password reset token.")?;
+
+    Ok(row.is_some())
+}
+
+/// Marks a reset token as used and sets the new password of the user it was issued for,
+/// returning their id. Both happen in one transaction, so a failure leaves the token usable.
+///
+/// Returns `None` if the token is unknown, expired or has already been used.
+#[tracing::instrument(
+    name = "Reset password with a token",
+    skip(token, new_password, password_hash_settings, db_pool)
+)]
+pub async fn reset_password_with_token(
+    token: &str,
+    new_password: Secret<String>,
+    password_hash_settings: &PasswordHashSettings,
+    db_pool: &MySqlPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let token_hash = hash_reset_token(token);
+    // Hashed before locking the token, hashing is slow on purpose
+    let password_hash = hash_password(new_password, password_hash_settings).await?;
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    let row = sqlx::query!(
+        r#"
+        SELECT `user_id`
+          FROM `password_reset_tokens`
+         WHERE `token_hash` = ? AND `used_at` IS NULL AND `expires_at` > ?
+           FOR UPDATE
+        "#,
+        token_hash,
+        Utc::now(),
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to perform a query to retrieve a password reset token.")?;
+
+    let user_id = match row {
+        Some(row) => Uuid::parse_str(&row.user_id)
+            .context("Failed to parse user UUID loaded from the database.")?,
+        None => return Ok(None),
+    };
+
+    sqlx::query!(
+        r#"UPDATE `password_reset_tokens` SET `used_at` = ? WHERE `token_hash` = ?"#,
+        Utc::now(),
+        token_hash,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to mark a password reset token as used.")?;
+
+    sqlx::query!(
+        r#"UPDATE `users` SET `password_hash` = ? WHERE `id` = ?"#,
+        password_hash.expose_secret(),
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to change the password.")?;
+    revoke_user_sessions(user_id, None, &mut transaction).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the password reset token transaction.")?;
+
+    Ok(Some(user_id))
+}
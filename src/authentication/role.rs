@@ -0,0 +1,136 @@
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Permission {
+    PublishNewsletters,
+    ManageUsers,
+    ManageSubscribers,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    pub fn parse(role: &str) -> Result<Self, String> {
+        match role {
+            "owner" => Ok(Self::Owner),
+            "editor" => Ok(Self::Editor),
+            "viewer" => Ok(Self::Viewer),
+            other => Err(format!("{} is not a valid role", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Editor => "editor",
+            Self::Viewer => "viewer",
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match self {
+            Self::Owner => true,
+            Self::Editor => permission == Permission::PublishNewsletters,
+            Self::Viewer => false,
+        }
+    }
+}
+
+impl Permission {
+    pub const ALL: [Permission; 3] = [
+        Permission::PublishNewsletters,
+        Permission::ManageUsers,
+        Permission::ManageSubscribers,
+    ];
+
+    /// Parses the scope name under which the permission is granted to API tokens.
+    pub fn parse(scope: &str) -> Result<Self, String> {
+        match scope {
+            "newsletters:publish" => Ok(Self::PublishNewsletters),
+            "users:manage" => Ok(Self::ManageUsers),
+            "subscribers:manage" => Ok(Self::ManageSubscribers),
+            other => Err(format!("{} is not a valid scope", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PublishNewsletters => "newsletters:publish",
+            Self::ManageUsers => "users:manage",
+            Self::ManageSubscribers => "subscribers:manage",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PublishNewsletters => "publish newsletters",
+            Self::ManageUsers => "manage users",
+            Self::ManageSubscribers => "manage subscribers",
+        }
+        .fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Permission, Role};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn roles_can_be_parsed_back_from_their_string_representation() {
+        for role in Role::ALL {
+            assert_ok_eq!(Role::parse(role.as_str()), role);
+        }
+    }
+
+    #[test]
+    fn unknown_role_is_invalid() {
+        assert_err!(Role::parse("administrator"));
+    }
+
+    #[test]
+    fn permissions_can_be_parsed_back_from_their_scope_name() {
+        for permission in Permission::ALL {
+            assert_ok_eq!(Permission::parse(permission.as_str()), permission);
+        }
+    }
+
+    #[test]
+    fn unknown_scope_is_invalid() {
+        assert_err!(Permission::parse("subscribers:delete"));
+    }
+
+    #[test]
+    fn owner_has_every_permission() {
+        assert!(Role::Owner.has_permission(Permission::PublishNewsletters));
+        assert!(Role::Owner.has_permission(Permission::ManageUsers));
+        assert!(Role::Owner.has_permission(Permission::ManageSubscribers));
+    }
+
+    #[test]
+    fn editor_can_only_publish_newsletters() {
+        assert!(Role::Editor.has_permission(Permission::PublishNewsletters));
+        assert!(!Role::Editor.has_permission(Permission::ManageUsers));
+        assert!(!Role::Editor.has_permission(Permission::ManageSubscribers));
+    }
+
+    #[test]
+    fn viewer_has_no_permissions() {
+        assert!(!Role::Viewer.has_permission(Permission::PublishNewsletters));
+        assert!(!Role::Viewer.has_permission(Permission::ManageUsers));
+        assert!(!Role::Viewer.has_permission(Permission::ManageSubscribers));
+    }
+}
@@ -0,0 +1,177 @@
+use super::Role;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Executor, MySql, MySqlPool};
+use uuid::Uuid;
+
+const MAX_USER_AGENT_LENGTH: usize = 512;
+const LAST_SEEN_RESOLUTION_SECONDS: i64 = 60;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SessionId(pub Uuid);
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+pub struct UserSession {
+    pub session_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[tracing::instrument(name = "Create user session", skip(db_pool))]
+pub async fn create_user_session(
+    user_id: Uuid,
+    ip: Option<String>,
+    user_agent: Option<&str>,
+    db_pool: &MySqlPool,
+) -> Result<Uuid, anyhow::Error> {
+    let session_id = Uuid::new_v4();
+    let user_agent: Option<String> =
+        user_agent.map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+    sqlx::query!(
+        r#"
+        INSERT INTO `user_sessions` (`id`, `user_id`, `ip`, `user_agent`)
+        VALUES (?, ?, ?, ?)
+        "#,
+        session_id,
+        user_id,
+        ip,
+        user_agent,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to create a user session.")?;
+
+    Ok(session_id)
+}
+
+/// Loads the role of the user owning a session, if both the session and the user are active,
+/// and refreshes the last time the session has been seen.
+#[tracing::instrument(name = "Touch user session", skip(db_pool))]
+pub async fn touch_user_session(
+    session_id: Uuid,
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<Option<Role>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT `u`.`role`, `s`.`last_seen_at`
+          FROM `user_sessions` `s`
+          JOIN `users` `u` ON `u`.`id` = `s`.`user_id`
+         WHERE `s`.`id` = ? AND `s`.`user_id` = ?
+           AND `s`.`revoked_at` IS NULL AND `u`.`status` = 'active'
+        "#,
+        session_id,
+        user_id,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve a user session.")?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    // Only write when the last seen timestamp is stale, to avoid a write on every request
+    let now = Utc::now();
+    if now - row.last_seen_at > Duration::seconds(LAST_SEEN_RESOLUTION_SECONDS) {
+        sqlx::query!(
+            r#"UPDATE `user_sessions` SET `last_seen_at` = ? WHERE `id` = ?"#,
+            now,
+            session_id
+        )
+        .execute(db_pool)
+        .await
+        .context("Failed to execute query to refresh a user session.")?;
+    }
+
+    Role::parse(&row.role).map(Some).map_err(anyhow::Error::msg)
+}
+
+#[tracing::instrument(name = "List user sessions", skip(db_pool))]
+pub async fn list_user_sessions(
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<Vec<UserSession>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT `id`, `created_at`, `last_seen_at`, `ip`, `user_agent`
+          FROM `user_sessions`
+         WHERE `user_id` = ? AND `revoked_at` IS NULL
+         ORDER BY `last_seen_at` DESC
+        "#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to list user sessions.")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(UserSession {
+                session_id: Uuid::parse_str(&row.id)
+                    .context("Failed to parse session UUID loaded from the database.")?,
+                created_at: row.created_at,
+                last_seen_at: row.last_seen_at,
+                ip: row.ip,
+                user_agent: row.user_agent,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Revoke user session", skip(db_pool))]
+pub async fn revoke_user_session(
+    session_id: Uuid,
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE `user_sessions`
+           SET `revoked_at` = ?
+         WHERE `id` = ? AND `user_id` = ? AND `revoked_at` IS NULL
+        "#,
+        Utc::now(),
+        session_id,
+        user_id,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to revoke a user session.")?;
+
+    Ok(())
+}
+
+/// Revokes every session of a user, except for the one to keep if any.
+#[tracing::instrument(name = "Revoke all user sessions", skip(executor))]
+pub async fn revoke_user_sessions<'e>(
+    user_id: Uuid,
+    keep: Option<Uuid>,
+    executor: impl Executor<'e, Database = MySql>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE `user_sessions`
+           SET `revoked_at` = ?
+         WHERE `user_id` = ? AND `revoked_at` IS NULL AND (? IS NULL OR `id` <> ?)
+        "#,
+        Utc::now(),
+        user_id,
+        keep,
+        keep,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to execute query to revoke user sessions.")?;
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::configuration::LoginThrottleSettings;
+use anyhow::Context;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use secrecy::{ExposeSecret, Secret};
+use std::{net::IpAddr, time::Duration};
+
+/// What the login throttle decided about an upcoming login attempt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThrottleDecision {
+    /// The attempt may go ahead once the delay has elapsed.
+    Allowed { delay: Duration },
+    /// The account or address is locked out until the lockout expires.
+    LockedOut { retry_after: Duration },
+}
+
+/// The subject of a lockout triggered by a failed login attempt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lockout {
+    Account,
+    Address,
+}
+
+/// Tracks login attempts per account and failed ones per account and client address in Redis.
+///
+/// Every attempt since the last success makes the next one for the same account wait a bit
+/// longer, until the account or the address runs out of attempts and is locked out for a while.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    connection: ConnectionManager,
+    settings: LoginThrottleSettings,
+}
+
+impl LoginThrottle {
+    pub async fn new(
+        redis_uri: &Secret<String>,
+        settings: LoginThrottleSettings,
+    ) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(redis_uri.expose_secret().as_str())
+            .context("Failed to parse the Redis connection URI.")?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis.")?;
+
+        Ok(Self {
+            connection,
+            settings,
+        })
+    }
+
+    pub fn lockout_duration(&self) -> Duration {
+        Duration::from_secs(self.settings.lockout_seconds)
+    }
+
+    #[tracing::instrument(name = "Check login throttle", skip(self))]
+    pub async fn check(
+        &self,
+        account: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<ThrottleDecision, anyhow::Error> {
+        let mut connection = self.connection.clone();
+
+        let mut lockout_keys = vec![account_lockout_key(account)];
+        lockout_keys.extend(ip.map(address_lockout_key));
+        for key in lockout_keys {
+            let ttl: i64 = connection
+                .ttl(&key)
+                .await
+                .context("Failed to check a login lockout.")?;
+            if ttl > 0 {
+                return Ok(ThrottleDecision::LockedOut {
+                    retry_after: Duration::from_secs(ttl as u64),
+                });
+            }
+        }
+
+        // Counted atomically before the attempt is made, so that concurrent attempts each wait
+        // longer than the previous one instead of all reading the same count
+        let attempts = self.increment(&account_attempts_key(account)).await?;
+
+        Ok(ThrottleDecision::Allowed {
+            delay: self.delay_after(attempts.saturating_sub(1)),
+        })
+    }
+
+    /// Records a failed attempt, locking out the account or the address once they run out of
+    /// attempts. Both are counted before either is checked.
+    #[tracing::instrument(name = "Record failed login attempt", skip(self))]
+    pub async fn record_failure(
+        &self,
+        account: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<Vec<Lockout>, anyhow::Error> {
+        let account_failures = self.increment(&account_failures_key(account)).await?;
+        let address_failures = match ip {
+            Some(ip) => Some((ip, self.increment(&address_failures_key(ip)).await?)),
+            None => None,
+        };
+
+        let mut lockouts = Vec::new();
+        if account_failures >= self.settings.max_failures_per_account {
+            self.lock_out(
+                &account_lockout_key(account),
+                &[account_failures_key(account), account_attempts_key(account)],
+            )
+            .await?;
+            lockouts.push(Lockout::Account);
+        }
+        if let Some((ip, address_failures)) = address_failures {
+            if address_failures >= self.settings.max_failures_per_ip {
+                self.lock_out(&address_lockout_key(ip), &[address_failures_key(ip)])
+                    .await?;
+                lockouts.push(Lockout::Address);
+            }
+        }
+
+        Ok(lockouts)
+    }
+
+    #[tracing::instrument(name = "Record successful login attempt", skip(self))]
+    pub async fn record_success(&self, account: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .del::<_, ()>(&[account_failures_key(account), account_attempts_key(account)][..])
+            .await
+            .context("Failed to reset failed login attempts.")?;
+
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str) -> Result<u32, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let (failures,): (u32,) = redis::pipe()
+            .atomic()
+            .incr(key, 1)
+            .expire(key, self.settings.window_seconds as usize)
+            .ignore()
+            .query_async(&mut connection)
+            .await
+            .context("Failed to record a failed login attempt.")?;
+
+        Ok(failures)
+    }
+
+    async fn lock_out(
+        &self,
+        lockout_key: &str,
+        counter_keys: &[String],
+    ) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        redis::pipe()
+            .atomic()
+            .set_ex(lockout_key, 1, self.settings.lockout_seconds as usize)
+            .ignore()
+            .del(counter_keys)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await
+            .context("Failed to store a login lockout.")?;
+
+        Ok(())
+    }
+
+    fn delay_after(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::ZERO;
+        }
+
+        let delay = self
+            .settings
+            .base_delay_milliseconds
+            .saturating_mul(1u64 << (failures - 1).min(16));
+
+        Duration::from_millis(delay.min(self.settings.max_delay_milliseconds))
+    }
+}
+
+pub fn lockout_message(retry_after: Duration) -> String {
+    let minutes = (retry_after.as_secs() + 59) / 60;
+
+    format!(
+        "Too many failed login attempts, please try again in {} minute(s)",
+        minutes.max(1)
+    )
+}
+
+fn account_failures_key(account: &str) -> String {
+    format!("login_failures:account:{}", account.to_lowercase())
+}
+
+fn account_attempts_key(account: &str) -> String {
+    format!("login_attempts:account:{}", account.to_lowercase())
+}
+
+fn account_lockout_key(account: &str) -> String {
+    format!("login_lockout:account:{}", account.to_lowercase())
+}
+
+fn address_failures_key(ip: IpAddr) -> String {
+    format!("login_failures:ip:{}", ip)
+}
+
+fn address_lockout_key(ip: IpAddr) -> String {
+    format!("login_lockout:ip:{}", ip)
+}
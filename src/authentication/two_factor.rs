@@ -0,0 +1,393 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use anyhow::Context;
+use chrono::Utc;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret as TotpSecret, TOTP};
+use uuid::Uuid;
+
+const TOTP_ISSUER: &str = "zero2prod";
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Generates a new base32 encoded secret for a TOTP authenticator app.
+pub fn generate_totp_secret() -> Secret<String> {
+    Secret::new(TotpSecret::generate_secret().to_encoded().to_string())
+}
+
+/// Builds the TOTP for a base32 encoded secret, labelled with the account name in apps.
+pub fn totp(secret: &Secret<String>, account_name: &str) -> Result<TOTP, anyhow::Error> {
+    let secret = TotpSecret::Encoded(secret.expose_secret().clone())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("The TOTP secret is not valid base32: {:?}", e))?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(TOTP_ISSUER.into()),
+        account_name.into(),
+    )
+    .context("Failed to build a TOTP from the secret.")
+}
+
+/// Checks a code against the current time, allowing for some clock skew.
+///
+/// Returns the time step the code belongs to, so that it can be refused if it is presented again.
+pub fn verify_totp_code(secret: &Secret<String>, code: &str) -> Result<Option<u64>, anyhow::Error> {
+    let mut totp = totp(secret, "")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("The system clock is set before the UNIX epoch.")?
+        .as_secs();
+    let current_step = now / totp.step;
+    let skew = u64::from(totp.skew);
+    // Each step is checked on its own to find out which one the code belongs to
+    totp.skew = 0;
+
+    let code = code.trim();
+    Ok((current_step.saturating_sub(skew)..=current_step + skew)
+        .find(|step| totp.check(code, step * totp.step)))
+}
+
+/// Encrypts TOTP secrets stored in the database, with a key derived from the configured one.
+#[derive(Clone)]
+pub struct TotpCipher(Aes256Gcm);
+
+impl TotpCipher {
+    pub fn new(key: &Secret<String>) -> Self {
+        let key = Sha256::digest(key.expose_secret().as_bytes());
+
+        Self(Aes256Gcm::new(&key))
+    }
+
+    /// Returns the hex encoded nonce and ciphertext, separated by a dot.
+    fn encrypt(&self, secret: &Secret<String>) -> Result<String, anyhow::Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, secret.expose_secret().as_bytes())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt the TOTP secret."))?;
+
+        Ok(format!(
+            "{}.{}",
+            hex::encode(nonce),
+            hex::encode(ciphertext)
+        ))
+    }
+
+    fn decrypt(&self, encrypted_secret: &str) -> Result<Secret<String>, anyhow::Error> {
+        let (nonce, ciphertext) = encrypted_secret
+            .split_once('.')
+            .context("The stored TOTP secret is not encrypted.")?;
+        let nonce = hex::decode(nonce).context("The stored TOTP nonce is not valid hex.")?;
+        let ciphertext =
+            hex::decode(ciphertext).context("The stored TOTP secret is not valid hex.")?;
+        if nonce.len() != 12 {
+            anyhow::bail!("The stored TOTP nonce has the wrong length.");
+        }
+
+        let secret = self
+            .0
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt the TOTP secret."))?;
+
+        String::from_utf8(secret)
+            .map(Secret::new)
+            .context("The decrypted TOTP secret is not valid UTF-8.")
+    }
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(normalize_recovery_code(code).as_bytes()))
+}
+
+fn generate_recovery_code() -> String {
+    let code: String = thread_rng()
+        .sample_iter(Alphanumeric)
+        .map(|c| char::from(c).to_ascii_lowercase())
+        .take(RECOVERY_CODE_LENGTH)
+        .collect();
+    let (head, tail) = code.split_at(RECOVERY_CODE_LENGTH / 2);
+
+    format!("{}-{}", head, tail)
+}
+
+#[tracing::instrument(
+    name = "Check whether two-factor authentication is enabled",
+    skip(db_pool)
+)]
+pub async fn is_two_factor_enabled(
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT `totp_secret` FROM `users` WHERE `id` = ?"#,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to check for a TOTP secret.")?;
+
+    Ok(row.and_then(|row| row.totp_secret).is_some())
+}
+
+/// Stores the TOTP secret of a user, encrypted, and replaces their recovery codes.
+///
+/// The step of the code used to enrol is recorded as used. The recovery codes are only returned
+/// here, the database keeps their hashes.
+#[tracing::instrument(
+    name = "Enable two-factor authentication",
+    skip(secret, cipher, db_pool)
+)]
+pub async fn enable_two_factor(
+    user_id: Uuid,
+    secret: &Secret<String>,
+    used_step: u64,
+    cipher: &TotpCipher,
+    db_pool: &MySqlPool,
+) -> Result<Vec<String>, anyhow::Error> {
+    let encrypted_secret = cipher.encrypt(secret)?;
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    sqlx::query!(
+        r#"UPDATE `users` SET `totp_secret` = ?, `totp_last_step` = ? WHERE `id` = ?"#,
+        encrypted_secret,
+        used_step,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to store the TOTP secret.")?;
+
+    sqlx::query!(
+        r#"DELETE FROM `user_recovery_codes` WHERE `user_id` = ?"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to delete old recovery codes.")?;
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    for code in &recovery_codes {
+        sqlx::query!(
+            r#"INSERT INTO `user_recovery_codes` (`user_id`, `code_hash`) VALUES (?, ?)"#,
+            user_id,
+            hash_recovery_code(code),
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to execute query to store a recovery code.")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the two-factor enrolment transaction.")?;
+
+    Ok(recovery_codes)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(db_pool))]
+pub async fn disable_two_factor(user_id: Uuid, db_pool: &MySqlPool) -> Result<(), anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    sqlx::query!(
+        r#"UPDATE `users` SET `totp_secret` = NULL, `totp_last_step` = NULL WHERE `id` = ?"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to remove the TOTP secret.")?;
+
+    sqlx::query!(
+        r#"DELETE FROM `user_recovery_codes` WHERE `user_id` = ?"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to delete recovery codes.")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the two-factor removal transaction.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Consume recovery code", skip(code, db_pool))]
+async fn consume_recovery_code(
+    user_id: Uuid,
+    code: &str,
+    db_pool: &MySqlPool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE `user_recovery_codes`
+           SET `used_at` = ?
+         WHERE `user_id` = ? AND `code_hash` = ? AND `used_at` IS NULL
+        "#,
+        Utc::now(),
+        user_id,
+        hash_recovery_code(code),
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to consume a recovery code.")?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Records the step of an accepted TOTP code, unless it is not newer than the last one used.
+#[tracing::instrument(name = "Consume TOTP step", skip(db_pool))]
+async fn consume_totp_step(
+    user_id: Uuid,
+    step: u64,
+    db_pool: &MySqlPool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE `users`
+           SET `totp_last_step` = ?
+         WHERE `id` = ? AND (`totp_last_step` IS NULL OR `totp_last_step` < ?)
+        "#,
+        step,
+        user_id,
+        step,
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to record a used TOTP step.")?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Checks the second factor of a user, either a TOTP code or one of their recovery codes.
+///
+/// A TOTP code is only accepted once, as are the codes of earlier time steps.
+#[tracing::instrument(name = "Verify second factor", skip(code, cipher, db_pool))]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: &str,
+    cipher: &TotpCipher,
+    db_pool: &MySqlPool,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT `totp_secret` FROM `users` WHERE `id` = ?"#,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve the TOTP secret.")?;
+    let secret = match row.and_then(|row| row.totp_secret) {
+        Some(encrypted_secret) => cipher.decrypt(&encrypted_secret)?,
+        None => return Ok(false),
+    };
+
+    if let Some(step) = verify_totp_code(&secret, code)? {
+        return consume_totp_step(user_id, step, db_pool).await;
+    }
+
+    consume_recovery_code(user_id, code, db_pool).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        generate_recovery_code, generate_totp_secret, hash_recovery_code, totp, verify_totp_code,
+        TotpCipher,
+    };
+    use claims::{assert_err, assert_none, assert_ok, assert_some};
+    use secrecy::{ExposeSecret, Secret};
+
+    #[test]
+    fn current_code_is_accepted() {
+        let secret = generate_totp_secret();
+        let code = assert_ok!(assert_ok!(totp(&secret, "ursula")).generate_current());
+
+        assert_some!(assert_ok!(verify_totp_code(&secret, &code)));
+    }
+
+    #[test]
+    fn codes_of_neighbouring_steps_are_told_apart() {
+        let secret = generate_totp_secret();
+        let totp = assert_ok!(totp(&secret, "ursula"));
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let next_code = totp.generate(now + totp.step);
+
+        // There is a one in a million chance that both steps share the same code
+        if next_code != assert_ok!(totp.generate_current()) {
+            let step = assert_some!(assert_ok!(verify_totp_code(&secret, &next_code)));
+            assert_eq!(now / totp.step + 1, step);
+        }
+    }
+
+    #[test]
+    fn totp_secrets_round_trip_through_encryption() {
+        let cipher = TotpCipher::new(&Secret::new("an encryption key".to_owned()));
+        let secret = generate_totp_secret();
+
+        let encrypted_secret = assert_ok!(cipher.encrypt(&secret));
+        assert!(!encrypted_secret.contains(secret.expose_secret().as_str()));
+        let decrypted_secret = assert_ok!(cipher.decrypt(&encrypted_secret));
+        assert_eq!(secret.expose_secret(), decrypted_secret.expose_secret());
+    }
+
+    #[test]
+    fn totp_secrets_cannot_be_decrypted_with_another_key() {
+        let cipher = TotpCipher::new(&Secret::new("an encryption key".to_owned()));
+        let other_cipher = TotpCipher::new(&Secret::new("another encryption key".to_owned()));
+
+        let encrypted_secret = assert_ok!(cipher.encrypt(&generate_totp_secret()));
+        assert_err!(other_cipher.decrypt(&encrypted_secret));
+    }
+
+    #[test]
+    fn code_for_another_secret_is_rejected() {
+        let secret = generate_totp_secret();
+        let code =
+            assert_ok!(assert_ok!(totp(&generate_totp_secret(), "ursula")).generate_current());
+
+        // There is a one in a million chance that both secrets share the current code
+        if code != assert_ok!(assert_ok!(totp(&secret, "ursula")).generate_current()) {
+            assert_none!(assert_ok!(verify_totp_code(&secret, &code)));
+        }
+    }
+
+    #[test]
+    fn recovery_codes_are_matched_regardless_of_case_and_dashes() {
+        let code = generate_recovery_code();
+
+        assert_eq!(
+            hash_recovery_code(&code),
+            hash_recovery_code(&code.to_uppercase().replace('-', ""))
+        );
+    }
+}
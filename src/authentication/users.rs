@@ -0,0 +1,191 @@
+use super::Role;
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use sqlx::{mysql::MySqlDatabaseError, MySqlPool};
+use uuid::Uuid;
+
+/// MySQL error number of a unique key violation.
+const DUPLICATE_ENTRY: u16 = 1062;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UserStatus {
+    Pending,
+    Active,
+    Deactivated,
+}
+
+impl UserStatus {
+    pub fn parse(status: &str) -> Result<Self, String> {
+        match status {
+            "pending" => Ok(Self::Pending),
+            "active" => Ok(Self::Active),
+            "deactivated" => Ok(Self::Deactivated),
+            other => Err(format!("{} is not a valid user status", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Active => "active",
+            Self::Deactivated => "deactivated",
+        }
+    }
+}
+
+pub struct User {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub status: UserStatus,
+}
+
+#[tracing::instrument(name = "Get the email of an active user", skip(db_pool))]
+pub async fn get_active_user_email(
+    username: &str,
+    db_pool: &MySqlPool,
+) -> Result<Option<(Uuid, SubscriberEmail)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT `id`, `email`
+          FROM `users`
+         WHERE `username` = ? AND `status` = 'active' AND `email` IS NOT NULL
+        "#,
+        username
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve the email of a user.")?;
+
+    let (id, email) = match row.and_then(|row| row.email.map(|email| (row.id, email))) {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let user_id =
+        Uuid::parse_str(&id).context("Failed to parse user UUID loaded from the database.")?;
+    let email = SubscriberEmail::parse(&email).map_err(anyhow::Error::msg)?;
+
+    Ok(Some((user_id, email)))
+}
+
+/// Returns `None` if the username is already taken, which can happen even after checking it since
+/// two invitations can be sent concurrently.
+#[tracing::instrument(name = "Create a pending user", skip(email, db_pool))]
+pub async fn create_pending_user(
+    username: &str,
+    email: &SubscriberEmail,
+    role: Role,
+    db_pool: &MySqlPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let user_id = Uuid::new_v4();
+
+    let outcome = sqlx::query!(
+        r#"
+        INSERT INTO `users` (`id`, `username`, `email`, `role`, `status`)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+        user_id,
+        username,
+        email.as_ref(),
+        role.as_str(),
+        UserStatus::Pending.as_str(),
+    )
+    .execute(db_pool)
+    .await;
+
+    match outcome {
+        Ok(_) => Ok(Some(user_id)),
+        Err(sqlx::Error::Database(e))
+            if e.try_downcast_ref::<MySqlDatabaseError>()
+                .map(MySqlDatabaseError::number)
+                == Some(DUPLICATE_ENTRY) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e).context("Failed to execute query to create a pending user."),
+    }
+}
+
+/// Removes a user whose invitation could not be sent, so that they can be invited again.
+#[tracing::instrument(name = "Delete pending user", skip(db_pool))]
+pub async fn delete_pending_user(user_id: Uuid, db_pool: &MySqlPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM `users` WHERE `id` = ? AND `status` = 'pending'"#,
+        user_id
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to delete a pending user.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get username of a pending user", skip(db_pool))]
+pub async fn get_pending_username(
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT `username` FROM `users` WHERE `id` = ? AND `status` = 'pending'"#,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve a pending user.")?;
+
+    Ok(row.map(|row| row.username))
+}
+
+#[tracing::instrument(name = "List users", skip(db_pool))]
+pub async fn list_users(db_pool: &MySqlPool) -> Result<Vec<User>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT `id`, `username`, `role`, `status` FROM `users` ORDER BY `username`"#
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to list users.")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(User {
+                user_id: Uuid::parse_str(&row.id)
+                    .context("Failed to parse user UUID loaded from the database.")?,
+                username: row.username,
+                role: Role::parse(&row.role).map_err(anyhow::Error::msg)?,
+                status: UserStatus::parse(&row.status).map_err(anyhow::Error::msg)?,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Change user role", skip(db_pool))]
+pub async fn set_user_role(
+    user_id: Uuid,
+    role: Role,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE `users` SET `role` = ? WHERE `id` = ?"#,
+        role.as_str(),
+        user_id
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to change the user role.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Deactivate user", skip(db_pool))]
+pub async fn deactivate_user(user_id: Uuid, db_pool: &MySqlPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE `users` SET `status` = ? WHERE `id` = ?"#,
+        UserStatus::Deactivated.as_str(),
+        user_id
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to execute query to deactivate the user.")?;
+
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use crate::{
+    authentication::{change_password, create_user, get_user_id, validate_password_length, Role},
+    configuration::Settings,
+    domain::{SubscriberEmail, SubscriptionStatus},
+    exports::{self, ExportFormat},
+    issue_delivery_worker::requeue_failed_deliveries,
+    migrations::run_migrations,
+    startup::get_connection_pool,
+    subscriber_import::{self, ImportOptions},
+    subscribers::SubscriberFilter,
+};
+use actix_web::web::Bytes;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, TryStreamExt};
+use secrecy::Secret;
+use sqlx::MySqlPool;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+use uuid::{fmt::Hyphenated, Uuid};
+
+pub async fn migrate(configuration: &Settings) -> Result<(), anyhow::Error> {
+    let db_pool = get_connection_pool(&configuration.database);
+
+    run_migrations(&db_pool).await?;
+
+    println!("Database migrations have been applied");
+
+    Ok(())
+}
+
+pub async fn create_admin(
+    configuration: &Settings,
+    username: &str,
+    password: Secret<String>,
+) -> Result<(), anyhow::Error> {
+    validate_password_length(&password).map_err(anyhow::Error::msg)?;
+
+    let db_pool = get_connection_pool(&configuration.database);
+    let user_id = create_user(
+        username,
+        password,
+        Role::Owner,
+        &configuration.application.password_hash,
+        &db_pool,
+    )
+    .await?;
+
+    println!("Created admin user `{}` with id {}", username, user_id);
+
+    Ok(())
+}
+
+pub async fn reset_password(
+    configuration: &Settings,
+    username: &str,
+    password: Secret<String>,
+) -> Result<(), anyhow::Error> {
+    validate_password_length(&password).map_err(anyhow::Error::msg)?;
+
+    let db_pool = get_connection_pool(&configuration.database);
+    let user_id = get_user_id(username, &db_pool)
+        .await?
+        .with_context(|| format!("There is no user named `{}`", username))?;
+    change_password(
+        user_id,
+        password,
+        None,
+        &configuration.application.password_hash,
+        &db_pool,
+    )
+    .await?;
+
+    println!(
+        "The password of `{}` has been reset and their sessions revoked",
+        username
+    );
+
+    Ok(())
+}
+
+pub async fn send_test_email(
+    configuration: &Settings,
+    recipient: &str,
+) -> Result<(), anyhow::Error> {
+    let recipient = SubscriberEmail::parse(recipient).map_err(anyhow::Error::msg)?;
+    let email_client = configuration.email.clone().client();
+
+    email_client
+        .send_email(
+            &recipient,
+            "Test email",
+            "<p>This is a test email sent from the <code>zero2prod</code> command line.</p>",
+            "This is a test email sent from the zero2prod command line.",
+        )
+        .await
+        .context("Failed to send the test email")?;
+
+    println!("A test email has been sent to {}", recipient);
+
+    Ok(())
+}
+
+pub async fn requeue_failed(
+    configuration: &Settings,
+    newsletter_issue_id: Option<Uuid>,
+) -> Result<(), anyhow::Error> {
+    let db_pool = get_connection_pool(&configuration.database);
+    let requeued_tasks = requeue_failed_deliveries(&db_pool, newsletter_issue_id)
+        .await
+        .context("Failed to requeue failed deliveries")?;
+
+    println!("Requeued {} failed deliveries", requeued_tasks);
+
+    Ok(())
+}
+
+struct IssueSummary {
+    newsletter_issue_id: Hyphenated,
+    title: String,
+    published_at: DateTime<Utc>,
+    pending: i64,
+    delivered: i64,
+    failed: i64,
+}
+
+async fn get_issue_summaries(db_pool: &MySqlPool) -> Result<Vec<IssueSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueSummary,
+        r#"SELECT `i`.`newsletter_issue_id` AS "newsletter_issue_id: Hyphenated",
+                  `i`.`title`,
+                  `i`.`published_at` AS "published_at!",
+                  (SELECT COUNT(*) FROM `issue_delivery_queue` `q`
+                    WHERE `q`.`newsletter_issue_id` = `i`.`newsletter_issue_id`) AS "pending!",
+                  (SELECT COUNT(*) FROM `issue_delivery_outcomes` `o`
+                    WHERE `o`.`newsletter_issue_id` = `i`.`newsletter_issue_id`
+                      AND `o`.`outcome` = 'delivered') AS "delivered!",
+                  (SELECT COUNT(*) FROM `issue_delivery_outcomes` `o`
+                    WHERE `o`.`newsletter_issue_id` = `i`.`newsletter_issue_id`
+                      AND `o`.`outcome` = 'failed') AS "failed!"
+             FROM `newsletter_issues` `i`
+            WHERE `i`.`published_at` IS NOT NULL
+            ORDER BY `i`.`published_at` DESC"#
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn list_issues(configuration: &Settings) -> Result<(), anyhow::Error> {
+    let db_pool = get_connection_pool(&configuration.database);
+    let issues = get_issue_summaries(&db_pool)
+        .await
+        .context("Failed to load newsletter issues")?;
+
+    println!(
+        "{:<36}  {:<20}  {:>8}  {:>9}  {:>6}  TITLE",
+        "ID", "PUBLISHED AT", "PENDING", "DELIVERED", "FAILED"
+    );
+    for issue in issues {
+        println!(
+            "{:<36}  {:<20}  {:>8}  {:>9}  {:>6}  {}",
+            issue.newsletter_issue_id,
+            issue.published_at.format("%Y-%m-%d %H:%M:%S"),
+            issue.pending,
+            issue.delivered,
+            issue.failed,
+            issue.title,
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn import_subscribers(
+    configuration: &Settings,
+    file: &Path,
+    mark_as_confirmed: bool,
+    report: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let db_pool = get_connection_pool(&configuration.database);
+    let reader = File::open(file)
+        .with_context(|| format!("Failed to open `{}`", file.display()))
+        .map(BufReader::new)?;
+    let outcome = subscriber_import::import_subscribers(
+        reader,
+        &ImportOptions { mark_as_confirmed },
+        &db_pool,
+    )
+    .await?;
+
+    println!(
+        "{} subscriber(s) imported, {} row(s) rejected",
+        outcome.imported,
+        outcome.rejected.len()
+    );
+    match report {
+        Some(report) if !outcome.rejected.is_empty() => {
+            std::fs::write(report, outcome.rejected_rows_csv()?)
+                .with_context(|| format!("Failed to write `{}`", report.display()))?;
+            println!(
+                "The rejected rows have been written to {}",
+                report.display()
+            );
+        }
+        _ => {
+            for row in &outcome.rejected {
+                println!("line {}: {}", row.line, row.reason);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn export_subscribers(
+    configuration: &Settings,
+    format: ExportFormat,
+    status: Option<SubscriptionStatus>,
+    output: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let db_pool = get_connection_pool(&configuration.database);
+    let filter = SubscriberFilter {
+        status,
+        ..Default::default()
+    };
+
+    write_export(exports::export_subscribers(filter, format, db_pool), output).await
+}
+
+pub async fn export_issue_outcomes(
+    configuration: &Settings,
+    issue_id: Uuid,
+    format: ExportFormat,
+    output: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let db_pool = get_connection_pool(&configuration.database);
+
+    write_export(
+        exports::export_delivery_outcomes(issue_id, format, db_pool),
+        output,
+    )
+    .await
+}
+
+/// Writes the export chunk by chunk to the output file, or to stdout.
+async fn write_export(
+    export: impl Stream<Item = Result<Bytes, anyhow::Error>>,
+    output: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+    let mut writer: Box<dyn Write> = match output {
+        Some(output) => {
+            let file = File::create(output)
+                .with_context(|| format!("Failed to create `{}`", output.display()))?;
+            Box::new(BufWriter::new(file))
+        }
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    let mut export = std::pin::pin!(export);
+    while let Some(chunk) = export.try_next().await? {
+        writer
+            .write_all(&chunk)
+            .context("Failed to write the export")?;
+    }
+    writer.flush().context("Failed to write the export")?;
+
+    if let Some(output) = output {
+        eprintln!("The export has been written to {}", output.display());
+    }
+
+    Ok(())
+}
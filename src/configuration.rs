@@ -1,11 +1,17 @@
 use std::time::Duration;
 
+use argon2::Params;
 use config::{Config, ConfigError, File, FileFormat};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::{mysql::MySqlConnectOptions, ConnectOptions};
 use tracing::log::LevelFilter;
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    rate_limiter::RateLimiter,
+    webhooks::{WebhookClient, WebhookEvent},
+};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
@@ -13,6 +19,9 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub worker: WorkerSettings,
+    #[serde(default)]
+    pub webhooks: WebhookSettings,
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -22,6 +31,8 @@ pub struct DatabaseSettings {
     pub port: u16,
     pub host: String,
     pub database_name: String,
+    #[serde(default)]
+    pub migrate_on_startup: bool,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -30,6 +41,46 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Encrypts the TOTP secrets of users at rest
+    pub totp_encryption_key: Secret<String>,
+    pub shutdown_timeout_seconds: u64,
+    pub login_throttle: LoginThrottleSettings,
+    pub password_hash: PasswordHashSettings,
+    pub session: SessionSettings,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SessionSettings {
+    pub idle_timeout_seconds: u64,
+    pub max_lifetime_seconds: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordHashSettings {
+    pub memory_size_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordHashSettings {
+    pub fn params(&self) -> Result<Params, argon2::Error> {
+        Params::new(
+            self.memory_size_kib,
+            self.iterations,
+            self.parallelism,
+            None,
+        )
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct LoginThrottleSettings {
+    pub window_seconds: u64,
+    pub max_failures_per_account: u32,
+    pub max_failures_per_ip: u32,
+    pub lockout_seconds: u64,
+    pub base_delay_milliseconds: u64,
+    pub max_delay_milliseconds: u64,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -40,6 +91,102 @@ pub struct EmailClientSettings {
     pub timeout_milliseconds: u64,
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct WorkerSettings {
+    pub host: String,
+    pub port: u16,
+    pub n_workers: usize,
+    pub poll_interval_milliseconds: u64,
+    pub error_backoff_milliseconds: u64,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+}
+
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct RateLimitSettings {
+    pub messages_per_second: Option<f64>,
+    #[serde(default)]
+    pub domains: Vec<DomainRateLimitSettings>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct DomainRateLimitSettings {
+    pub domain: String,
+    pub messages_per_second: f64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookSettings {
+    pub timeout_milliseconds: u64,
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after each failed attempt
+    pub retry_delay_seconds: u64,
+    /// Delivered and failed deliveries are deleted this long after their creation
+    pub retention_days: u64,
+    #[serde(default)]
+    pub endpoints: Vec<WebhookEndpointSettings>,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            timeout_milliseconds: 10000,
+            max_attempts: 8,
+            retry_delay_seconds: 60,
+            retention_days: 30,
+            endpoints: Vec::new(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookEndpointSettings {
+    pub url: String,
+    /// Key used to sign the payloads sent to this endpoint
+    pub secret: Secret<String>,
+    /// Events sent to this endpoint, all of them when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+impl WebhookEndpointSettings {
+    pub fn receives(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+impl WebhookSettings {
+    pub fn client(self) -> WebhookClient {
+        WebhookClient::new(self)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    pub fn retry_delay(&self) -> Duration {
+        Duration::from_secs(self.retry_delay_seconds)
+    }
+
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_days * 24 * 60 * 60)
+    }
+}
+
+impl WorkerSettings {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_milliseconds)
+    }
+
+    pub fn error_backoff(&self) -> Duration {
+        Duration::from_millis(self.error_backoff_milliseconds)
+    }
+
+    pub fn rate_limiter(&self) -> RateLimiter {
+        RateLimiter::new(&self.rate_limit)
+    }
+}
+
 impl EmailClientSettings {
     pub fn client(self) -> EmailClient {
         let sender_email = self.sender().expect("Invalid sender email address");
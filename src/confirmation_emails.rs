@@ -0,0 +1,290 @@
+use crate::{
+    configuration::WorkerSettings,
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    email_client::{EmailClient, SendEmailError},
+    issue_delivery_worker::ExecutionOutcome,
+    rate_limiter::RateLimiter,
+    shutdown::ShutdownListener,
+    utils::exponential_backoff,
+};
+use sqlx::{MySql, MySqlPool, Transaction};
+use std::{sync::Arc, time::Duration};
+use tracing::{field::display, Span};
+use uuid::{fmt::Hyphenated, Uuid};
+
+/// Attempts made before giving up on a confirmation email, which can still be resent from the
+/// admin pages.
+const MAX_ATTEMPTS: i32 = 8;
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// A claimed email is given back to the queue if its outcome is not recorded within this delay,
+/// e.g. because the worker crashed while sending it.
+const CLAIM_DURATION: Duration = Duration::from_secs(5 * 60);
+
+/// Queues the confirmation email of a new subscriber in the transaction storing them, so that
+/// the email is sent if and only if the subscription is committed.
+#[tracing::instrument(skip(transaction, subscription_token))]
+pub async fn enqueue_confirmation_email(
+    transaction: &mut Transaction<'_, MySql>,
+    subscriber_id: Uuid,
+    subscription_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO `confirmation_email_outbox` (`subscription_token`, `subscriber_id`)
+           VALUES (?, ?)"#,
+        subscription_token,
+        subscriber_id,
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Send a confirmation email to a new subscriber",
+    skip(email_client, new_subscriber, base_url, subscription_token)
+)]
+pub async fn send_confirmation_email(
+    email_client: &EmailClient,
+    new_subscriber: NewSubscriber,
+    base_url: &str,
+    subscription_token: &str,
+) -> Result<(), SendEmailError> {
+    let confirmation_link = format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token,
+    );
+    let plain_body = format!(
+        "Welcome to our newsletter\nVisit {} to confirm your subscription.",
+        confirmation_link
+    );
+    let html_body = format!(
+        "Welcome to our newsletter!<br />\
+                Click <a href=\"{}\">here</a> to confirm your subscription.",
+        confirmation_link
+    );
+    email_client
+        .send_email(&new_subscriber.email, "Welcome", &html_body, &plain_body)
+        .await?;
+
+    Ok(())
+}
+
+struct OutboxItem {
+    subscription_token: String,
+    subscriber_id: Hyphenated,
+    n_attempts: i32,
+}
+
+/// Sends the oldest confirmation email that is due, if any. The email is claimed in a short
+/// transaction so that no lock is held while waiting for the email service.
+#[tracing::instrument(skip_all, fields(subscriber_id=tracing::field::Empty))]
+pub async fn try_send_confirmation_email(
+    db_pool: &MySqlPool,
+    email_client: &EmailClient,
+    rate_limiter: &RateLimiter,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    if let Some(wait_time) = rate_limiter.global_wait_time() {
+        return Ok(ExecutionOutcome::TaskPostponed(wait_time));
+    }
+    let Some(item) = claim_confirmation_email(db_pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    let subscriber_id: Uuid = item.subscriber_id.into();
+    Span::current().record("subscriber_id", &display(subscriber_id));
+
+    let subscriber = sqlx::query!(
+        r#"SELECT `email`, `name` FROM `subscriptions`
+            WHERE `id` = ? AND `status` = 'pending_confirmation'"#,
+        subscriber_id,
+    )
+    .fetch_optional(db_pool)
+    .await?;
+    let new_subscriber = match subscriber {
+        Some(subscriber) => match (
+            SubscriberEmail::parse(&subscriber.email),
+            SubscriberName::parse(&subscriber.name),
+        ) {
+            (Ok(email), Ok(name)) => Some(NewSubscriber { email, name }),
+            _ => {
+                tracing::error!(
+                    "Dropping the confirmation email of a subscriber whose stored details are invalid"
+                );
+                None
+            }
+        },
+        // Confirmed or removed in the meantime, there is nothing left to confirm
+        None => None,
+    };
+
+    if let Some(new_subscriber) = new_subscriber {
+        if let Err(wait_time) = rate_limiter.try_acquire_for(&new_subscriber.email) {
+            release_confirmation_email(db_pool, &item).await?;
+
+            return Ok(ExecutionOutcome::TaskPostponed(wait_time));
+        }
+        match send_confirmation_email(
+            email_client,
+            new_subscriber,
+            base_url,
+            &item.subscription_token,
+        )
+        .await
+        {
+            Ok(()) => {}
+            Err(SendEmailError::RateLimited { retry_after }) => {
+                tracing::warn!(
+                    ?retry_after,
+                    "The email delivery service is throttling us. \
+                    Postponing the confirmation email."
+                );
+                rate_limiter.pause(retry_after);
+                release_confirmation_email(db_pool, &item).await?;
+
+                return Ok(ExecutionOutcome::TaskPostponed(Duration::ZERO));
+            }
+            Err(e) => {
+                if item.n_attempts < MAX_ATTEMPTS {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        n_attempts = item.n_attempts,
+                        "Failed to send a confirmation email. Retrying later."
+                    );
+                    let retry_delay =
+                        exponential_backoff(RETRY_DELAY, item.n_attempts as u32, MAX_RETRY_DELAY);
+                    sqlx::query!(
+                        r#"UPDATE `confirmation_email_outbox`
+                              SET `last_error` = ?,
+                                  `next_attempt_at` = CURRENT_TIMESTAMP() + INTERVAL ? SECOND
+                            WHERE `subscription_token` = ? AND `n_attempts` = ?"#,
+                        e.to_string(),
+                        retry_delay.as_secs(),
+                        item.subscription_token,
+                        item.n_attempts,
+                    )
+                    .execute(db_pool)
+                    .await?;
+
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
+
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    n_attempts = item.n_attempts,
+                    "Giving up on a confirmation email."
+                );
+            }
+        }
+    }
+
+    sqlx::query!(
+        r#"DELETE FROM `confirmation_email_outbox` WHERE `subscription_token` = ?"#,
+        item.subscription_token,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Counts an attempt and postpones the email until it is over, committing right away. The
+/// returned item includes the attempt being made.
+#[tracing::instrument(skip_all)]
+async fn claim_confirmation_email(
+    db_pool: &MySqlPool,
+) -> Result<Option<OutboxItem>, anyhow::Error> {
+    let mut transaction = db_pool.begin().await?;
+    let Some(item) = sqlx::query_as!(
+        OutboxItem,
+        r#"SELECT `subscription_token`, `subscriber_id` AS "subscriber_id: Hyphenated",
+                  `n_attempts`
+             FROM `confirmation_email_outbox`
+            WHERE `next_attempt_at` <= CURRENT_TIMESTAMP()
+            ORDER BY `next_attempt_at`
+            LIMIT 1
+              FOR UPDATE
+             SKIP LOCKED"#
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        r#"UPDATE `confirmation_email_outbox`
+              SET `n_attempts` = `n_attempts` + 1,
+                  `next_attempt_at` = CURRENT_TIMESTAMP() + INTERVAL ? SECOND
+            WHERE `subscription_token` = ?"#,
+        CLAIM_DURATION.as_secs(),
+        item.subscription_token,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(Some(OutboxItem {
+        n_attempts: item.n_attempts + 1,
+        ..item
+    }))
+}
+
+/// Gives a claimed email back to the queue without counting the attempt, when it could not be
+/// made because of the rate limit.
+async fn release_confirmation_email(
+    db_pool: &MySqlPool,
+    item: &OutboxItem,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE `confirmation_email_outbox`
+              SET `n_attempts` = `n_attempts` - 1, `next_attempt_at` = CURRENT_TIMESTAMP()
+            WHERE `subscription_token` = ? AND `n_attempts` = ?"#,
+        item.subscription_token,
+        item.n_attempts,
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn confirmation_email_loop(
+    db_pool: MySqlPool,
+    email_client: Arc<EmailClient>,
+    rate_limiter: Arc<RateLimiter>,
+    base_url: String,
+    settings: WorkerSettings,
+    mut shutdown: ShutdownListener,
+) -> Result<u64, anyhow::Error> {
+    let mut completed_tasks = 0;
+
+    while !shutdown.is_requested() {
+        let sleep_duration =
+            match try_send_confirmation_email(&db_pool, &email_client, &rate_limiter, &base_url)
+                .await
+            {
+                Ok(ExecutionOutcome::TaskCompleted) => {
+                    completed_tasks += 1;
+                    continue;
+                }
+                Ok(ExecutionOutcome::TaskPostponed(wait_time)) => wait_time,
+                Ok(ExecutionOutcome::EmptyQueue) => settings.poll_interval(),
+                _ => settings.error_backoff(),
+            };
+
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_duration) => {}
+            _ = shutdown.requested() => {}
+        }
+    }
+
+    tracing::info!(completed_tasks, "Confirmation email worker has stopped");
+
+    Ok(completed_tasks)
+}
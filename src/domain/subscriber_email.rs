@@ -11,6 +11,13 @@ impl SubscriberEmail {
 
         Ok(Self(string.to_owned()))
     }
+
+    pub fn domain(&self) -> &str {
+        self.0
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or_default()
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
@@ -57,6 +64,12 @@ mod test {
         assert_err!(SubscriberEmail::parse("@missing-subject.net"));
     }
 
+    #[test]
+    fn domain_is_the_part_after_the_at_symbol() {
+        let email = SubscriberEmail::parse("john.doe@example.com").unwrap();
+        assert_eq!("example.com", email.domain());
+    }
+
     #[quickcheck]
     fn valid_email_is_properly_parsed(email: ValidEmailFixture) {
         assert_ok!(SubscriberEmail::parse(&email.0));
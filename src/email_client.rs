@@ -1,8 +1,16 @@
 use crate::domain::SubscriberEmail;
-use reqwest::Client;
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
 use secrecy::{ExposeSecret, Secret};
 use std::time::Duration;
 
+#[derive(thiserror::Error, Debug)]
+pub enum SendEmailError {
+    #[error("The email delivery service is throttling our requests")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error(transparent)]
+    RequestError(#[from] reqwest::Error),
+}
+
 #[derive(serde::Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct SendEmailRequest<'a> {
@@ -42,7 +50,7 @@ impl EmailClient {
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<(), SendEmailError> {
         let request_uri = format!("{}/email", self.base_url);
         let request_body = SendEmailRequest {
             from: self.sender.as_ref(),
@@ -51,7 +59,8 @@ impl EmailClient {
             html_body: html_content,
             text_body: text_content,
         };
-        self.http_client
+        let response = self
+            .http_client
             .post(&request_uri)
             .header(
                 "X-Postmark-Server-Token",
@@ -59,8 +68,20 @@ impl EmailClient {
             )
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+
+            return Err(SendEmailError::RateLimited { retry_after });
+        }
+
+        response.error_for_status()?;
 
         Ok(())
     }
@@ -68,6 +89,7 @@ impl EmailClient {
 
 #[cfg(test)]
 mod test {
+    use super::SendEmailError;
     use claims::{assert_err, assert_ok};
     use fake::{Fake, Faker};
     use secrecy::Secret;
@@ -193,4 +215,27 @@ mod test {
 
         assert_err!(result);
     }
+
+    #[tokio::test]
+    async fn send_email_reports_retry_after_if_the_server_responds_with_429() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "42"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        match result {
+            Err(SendEmailError::RateLimited { retry_after }) => {
+                assert_eq!(Some(Duration::from_secs(42)), retry_after)
+            }
+            _ => panic!("Expected the request to be rate limited"),
+        }
+    }
 }
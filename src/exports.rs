@@ -0,0 +1,286 @@
+use crate::{
+    newsletter_issues::{list_delivery_outcomes, DeliveryOutcome},
+    subscribers::{list_subscribers, Subscriber, SubscriberFilter},
+};
+use actix_web::web::Bytes;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::{stream, Stream};
+use sqlx::MySqlPool;
+use std::future::Future;
+use uuid::Uuid;
+
+/// Rows are loaded one page at a time, so that exports use the same amount of memory whatever
+/// their size.
+const PAGE_SIZE: u32 = 500;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// A single JSON array
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            other => Err(format!("{} is not a supported export format", other)),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+trait ExportRow: serde::Serialize {
+    /// Header of CSV exports, matching the serialized fields
+    const COLUMNS: &'static [&'static str];
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: &'static str,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl ExportRow for SubscriberRow {
+    const COLUMNS: &'static [&'static str] = &["id", "email", "name", "status", "subscribed_at"];
+}
+
+impl From<Subscriber> for SubscriberRow {
+    fn from(subscriber: Subscriber) -> Self {
+        Self {
+            id: subscriber.subscriber_id,
+            email: subscriber.email,
+            name: subscriber.name,
+            status: subscriber.status.as_str(),
+            subscribed_at: subscriber.subscribed_at,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct OutcomeRow {
+    email: String,
+    outcome: String,
+    error_message: Option<String>,
+    attempted_at: Option<DateTime<Utc>>,
+}
+
+impl ExportRow for OutcomeRow {
+    const COLUMNS: &'static [&'static str] = &["email", "outcome", "error_message", "attempted_at"];
+}
+
+impl From<DeliveryOutcome> for OutcomeRow {
+    fn from(outcome: DeliveryOutcome) -> Self {
+        Self {
+            email: outcome.subscriber_email,
+            outcome: outcome.outcome,
+            error_message: outcome.error_message,
+            attempted_at: outcome.attempted_at,
+        }
+    }
+}
+
+/// Exports the subscribers matching the filter, most recent first.
+pub fn export_subscribers(
+    filter: SubscriberFilter,
+    format: ExportFormat,
+    db_pool: MySqlPool,
+) -> impl Stream<Item = Result<Bytes, anyhow::Error>> {
+    export_pages(format, move |cursor| {
+        let filter = filter.clone();
+        let db_pool = db_pool.clone();
+        async move {
+            let (subscribers, next_cursor) =
+                list_subscribers(&filter, cursor.as_ref(), PAGE_SIZE, &db_pool).await?;
+            let rows = subscribers.into_iter().map(SubscriberRow::from).collect();
+
+            Ok((rows, next_cursor))
+        }
+    })
+}
+
+/// Exports the outcome of every delivery of an issue, including the ones still pending.
+pub fn export_delivery_outcomes(
+    newsletter_issue_id: Uuid,
+    format: ExportFormat,
+    db_pool: MySqlPool,
+) -> impl Stream<Item = Result<Bytes, anyhow::Error>> {
+    export_pages(format, move |after_email: Option<String>| {
+        let db_pool = db_pool.clone();
+        async move {
+            let outcomes = list_delivery_outcomes(
+                newsletter_issue_id,
+                after_email.as_deref(),
+                PAGE_SIZE,
+                &db_pool,
+            )
+            .await?;
+            let next_email = if outcomes.len() == PAGE_SIZE as usize {
+                outcomes.last().map(|row| row.subscriber_email.clone())
+            } else {
+                None
+            };
+            let rows = outcomes.into_iter().map(OutcomeRow::from).collect();
+
+            Ok((rows, next_email))
+        }
+    })
+}
+
+struct ExportState<Cursor, FetchPage> {
+    fetch_page: FetchPage,
+    /// `None` once the last page has been fetched
+    next_page: Option<Option<Cursor>>,
+    rows_written: usize,
+    finished: bool,
+}
+
+/// Turns a page by page query into a stream of chunks of the export file, one per page.
+fn export_pages<Row, Cursor, FetchPage, Page>(
+    format: ExportFormat,
+    fetch_page: FetchPage,
+) -> impl Stream<Item = Result<Bytes, anyhow::Error>>
+where
+    Row: ExportRow,
+    FetchPage: FnMut(Option<Cursor>) -> Page,
+    Page: Future<Output = Result<(Vec<Row>, Option<Cursor>), anyhow::Error>>,
+{
+    let state = ExportState {
+        fetch_page,
+        next_page: Some(None),
+        rows_written: 0,
+        finished: false,
+    };
+
+    stream::unfold(state, move |mut state| async move {
+        if state.finished {
+            return None;
+        }
+
+        let Some(cursor) = state.next_page.take() else {
+            state.finished = true;
+            let footer = match format {
+                ExportFormat::Csv => Bytes::new(),
+                ExportFormat::Json => Bytes::from_static(b"]"),
+            };
+            return Some((Ok(footer), state));
+        };
+
+        let is_first_page = state.rows_written == 0 && cursor.is_none();
+        let chunk = match (state.fetch_page)(cursor).await {
+            Ok((rows, next_cursor)) => {
+                state.next_page = next_cursor.map(Some);
+                let chunk = encode_rows(&rows, format, is_first_page, state.rows_written);
+                state.rows_written += rows.len();
+                chunk
+            }
+            Err(e) => Err(e),
+        };
+        if chunk.is_err() {
+            // The export cannot be completed, the client is left with a truncated file
+            state.finished = true;
+        }
+
+        Some((chunk, state))
+    })
+}
+
+fn encode_rows<Row: ExportRow>(
+    rows: &[Row],
+    format: ExportFormat,
+    is_first_page: bool,
+    rows_written: usize,
+) -> Result<Bytes, anyhow::Error> {
+    let mut chunk = Vec::new();
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(&mut chunk);
+            if is_first_page {
+                writer.write_record(Row::COLUMNS)?;
+            }
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush().context("Failed to write CSV rows")?;
+        }
+        ExportFormat::Json => {
+            if is_first_page {
+                chunk.push(b'[');
+            }
+            for (i, row) in rows.iter().enumerate() {
+                if rows_written + i > 0 {
+                    chunk.push(b',');
+                }
+                serde_json::to_writer(&mut chunk, row)?;
+            }
+        }
+    }
+
+    Ok(Bytes::from(chunk))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode_rows, ExportFormat, ExportRow};
+
+    #[derive(serde::Serialize)]
+    struct Row {
+        email: &'static str,
+        error_message: Option<&'static str>,
+    }
+
+    impl ExportRow for Row {
+        const COLUMNS: &'static [&'static str] = &["email", "error_message"];
+    }
+
+    const ROWS: [Row; 2] = [
+        Row {
+            email: "a@example.com",
+            error_message: None,
+        },
+        Row {
+            email: "b@example.com",
+            error_message: Some("Bounced, twice"),
+        },
+    ];
+
+    #[test]
+    fn only_the_first_csv_page_has_a_header() {
+        let first_page = encode_rows(&ROWS[..1], ExportFormat::Csv, true, 0).unwrap();
+        let second_page = encode_rows(&ROWS[1..], ExportFormat::Csv, false, 1).unwrap();
+
+        assert_eq!(&first_page[..], b"email,error_message\na@example.com,\n");
+        assert_eq!(&second_page[..], b"b@example.com,\"Bounced, twice\"\n");
+    }
+
+    #[test]
+    fn json_pages_join_into_a_single_array() {
+        let first_page = encode_rows(&ROWS[..1], ExportFormat::Json, true, 0).unwrap();
+        let second_page = encode_rows(&ROWS[1..], ExportFormat::Json, false, 1).unwrap();
+        let document = [&first_page[..], &second_page[..], b"]"].concat();
+
+        let parsed: serde_json::Value = serde_json::from_slice(&document).unwrap();
+        assert_eq!(parsed[1]["error_message"], "Bounced, twice");
+        assert!(parsed[0]["error_message"].is_null());
+    }
+}
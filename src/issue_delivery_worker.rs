@@ -1,11 +1,19 @@
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
+    configuration::{Settings, WorkerSettings},
+    confirmation_emails::confirmation_email_loop,
+    domain::SubscriberEmail,
+    email_client::{EmailClient, SendEmailError},
+    rate_limiter::RateLimiter,
+    shutdown::ShutdownListener,
     startup::get_connection_pool,
+    webhook_delivery_worker::{webhook_delivery_loop, webhook_dispatch_loop},
 };
-use sqlx::MySqlPool;
-use std::time::Duration;
+use anyhow::Context;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinSet;
 use tracing::{field::display, Span};
-use uuid::{fmt::Hyphenated, Uuid};
+use uuid::Uuid;
 
 #[tracing::instrument(
     skip_all,
@@ -17,17 +25,32 @@ use uuid::{fmt::Hyphenated, Uuid};
 pub async fn try_execute_task(
     db_pool: &MySqlPool,
     email_client: &EmailClient,
+    rate_limiter: &RateLimiter,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    match dequeue_task(db_pool).await? {
-        Some((transaction, issue_id, email)) => {
+    if let Some(wait_time) = rate_limiter.global_wait_time() {
+        return Ok(ExecutionOutcome::TaskPostponed(wait_time));
+    }
+    // Leave the subscribers of throttled domains aside rather than picking them up only to wait,
+    // so that the other domains keep being delivered to
+    let throttled_domains = rate_limiter.throttled_domains();
+
+    match dequeue_task(db_pool, &throttled_domains).await? {
+        Some((mut transaction, issue_id, email)) => {
             Span::current()
                 .record("newsletter_issue_id", &display(issue_id))
                 .record("subscriber_email", &display(&email));
 
-            match SubscriberEmail::parse(&email) {
+            let outcome = match SubscriberEmail::parse(&email) {
                 Ok(email) => {
-                    let issue = get_issue(db_pool, issue_id).await?;
-                    if let Err(e) = email_client
+                    if let Err(wait_time) = rate_limiter.try_acquire_for(&email) {
+                        // Release the task instead of holding its lock and a connection while
+                        // waiting, another worker may pick it up once the limit allows it
+                        transaction.rollback().await?;
+
+                        return Ok(ExecutionOutcome::TaskPostponed(wait_time));
+                    }
+                    let issue = get_issue(&mut transaction, issue_id).await?;
+                    match email_client
                         .send_email(
                             &email,
                             &issue.title,
@@ -36,70 +59,138 @@ pub async fn try_execute_task(
                         )
                         .await
                     {
-                        tracing::error!(
-                            error.cause_chain = %e,
-                            error.message = %e,
-                            "Failed to deliver issue to a confirmed \
-                            subscriber. Skipping."
-                        )
+                        Ok(()) => DeliveryOutcome::Delivered,
+                        Err(SendEmailError::RateLimited { retry_after }) => {
+                            tracing::warn!(
+                                ?retry_after,
+                                "The email delivery service is throttling us. \
+                                Postponing the delivery."
+                            );
+                            rate_limiter.pause(retry_after);
+
+                            return Ok(ExecutionOutcome::TaskPostponed(Duration::ZERO));
+                        }
+                        Err(e) => {
+                            tracing::error!(
+                                error.cause_chain = ?e,
+                                error.message = %e,
+                                "Failed to deliver issue to a confirmed \
+                                subscriber. Skipping."
+                            );
+
+                            DeliveryOutcome::Failed(e.to_string())
+                        }
                     }
                 }
-                Err(e) => tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Skipping a confirmed subscriber. \
-                    Their stored email address is invalid",
-                ),
-            }
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Skipping a confirmed subscriber. \
+                        Their stored email address is invalid",
+                    );
 
-            delete_task(transaction, issue_id, &email).await?;
+                    DeliveryOutcome::Skipped(e)
+                }
+            };
+
+            complete_task(transaction, issue_id, &email, outcome).await?;
 
             Ok(ExecutionOutcome::TaskCompleted)
         }
-        _ => Ok(ExecutionOutcome::EmptyQueue),
+        // Only the subscribers of throttled domains may be left
+        None => match throttled_domains
+            .iter()
+            .map(|(_, wait_time)| *wait_time)
+            .min()
+        {
+            Some(wait_time) => Ok(ExecutionOutcome::TaskPostponed(wait_time)),
+            None => Ok(ExecutionOutcome::EmptyQueue),
+        },
     }
 }
 
 type MySqlTransaction = sqlx::Transaction<'static, sqlx::MySql>;
 
-struct IssueQueueItem {
-    newsletter_issue_id: Hyphenated,
-    subscriber_email: String,
+enum DeliveryOutcome {
+    Delivered,
+    Failed(String),
+    Skipped(String),
+}
+
+impl DeliveryOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delivered => "delivered",
+            Self::Failed(_) => "failed",
+            Self::Skipped(_) => "skipped",
+        }
+    }
+
+    fn error_message(&self) -> Option<&str> {
+        match self {
+            Self::Delivered => None,
+            Self::Failed(message) | Self::Skipped(message) => Some(message),
+        }
+    }
 }
 
+/// Locks a task whose subscriber is not in one of the throttled domains.
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     db_pool: &MySqlPool,
+    throttled_domains: &[(String, Duration)],
 ) -> Result<Option<(MySqlTransaction, Uuid, String)>, anyhow::Error> {
     let mut transaction = db_pool.begin().await?;
-    let r = sqlx::query_as!(
-        IssueQueueItem,
-        r#"SELECT `newsletter_issue_id` AS "newsletter_issue_id: Hyphenated", `subscriber_email`
-             FROM `issue_delivery_queue`
-            LIMIT 1
-              FOR UPDATE
-             SKIP LOCKED"#
-    )
-    .fetch_optional(&mut transaction)
-    .await?;
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT `newsletter_issue_id`, `subscriber_email` FROM `issue_delivery_queue`",
+    );
+    if !throttled_domains.is_empty() {
+        query.push(" WHERE SUBSTRING_INDEX(`subscriber_email`, '@', -1) NOT IN (");
+        let mut domains = query.separated(", ");
+        for (domain, _) in throttled_domains {
+            domains.push_bind(domain.as_str());
+        }
+        domains.push_unseparated(")");
+    }
+    query.push(" LIMIT 1 FOR UPDATE SKIP LOCKED");
+    let r = query
+        .build_query_as::<(String, String)>()
+        .fetch_optional(&mut transaction)
+        .await?;
 
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id.into(),
-            r.subscriber_email,
-        )))
+    if let Some((newsletter_issue_id, subscriber_email)) = r {
+        let newsletter_issue_id = Uuid::parse_str(&newsletter_issue_id)
+            .context("Failed to parse newsletter issue UUID loaded from the database.")?;
+        Ok(Some((transaction, newsletter_issue_id, subscriber_email)))
     } else {
         Ok(None)
     }
 }
 
 #[tracing::instrument(skip_all)]
-async fn delete_task(
+async fn complete_task(
     mut transaction: MySqlTransaction,
     issue_id: Uuid,
     email: &str,
+    outcome: DeliveryOutcome,
 ) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO `issue_delivery_outcomes` (
+            `newsletter_issue_id`, `subscriber_email`, `outcome`, `error_message`, `attempted_at`
+        ) VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP())
+        ON DUPLICATE KEY UPDATE
+            `outcome` = VALUES(`outcome`),
+            `error_message` = VALUES(`error_message`),
+            `attempted_at` = VALUES(`attempted_at`)"#,
+        issue_id,
+        email,
+        outcome.as_str(),
+        outcome.error_message(),
+    )
+    .execute(&mut transaction)
+    .await?;
+
     sqlx::query!(
         r#"DELETE FROM `issue_delivery_queue`
             WHERE `newsletter_issue_id` = ? AND `subscriber_email` = ?"#,
@@ -121,7 +212,10 @@ struct NewsletterIssue {
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_issue(db_pool: &MySqlPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+async fn get_issue(
+    transaction: &mut MySqlTransaction,
+    issue_id: Uuid,
+) -> Result<NewsletterIssue, anyhow::Error> {
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"SELECT `title`, `text_content`, `html_content`
@@ -129,30 +223,157 @@ async fn get_issue(db_pool: &MySqlPool, issue_id: Uuid) -> Result<NewsletterIssu
             WHERE `newsletter_issue_id` = ?"#,
         issue_id
     )
-    .fetch_one(db_pool)
+    .fetch_one(transaction)
     .await?;
 
     Ok(issue)
 }
 
-async fn worker_loop(db_pool: MySqlPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
-    loop {
-        match try_execute_task(&db_pool, &email_client).await {
-            Ok(ExecutionOutcome::TaskCompleted) => {}
-            Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(Duration::from_secs(10)).await,
-            _ => tokio::time::sleep(Duration::from_secs(1)).await,
+#[tracing::instrument(skip(db_pool, email_client, rate_limiter, settings, shutdown))]
+async fn worker_loop(
+    worker_id: usize,
+    db_pool: MySqlPool,
+    email_client: Arc<EmailClient>,
+    rate_limiter: Arc<RateLimiter>,
+    settings: WorkerSettings,
+    mut shutdown: ShutdownListener,
+) -> Result<u64, anyhow::Error> {
+    let mut completed_tasks = 0;
+
+    while !shutdown.is_requested() {
+        let sleep_duration = match try_execute_task(&db_pool, &email_client, &rate_limiter).await {
+            Ok(ExecutionOutcome::TaskCompleted) => {
+                completed_tasks += 1;
+                continue;
+            }
+            Ok(ExecutionOutcome::TaskPostponed(wait_time)) => wait_time,
+            Ok(ExecutionOutcome::EmptyQueue) => settings.poll_interval(),
+            _ => settings.error_backoff(),
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_duration) => {}
+            _ = shutdown.requested() => {}
         }
     }
+
+    tracing::info!(completed_tasks, "Delivery worker has stopped");
+
+    Ok(completed_tasks)
+}
+
+#[tracing::instrument(skip_all)]
+async fn count_pending_tasks(db_pool: &MySqlPool) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM `issue_delivery_queue`"#)
+        .fetch_one(db_pool)
+        .await?;
+
+    Ok(row.count)
 }
 
 pub enum ExecutionOutcome {
     TaskCompleted,
+    /// Nothing can be done before the given delay, e.g. because of the outbound rate limit
+    TaskPostponed(Duration),
     EmptyQueue,
 }
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    shutdown: ShutdownListener,
+) -> Result<(), anyhow::Error> {
     let db_pool = get_connection_pool(&configuration.database);
-    let email_client = configuration.email.client();
+    let email_client = Arc::new(configuration.email.client());
+    let rate_limiter = Arc::new(configuration.worker.rate_limiter());
+
+    let mut workers = JoinSet::new();
+    for worker_id in 0..configuration.worker.n_workers.max(1) {
+        workers.spawn(worker_loop(
+            worker_id,
+            db_pool.clone(),
+            email_client.clone(),
+            rate_limiter.clone(),
+            configuration.worker.clone(),
+            shutdown.clone(),
+        ));
+    }
+
+    workers.spawn(confirmation_email_loop(
+        db_pool.clone(),
+        email_client.clone(),
+        rate_limiter.clone(),
+        configuration.application.base_url.clone(),
+        configuration.worker.clone(),
+        shutdown.clone(),
+    ));
+    let webhook_client = Arc::new(configuration.webhooks.client());
+    for endpoint in webhook_client.endpoints() {
+        workers.spawn(webhook_delivery_loop(
+            db_pool.clone(),
+            webhook_client.clone(),
+            endpoint.url.clone(),
+            configuration.worker.clone(),
+            shutdown.clone(),
+        ));
+    }
+    workers.spawn(webhook_dispatch_loop(
+        db_pool.clone(),
+        webhook_client,
+        configuration.worker.clone(),
+        shutdown.clone(),
+    ));
+
+    let mut completed_tasks = 0;
+    while let Some(outcome) = workers.join_next().await {
+        completed_tasks += outcome??;
+    }
+
+    let pending_tasks = count_pending_tasks(&db_pool).await?;
+    tracing::info!(
+        completed_tasks,
+        pending_tasks,
+        "Background worker has drained in-flight deliveries",
+    );
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(db_pool))]
+pub async fn requeue_failed_deliveries(
+    db_pool: &MySqlPool,
+    newsletter_issue_id: Option<Uuid>,
+) -> Result<u64, anyhow::Error> {
+    let mut transaction = db_pool.begin().await?;
+    let requeued_tasks = sqlx::query!(
+        r#"INSERT IGNORE INTO `issue_delivery_queue` (`newsletter_issue_id`, `subscriber_email`)
+           SELECT `newsletter_issue_id`, `subscriber_email`
+             FROM `issue_delivery_outcomes`
+            WHERE `outcome` = 'failed' AND (? IS NULL OR `newsletter_issue_id` = ?)"#,
+        newsletter_issue_id,
+        newsletter_issue_id,
+    )
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        r#"DELETE FROM `issue_delivery_outcomes`
+            WHERE `outcome` = 'failed' AND (? IS NULL OR `newsletter_issue_id` = ?)"#,
+        newsletter_issue_id,
+        newsletter_issue_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    // Requeued issues are sent again, and notified as such once done
+    sqlx::query!(
+        r#"UPDATE `newsletter_issues` SET `sent_at` = NULL
+            WHERE `newsletter_issue_id` IN (SELECT `newsletter_issue_id` FROM `issue_delivery_queue`)"#
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
 
-    worker_loop(db_pool, email_client).await
+    Ok(requeued_tasks)
 }
@@ -1,12 +1,24 @@
+pub mod audit;
 pub mod authentication;
+pub mod cli;
 pub mod configuration;
+pub mod confirmation_emails;
 pub mod domain;
 pub mod email_client;
 mod errors;
+pub mod exports;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod migrations;
+pub mod newsletter_issues;
+pub mod rate_limiter;
 pub mod routes;
 pub mod session_state;
+pub mod shutdown;
 pub mod startup;
+pub mod subscriber_import;
+pub mod subscribers;
 pub mod telemetry;
 pub mod utils;
+pub mod webhook_delivery_worker;
+pub mod webhooks;
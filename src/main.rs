@@ -1,50 +1,239 @@
-use std::fmt::{Debug, Display};
+use std::{future::Future, path::PathBuf};
 
-use tokio::task::JoinError;
+use clap::{Parser, Subcommand};
+use secrecy::Secret;
+use tokio::task::{JoinError, JoinSet};
+use uuid::Uuid;
 use zero2prod::{
-    configuration::get_configuration,
+    cli,
+    configuration::{get_configuration, Settings},
+    domain::SubscriptionStatus,
+    exports::ExportFormat,
     issue_delivery_worker::run_worker_until_stopped,
-    startup::Application,
+    migrations::{check_schema, run_migrations},
+    shutdown::{wait_for_signal, Shutdown},
+    startup::{get_connection_pool, Application, HealthCheckServer},
     telemetry::{get_subscriber, init_subscriber},
 };
 
-fn get_configuration_path() -> String {
-    let args: Vec<String> = std::env::args().collect();
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Path to the YAML configuration file
+    configuration_file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    match args.get(1) {
-        Some(path) => path.to_owned(),
-        None => {
-            eprintln!("usage: {} <configuration_file>", args[0]);
-            std::process::exit(1);
-        }
+#[derive(Subcommand, Clone, PartialEq, Eq)]
+enum Command {
+    /// Run the API server only
+    Serve,
+    /// Run the background delivery worker only, along with its own health check endpoint
+    Worker,
+    /// Run both the API server and the background delivery worker (default)
+    All,
+    /// Apply the database migrations embedded in the binary
+    Migrate,
+    /// Create a new admin user, prompting for their password
+    CreateAdmin { username: String },
+    /// Reset the password of an existing user, prompting for the new one
+    ResetPassword { username: String },
+    /// Send a test email to check the email delivery configuration
+    SendTestEmail { recipient: String },
+    /// Put failed newsletter deliveries back into the delivery queue
+    RequeueFailed {
+        /// Only requeue deliveries of the given newsletter issue
+        #[arg(long)]
+        issue_id: Option<Uuid>,
+    },
+    /// List published newsletter issues along with their delivery progress
+    ListIssues,
+    /// Import subscribers from a CSV file with `email` and `name` columns
+    ImportSubscribers {
+        file: PathBuf,
+        /// Import them as confirmed rather than sending them a confirmation email
+        #[arg(long)]
+        confirmed: bool,
+        /// Write the rejected rows to this CSV file instead of listing them
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Export subscribers, most recent first
+    ExportSubscribers {
+        /// `csv` or `json`
+        #[arg(long, default_value = "csv", value_parser = ExportFormat::parse)]
+        format: ExportFormat,
+        /// Only export subscribers with this status
+        #[arg(long, value_parser = SubscriptionStatus::parse)]
+        status: Option<SubscriptionStatus>,
+        /// Write the export to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Export the outcome of the delivery of a newsletter issue to each subscriber
+    ExportIssueOutcomes {
+        issue_id: Uuid,
+        /// `csv` or `json`
+        #[arg(long, default_value = "csv", value_parser = ExportFormat::parse)]
+        format: ExportFormat,
+        /// Write the export to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Command {
+    fn runs_api(&self) -> bool {
+        matches!(self, Self::Serve | Self::All)
+    }
+
+    fn runs_worker(&self) -> bool {
+        matches!(self, Self::Worker | Self::All)
+    }
+
+    fn is_long_running(&self) -> bool {
+        self.runs_api() || self.runs_worker()
     }
 }
 
+type TaskOutcome = (&'static str, Result<(), anyhow::Error>);
+
+async fn named_task<E>(
+    task_name: &'static str,
+    task: impl Future<Output = Result<(), E>>,
+) -> TaskOutcome
+where
+    E: Into<anyhow::Error>,
+{
+    (task_name, task.await.map_err(Into::into))
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
+    let args = Cli::parse();
+    let command = args.command.unwrap_or(Command::All);
+
+    if command.is_long_running() {
+        let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
+        init_subscriber(subscriber);
+    } else {
+        let subscriber = get_subscriber("zero2prod".into(), "warn".into(), std::io::stderr);
+        init_subscriber(subscriber);
+    }
 
-    let configuration = get_configuration(&get_configuration_path())
+    let configuration = get_configuration(&args.configuration_file)
         .expect("Failed to read the `{}` configuration file");
-    let server = Application::build(configuration.clone()).await?;
-    let worker = run_worker_until_stopped(configuration);
 
-    let server_task = tokio::spawn(server.run_until_stopped());
-    let worker_task = tokio::spawn(worker);
+    match command {
+        Command::Migrate => cli::migrate(&configuration).await,
+        Command::CreateAdmin { username } => {
+            let password = prompt_password()?;
+            cli::create_admin(&configuration, &username, password).await
+        }
+        Command::ResetPassword { username } => {
+            let password = prompt_password()?;
+            cli::reset_password(&configuration, &username, password).await
+        }
+        Command::SendTestEmail { recipient } => {
+            cli::send_test_email(&configuration, &recipient).await
+        }
+        Command::RequeueFailed { issue_id } => cli::requeue_failed(&configuration, issue_id).await,
+        Command::ListIssues => cli::list_issues(&configuration).await,
+        Command::ImportSubscribers {
+            file,
+            confirmed,
+            report,
+        } => cli::import_subscribers(&configuration, &file, confirmed, report.as_deref()).await,
+        Command::ExportSubscribers {
+            format,
+            status,
+            output,
+        } => cli::export_subscribers(&configuration, format, status, output.as_deref()).await,
+        Command::ExportIssueOutcomes {
+            issue_id,
+            format,
+            output,
+        } => cli::export_issue_outcomes(&configuration, issue_id, format, output.as_deref()).await,
+        command => run_services(command, configuration).await,
+    }
+}
+
+fn prompt_password() -> Result<Secret<String>, anyhow::Error> {
+    let password = rpassword::prompt_password("Password: ")?;
+    let password_check = rpassword::prompt_password("Repeat password: ")?;
+
+    if password != password_check {
+        anyhow::bail!("Passwords do not match");
+    }
+
+    Ok(Secret::new(password))
+}
+
+async fn run_services(command: Command, configuration: Settings) -> Result<(), anyhow::Error> {
+    let db_pool = get_connection_pool(&configuration.database);
+    if configuration.database.migrate_on_startup {
+        run_migrations(&db_pool).await?;
+    } else {
+        check_schema(&db_pool).await?;
+    }
+
+    let shutdown_timeout = configuration.application.shutdown_timeout_seconds;
+    let shutdown = Shutdown::new();
+
+    let mut tasks = JoinSet::new();
+    let mut server_handles = Vec::new();
+
+    if command.runs_api() {
+        let server = Application::build(configuration.clone()).await?;
+        server_handles.push(server.handle());
+        tasks.spawn(named_task("API", server.run_until_stopped()));
+    }
+
+    if command.runs_worker() {
+        let worker = run_worker_until_stopped(configuration.clone(), shutdown.listener());
+        tasks.spawn(named_task("Background worker", worker));
+    }
+
+    if command == Command::Worker {
+        let health_check = HealthCheckServer::build(&configuration.worker, shutdown_timeout)?;
+        server_handles.push(health_check.handle());
+        tasks.spawn(named_task(
+            "Worker health check",
+            health_check.run_until_stopped(),
+        ));
+    }
 
     tokio::select! {
-        o = server_task => report_exit("API", o),
-        o = worker_task => report_exit("Background worker", o),
+        Some(outcome) = tasks.join_next() => report_exit(outcome),
+        signal = wait_for_signal() => {
+            if let Err(e) = signal {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to listen for shutdown signals",
+                );
+            }
+
+            tracing::info!("Shutting down gracefully");
+            shutdown.trigger();
+            for server_handle in server_handles {
+                server_handle.stop(true).await;
+            }
+
+            while let Some(outcome) = tasks.join_next().await {
+                report_exit(outcome);
+            }
+        }
     };
 
     Ok(())
 }
 
-fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+fn report_exit(outcome: Result<TaskOutcome, JoinError>) {
     match outcome {
-        Ok(Ok(())) => tracing::info!("{} has exited", task_name),
-        Ok(Err(e)) => tracing::error!(
+        Ok((task_name, Ok(()))) => tracing::info!("{} has exited", task_name),
+        Ok((task_name, Err(e))) => tracing::error!(
             error.cause_chain = ?e,
             error.message = %e,
             "{} failed",
@@ -53,8 +242,7 @@ fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>
         Err(e) => tracing::error!(
             error.cause_chain = ?e,
             error.message = %e,
-            "{} task failed to complete",
-            task_name,
+            "A task failed to complete",
         ),
     }
 }
@@ -0,0 +1,115 @@
+use anyhow::Context;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    MySqlConnection, MySqlPool,
+};
+use std::collections::HashSet;
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+const MIGRATION_LOCK_NAME: &str = "zero2prod_migrations";
+const MIGRATION_LOCK_TIMEOUT_SECONDS: i32 = 300;
+
+#[tracing::instrument(skip_all)]
+pub async fn run_migrations(db_pool: &MySqlPool) -> Result<(), anyhow::Error> {
+    let mut connection = db_pool
+        .acquire()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    let lock = sqlx::query!(
+        r#"SELECT GET_LOCK(?, ?) AS "acquired""#,
+        MIGRATION_LOCK_NAME,
+        MIGRATION_LOCK_TIMEOUT_SECONDS,
+    )
+    .fetch_one(&mut connection)
+    .await
+    .context("Failed to acquire the migration lock.")?;
+
+    if lock.acquired != Some(1) {
+        anyhow::bail!(
+            "Timed out waiting for another instance to finish applying database migrations"
+        );
+    }
+
+    let outcome = apply_migrations(&mut connection).await;
+
+    // The lock is released with the connection anyway, so a failure here must not hide the
+    // outcome of the migrations themselves
+    if let Err(e) = sqlx::query!(
+        r#"SELECT RELEASE_LOCK(?) AS "released""#,
+        MIGRATION_LOCK_NAME
+    )
+    .fetch_one(&mut connection)
+    .await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to release the migration lock"
+        );
+    }
+
+    outcome
+}
+
+async fn apply_migrations(connection: &mut MySqlConnection) -> Result<(), anyhow::Error> {
+    connection
+        .ensure_migrations_table()
+        .await
+        .context("Failed to create the migrations table.")?;
+    ensure_schema_is_not_ahead(connection).await?;
+
+    MIGRATOR
+        .run(&mut *connection)
+        .await
+        .context("Failed to apply database migrations.")?;
+
+    tracing::info!("Database schema is up to date");
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn check_schema(db_pool: &MySqlPool) -> Result<(), anyhow::Error> {
+    let mut connection = db_pool
+        .acquire()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    // Only reads the schema: without a migrations table, nothing has been applied yet
+    let migrations_table = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM `information_schema`.`tables`
+            WHERE `table_schema` = DATABASE() AND `table_name` = '_sqlx_migrations'"#
+    )
+    .fetch_one(&mut connection)
+    .await
+    .context("Failed to look for the migrations table.")?;
+    if migrations_table.count == 0 {
+        return Ok(());
+    }
+
+    ensure_schema_is_not_ahead(&mut connection).await
+}
+
+async fn ensure_schema_is_not_ahead(connection: &mut MySqlConnection) -> Result<(), anyhow::Error> {
+    let known_versions: HashSet<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+    let unknown_versions: Vec<i64> = connection
+        .list_applied_migrations()
+        .await
+        .context("Failed to list applied database migrations.")?
+        .into_iter()
+        .map(|m| m.version)
+        .filter(|version| !known_versions.contains(version))
+        .collect();
+
+    if !unknown_versions.is_empty() {
+        anyhow::bail!(
+            "The database schema is ahead of this binary. \
+            Unknown migrations have been applied: {:?}",
+            unknown_versions
+        );
+    }
+
+    Ok(())
+}
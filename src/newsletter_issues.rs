@@ -0,0 +1,307 @@
+use crate::webhooks::{record_event, WebhookEvent};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, MySql, MySqlPool, Transaction};
+use uuid::Uuid;
+
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub created_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+pub struct DeliveryStatus {
+    pub pending: i64,
+    pub delivered: i64,
+    pub failed: i64,
+    pub skipped: i64,
+}
+
+/// What happened to the delivery of an issue to one subscriber, `pending` while still queued.
+pub struct DeliveryOutcome {
+    pub subscriber_email: String,
+    pub outcome: String,
+    pub error_message: Option<String>,
+    pub attempted_at: Option<DateTime<Utc>>,
+}
+
+/// Stores a new issue as a draft, it is only sent out once published.
+#[tracing::instrument(skip_all)]
+pub async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, MySql>,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO `newsletter_issues` (
+            `newsletter_issue_id`, `title`, `text_content`, `html_content`
+        ) VALUES (?, ?, ?, ?)"#,
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content,
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
+
+/// Marks a draft as published, returning `false` if it has been published already.
+#[tracing::instrument(skip(transaction))]
+pub async fn publish_newsletter_issue(
+    transaction: &mut Transaction<'_, MySql>,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let published_rows = sqlx::query!(
+        r#"UPDATE `newsletter_issues`
+              SET `published_at` = CURRENT_TIMESTAMP()
+            WHERE `newsletter_issue_id` = ? AND `published_at` IS NULL"#,
+        newsletter_issue_id,
+    )
+    .execute(transaction)
+    .await?
+    .rows_affected();
+
+    Ok(published_rows > 0)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_task(
+    transaction: &mut Transaction<'_, MySql>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO `issue_delivery_queue` (
+            `newsletter_issue_id`, `subscriber_email`
+        ) SELECT ?, `email` FROM `subscriptions`
+           WHERE `status`="confirmed""#,
+        newsletter_issue_id,
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Looks an issue up either through the pool or, to see changes that are not committed yet,
+/// within a transaction.
+#[tracing::instrument(skip(executor))]
+pub async fn get_newsletter_issue<'e>(
+    newsletter_issue_id: Uuid,
+    executor: impl Executor<'e, Database = MySql>,
+) -> Result<Option<NewsletterIssue>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT `title`, `text_content`, `html_content`, `created_at`, `published_at`
+             FROM `newsletter_issues`
+            WHERE `newsletter_issue_id` = ?"#,
+        newsletter_issue_id
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to perform a query to retrieve a newsletter issue.")?;
+
+    Ok(row.map(|row| NewsletterIssue {
+        newsletter_issue_id,
+        title: row.title,
+        text_content: row.text_content,
+        html_content: row.html_content,
+        created_at: row.created_at,
+        published_at: row.published_at,
+    }))
+}
+
+/// Lists issues from the most recently created one, along with the total number of issues.
+#[tracing::instrument(skip(db_pool))]
+pub async fn list_newsletter_issues(
+    limit: u32,
+    offset: u32,
+    db_pool: &MySqlPool,
+) -> Result<(Vec<NewsletterIssue>, i64), anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT `newsletter_issue_id`, `title`, `text_content`, `html_content`,
+                  `created_at`, `published_at`
+             FROM `newsletter_issues`
+            ORDER BY `created_at` DESC, `newsletter_issue_id`
+            LIMIT ? OFFSET ?"#,
+        limit,
+        offset,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to list newsletter issues.")?;
+
+    let total = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM `newsletter_issues`"#)
+        .fetch_one(db_pool)
+        .await
+        .context("Failed to perform a query to count newsletter issues.")?
+        .count;
+
+    let issues = rows
+        .into_iter()
+        .map(|row| {
+            Ok(NewsletterIssue {
+                newsletter_issue_id: Uuid::parse_str(&row.newsletter_issue_id)
+                    .context("Failed to parse newsletter issue UUID loaded from the database.")?,
+                title: row.title,
+                text_content: row.text_content,
+                html_content: row.html_content,
+                created_at: row.created_at,
+                published_at: row.published_at,
+            })
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+
+    Ok((issues, total))
+}
+
+#[tracing::instrument(skip(db_pool))]
+pub async fn get_delivery_status(
+    newsletter_issue_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<DeliveryStatus, anyhow::Error> {
+    let pending = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM `issue_delivery_queue`
+            WHERE `newsletter_issue_id` = ?"#,
+        newsletter_issue_id
+    )
+    .fetch_one(db_pool)
+    .await
+    .context("Failed to perform a query to count pending deliveries.")?
+    .count;
+
+    let outcomes = sqlx::query!(
+        r#"SELECT `outcome`, COUNT(*) AS "count!" FROM `issue_delivery_outcomes`
+            WHERE `newsletter_issue_id` = ?
+            GROUP BY `outcome`"#,
+        newsletter_issue_id
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to count delivery outcomes.")?;
+
+    let mut status = DeliveryStatus {
+        pending,
+        delivered: 0,
+        failed: 0,
+        skipped: 0,
+    };
+    for row in outcomes {
+        match row.outcome.as_str() {
+            "delivered" => status.delivered = row.count,
+            "failed" => status.failed = row.count,
+            "skipped" => status.skipped = row.count,
+            other => tracing::warn!(outcome = other, "Unknown delivery outcome"),
+        }
+    }
+
+    Ok(status)
+}
+
+/// Lists the deliveries of an issue by subscriber email, starting after `after_email`.
+#[tracing::instrument(skip(db_pool))]
+pub async fn list_delivery_outcomes(
+    newsletter_issue_id: Uuid,
+    after_email: Option<&str>,
+    limit: u32,
+    db_pool: &MySqlPool,
+) -> Result<Vec<DeliveryOutcome>, anyhow::Error> {
+    sqlx::query_as!(
+        DeliveryOutcome,
+        r#"SELECT `subscriber_email`, `outcome`, `error_message` AS "error_message?",
+                  `attempted_at` AS "attempted_at?: DateTime<Utc>"
+             FROM (SELECT `subscriber_email`, `outcome`, `error_message`, `attempted_at`
+                     FROM `issue_delivery_outcomes`
+                    WHERE `newsletter_issue_id` = ?
+                    UNION ALL
+                   SELECT `subscriber_email`, 'pending', NULL, NULL
+                     FROM `issue_delivery_queue`
+                    WHERE `newsletter_issue_id` = ?) AS `deliveries`
+            WHERE (? IS NULL OR `subscriber_email` > ?)
+            ORDER BY `subscriber_email`
+            LIMIT ?"#,
+        newsletter_issue_id,
+        newsletter_issue_id,
+        after_email,
+        after_email,
+        limit,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to list delivery outcomes.")
+}
+
+/// Marks one published issue whose deliveries have all been attempted as sent, recording an
+/// `issue.sent` webhook event for it. Returns `None` when there is no such issue.
+#[tracing::instrument(skip(db_pool))]
+pub async fn mark_next_sent_issue(db_pool: &MySqlPool) -> Result<Option<Uuid>, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    let Some(issue) = sqlx::query!(
+        r#"SELECT `newsletter_issue_id`, `title`, `published_at` AS "published_at!"
+             FROM `newsletter_issues`
+            WHERE `published_at` IS NOT NULL AND `sent_at` IS NULL
+              AND `newsletter_issue_id` NOT IN (SELECT `newsletter_issue_id` FROM `issue_delivery_queue`)
+            LIMIT 1
+              FOR UPDATE
+             SKIP LOCKED"#
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to perform a query to find sent newsletter issues.")?
+    else {
+        return Ok(None);
+    };
+    let newsletter_issue_id = Uuid::parse_str(&issue.newsletter_issue_id)
+        .context("Failed to parse newsletter issue UUID loaded from the database.")?;
+
+    let sent_at = Utc::now();
+    sqlx::query!(
+        r#"UPDATE `newsletter_issues` SET `sent_at` = ? WHERE `newsletter_issue_id` = ?"#,
+        sent_at,
+        newsletter_issue_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to mark a newsletter issue as sent.")?;
+
+    let outcomes = sqlx::query!(
+        r#"SELECT `outcome`, COUNT(*) AS "count!" FROM `issue_delivery_outcomes`
+            WHERE `newsletter_issue_id` = ?
+            GROUP BY `outcome`"#,
+        newsletter_issue_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to perform a query to count delivery outcomes.")?;
+    let mut data = serde_json::json!({
+        "id": newsletter_issue_id,
+        "title": issue.title,
+        "published_at": issue.published_at,
+        "sent_at": sent_at,
+        "delivered": 0,
+        "failed": 0,
+        "skipped": 0,
+    });
+    for row in outcomes {
+        data[row.outcome.as_str()] = row.count.into();
+    }
+    record_event(&mut transaction, WebhookEvent::IssueSent, data)
+        .await
+        .context("Failed to record an issue.sent webhook event.")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction.")?;
+
+    Ok(Some(newsletter_issue_id))
+}
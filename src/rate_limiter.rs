@@ -0,0 +1,262 @@
+use crate::{configuration::RateLimitSettings, domain::SubscriberEmail};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        let capacity = rate.max(1.0);
+
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            refilled_at: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.capacity);
+        self.refilled_at = now.max(self.refilled_at);
+    }
+
+    fn wait_time(&self) -> Duration {
+        if self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+struct RateLimiterState {
+    global: Option<TokenBucket>,
+    domains: HashMap<String, TokenBucket>,
+    paused_until: Option<Instant>,
+}
+
+pub struct RateLimiter(Mutex<RateLimiterState>);
+
+impl RateLimiter {
+    const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+    pub fn new(settings: &RateLimitSettings) -> Self {
+        let now = Instant::now();
+        let global = settings
+            .messages_per_second
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| TokenBucket::new(rate, now));
+        let domains = settings
+            .domains
+            .iter()
+            .filter(|limit| limit.messages_per_second > 0.0)
+            .map(|limit| {
+                (
+                    limit.domain.to_lowercase(),
+                    TokenBucket::new(limit.messages_per_second, now),
+                )
+            })
+            .collect();
+
+        Self(Mutex::new(RateLimiterState {
+            global,
+            domains,
+            paused_until: None,
+        }))
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(&RateLimitSettings::default())
+    }
+
+    /// Takes a permit if one is available right away, or tells how long to wait for one.
+    pub fn try_acquire_for(&self, recipient: &SubscriberEmail) -> Result<(), Duration> {
+        self.try_acquire(&recipient.domain().to_lowercase(), Instant::now())
+    }
+
+    /// How long to wait before sending to any domain, while paused or over the global limit.
+    pub fn global_wait_time(&self) -> Option<Duration> {
+        self.global_wait_time_at(Instant::now())
+    }
+
+    /// Domains over their own limit, with how long to wait before sending to each of them.
+    pub fn throttled_domains(&self) -> Vec<(String, Duration)> {
+        self.throttled_domains_at(Instant::now())
+    }
+
+    pub fn pause(&self, retry_after: Option<Duration>) {
+        let paused_until = Instant::now() + retry_after.unwrap_or(Self::DEFAULT_RETRY_AFTER);
+        let mut state = self.0.lock().unwrap();
+
+        state.paused_until = state.paused_until.max(Some(paused_until));
+    }
+
+    fn global_wait_time_at(&self, now: Instant) -> Option<Duration> {
+        let mut state = self.0.lock().unwrap();
+        if let Some(until) = state.paused_until.filter(|until| *until > now) {
+            return Some(until - now);
+        }
+
+        let global = state.global.as_mut()?;
+        global.refill(now);
+        Some(global.wait_time()).filter(|wait_time| !wait_time.is_zero())
+    }
+
+    fn throttled_domains_at(&self, now: Instant) -> Vec<(String, Duration)> {
+        let mut state = self.0.lock().unwrap();
+
+        state
+            .domains
+            .iter_mut()
+            .filter_map(|(domain, bucket)| {
+                bucket.refill(now);
+                let wait_time = bucket.wait_time();
+                (!wait_time.is_zero()).then(|| (domain.clone(), wait_time))
+            })
+            .collect()
+    }
+
+    fn try_acquire(&self, domain: &str, now: Instant) -> Result<(), Duration> {
+        let mut state = self.0.lock().unwrap();
+        let RateLimiterState {
+            global,
+            domains,
+            paused_until,
+        } = &mut *state;
+
+        if let Some(until) = *paused_until {
+            if until > now {
+                return Err(until - now);
+            }
+
+            *paused_until = None;
+        }
+
+        let mut buckets: Vec<&mut TokenBucket> =
+            global.iter_mut().chain(domains.get_mut(domain)).collect();
+
+        let mut wait_time = Duration::ZERO;
+        for bucket in buckets.iter_mut() {
+            bucket.refill(now);
+            wait_time = wait_time.max(bucket.wait_time());
+        }
+
+        if !wait_time.is_zero() {
+            return Err(wait_time);
+        }
+
+        buckets.into_iter().for_each(TokenBucket::take);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RateLimiter;
+    use crate::configuration::{DomainRateLimitSettings, RateLimitSettings};
+    use claims::{assert_err, assert_ok};
+    use std::time::{Duration, Instant};
+
+    fn rate_limiter(global: Option<f64>, domains: &[(&str, f64)]) -> RateLimiter {
+        RateLimiter::new(&RateLimitSettings {
+            messages_per_second: global,
+            domains: domains
+                .iter()
+                .map(|(domain, rate)| DomainRateLimitSettings {
+                    domain: domain.to_string(),
+                    messages_per_second: *rate,
+                })
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn unlimited_rate_limiter_never_waits() {
+        let rate_limiter = RateLimiter::unlimited();
+        let now = Instant::now();
+
+        for _ in 0..1000 {
+            assert_ok!(rate_limiter.try_acquire("example.com", now));
+        }
+    }
+
+    #[test]
+    fn global_limit_is_enforced_until_tokens_are_refilled() {
+        let rate_limiter = rate_limiter(Some(2.0), &[]);
+        let now = Instant::now();
+
+        assert_ok!(rate_limiter.try_acquire("example.com", now));
+        assert_ok!(rate_limiter.try_acquire("example.org", now));
+        let wait_time = assert_err!(rate_limiter.try_acquire("example.net", now));
+        assert_eq!(Duration::from_millis(500), wait_time);
+
+        assert_ok!(rate_limiter.try_acquire("example.net", now + wait_time));
+    }
+
+    #[test]
+    fn domain_limit_does_not_affect_other_domains() {
+        let rate_limiter = rate_limiter(None, &[("Gmail.com", 1.0)]);
+        let now = Instant::now();
+
+        assert_ok!(rate_limiter.try_acquire("gmail.com", now));
+        assert_err!(rate_limiter.try_acquire("gmail.com", now));
+        assert_ok!(rate_limiter.try_acquire("example.com", now));
+        assert_ok!(rate_limiter.try_acquire("gmail.com", now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn only_domains_over_their_limit_are_throttled() {
+        let rate_limiter = rate_limiter(Some(10.0), &[("gmail.com", 1.0), ("yahoo.com", 1.0)]);
+        let now = Instant::now();
+
+        assert_ok!(rate_limiter.try_acquire("gmail.com", now));
+
+        assert_eq!(None, rate_limiter.global_wait_time_at(now));
+        assert_eq!(
+            vec![("gmail.com".to_owned(), Duration::from_secs(1))],
+            rate_limiter.throttled_domains_at(now)
+        );
+        assert!(rate_limiter
+            .throttled_domains_at(now + Duration::from_secs(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn paused_rate_limiter_throttles_every_domain() {
+        let rate_limiter = RateLimiter::unlimited();
+        rate_limiter.pause(Some(Duration::from_secs(60)));
+        let now = Instant::now();
+
+        assert!(rate_limiter.global_wait_time_at(now).unwrap() > Duration::from_secs(59));
+        assert_eq!(
+            None,
+            rate_limiter.global_wait_time_at(now + Duration::from_secs(61))
+        );
+    }
+
+    #[test]
+    fn paused_rate_limiter_waits_for_retry_after() {
+        let rate_limiter = RateLimiter::unlimited();
+        rate_limiter.pause(Some(Duration::from_secs(60)));
+        let now = Instant::now();
+
+        let wait_time = assert_err!(rate_limiter.try_acquire("example.com", now));
+        assert!(wait_time > Duration::from_secs(59));
+        assert_ok!(rate_limiter.try_acquire("example.com", now + Duration::from_secs(61)));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::authentication::UserId;
+use crate::authentication::{CsrfToken, Permission, Role, UserId};
 use actix_web::{
     error::ErrorInternalServerError, http::header::ContentType, web, Error, HttpResponse,
 };
@@ -22,15 +22,40 @@ pub async fn get_username(user_id: Uuid, db_pool: &MySqlPool) -> Result<String,
 
     Ok(row.username)
 }
+#[utoipa::path(
+    get,
+    path = "/admin/dashboard",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 200, description = "Admin dashboard", content_type = "text/html"))
+)]
 pub async fn admin_dashboard(
     user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    csrf_token: web::ReqData<CsrfToken>,
     db_pool: web::Data<MySqlPool>,
 ) -> Result<HttpResponse, Error> {
     let user_id = user_id.into_inner();
+    let role = role.into_inner();
+    let csrf_field = csrf_token.form_field();
     let username = get_username(*user_id, &db_pool)
         .await
         .map_err(internal_server_error)?;
 
+    let mut links_html = String::new();
+    if role.has_permission(Permission::PublishNewsletters) {
+        links_html
+            .push_str(r#"<li><a href="/admin/newsletter">Publish a newsletter issue</a></li>"#);
+    }
+    if role.has_permission(Permission::ManageUsers) {
+        links_html.push_str(r#"<li><a href="/admin/users">Manage users</a></li>"#);
+    }
+    if role.has_permission(Permission::ManageSubscribers) {
+        links_html.push_str(r#"<li><a href="/admin/subscribers">Manage subscribers</a></li>"#);
+        links_html.push_str(r#"<li><a href="/admin/exports">Exports</a></li>"#);
+        links_html.push_str(r#"<li><a href="/admin/webhooks">Webhook deliveries</a></li>"#);
+    }
+
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -46,11 +71,16 @@ pub async fn admin_dashboard(
         <ol>
             <li>
                 <form name="logoutForm" action="/admin/logout" method="post">
+                    {csrf_field}
                     <input type="submit" value="logout" />
                 </form>
             </li>
         </ol><ol>
             <li><a href="/admin/password">Change password</a></li>
+            <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+            <li><a href="/admin/sessions">Active sessions</a></li>
+            <li><a href="/admin/tokens">API tokens</a></li>
+            {links_html}
         </ol>
     </body>
 </html>"#
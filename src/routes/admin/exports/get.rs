@@ -0,0 +1,194 @@
+use crate::{
+    domain::SubscriptionStatus,
+    exports::{self, ExportFormat},
+    newsletter_issues::{get_newsletter_issue, list_newsletter_issues},
+    subscribers::SubscriberFilter,
+    utils::{bad_request, internal_server_error},
+};
+use actix_web::{
+    http::header::{ContentDisposition, ContentType},
+    web, HttpResponse,
+};
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+/// Only the most recent issues are offered on the page, older ones can be exported by id.
+const LISTED_ISSUES: u32 = 50;
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscriberExportParameters {
+    /// `csv` (the default) or `json`
+    format: Option<String>,
+    status: Option<String>,
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IssueExportParameters {
+    /// `csv` (the default) or `json`
+    format: Option<String>,
+}
+
+fn parse_format(format: Option<&str>) -> Result<ExportFormat, actix_web::Error> {
+    format
+        .filter(|format| !format.is_empty())
+        .map_or(Ok(ExportFormat::Csv), ExportFormat::parse)
+        .map_err(bad_request)
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/exports",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    responses((status = 200, description = "Forms to export subscribers and the delivery outcomes of issues", content_type = "text/html"))
+)]
+#[tracing::instrument(name = "Show exports", skip_all)]
+pub async fn exports_form(db_pool: web::Data<MySqlPool>) -> Result<HttpResponse, actix_web::Error> {
+    let (issues, _) = list_newsletter_issues(LISTED_ISSUES, 0, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    let mut status_options = String::from(r#"<option value="">All statuses</option>"#);
+    for status in SubscriptionStatus::ALL {
+        write!(
+            status_options,
+            r#"<option value="{status}">{status}</option>"#
+        )
+        .unwrap();
+    }
+
+    let mut issues_html = String::new();
+    for issue in issues.iter().filter(|issue| issue.published_at.is_some()) {
+        let id = issue.newsletter_issue_id;
+        write!(
+            issues_html,
+            r#"<li>{} <a href="/admin/exports/issues/{id}?format=csv">CSV</a> <a href="/admin/exports/issues/{id}?format=json">JSON</a></li>"#,
+            encode_minimal(&issue.title),
+        )
+        .unwrap();
+    }
+    if issues_html.is_empty() {
+        issues_html.push_str("<li>No issue has been published yet.</li>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Exports</title>
+        </head>
+        <body>
+            <h2>Subscribers</h2>
+            <form action="/admin/exports/subscribers" method="get">
+                <label>Status:
+                    <select name="status">{status_options}</select>
+                </label>
+                <label>Format:
+                    <select name="format">
+                        <option value="csv">CSV</option>
+                        <option value="json">JSON</option>
+                    </select>
+                </label>
+                <button type="submit">Export</button>
+            </form>
+            <h2>Delivery outcomes</h2>
+            <ul>{issues_html}</ul>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/exports/subscribers",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(SubscriberExportParameters),
+    responses(
+        (status = 200, description = "Every matching subscriber, most recent first", content_type = "text/csv"),
+        (status = 400, description = "Unknown format or status", content_type = "text/plain"),
+    )
+)]
+#[tracing::instrument(name = "Export subscribers", skip_all)]
+pub async fn export_subscribers(
+    parameters: web::Query<SubscriberExportParameters>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let SubscriberExportParameters { format, status } = parameters.into_inner();
+    let format = parse_format(format.as_deref())?;
+    let status = status
+        .filter(|status| !status.is_empty())
+        .as_deref()
+        .map(SubscriptionStatus::parse)
+        .transpose()
+        .map_err(bad_request)?;
+    let filter = SubscriberFilter {
+        status,
+        ..Default::default()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition::attachment(format!(
+            "subscribers.{}",
+            format.extension()
+        )))
+        .streaming(exports::export_subscribers(
+            filter,
+            format,
+            db_pool.get_ref().clone(),
+        )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/exports/issues/{issue_id}",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(
+        ("issue_id" = Uuid, Path, description = "Identifier of the issue"),
+        IssueExportParameters
+    ),
+    responses(
+        (status = 200, description = "The outcome of the delivery to every subscriber, by email", content_type = "text/csv"),
+        (status = 400, description = "Unknown format", content_type = "text/plain"),
+        (status = 404, description = "The issue does not exist"),
+    )
+)]
+#[tracing::instrument(name = "Export delivery outcomes", skip(parameters, db_pool))]
+pub async fn export_issue_outcomes(
+    issue_id: web::Path<Uuid>,
+    parameters: web::Query<IssueExportParameters>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let format = parse_format(parameters.format.as_deref())?;
+    if get_newsletter_issue(issue_id, db_pool.get_ref())
+        .await
+        .map_err(internal_server_error)?
+        .is_none()
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition::attachment(format!(
+            "issue-{issue_id}.{}",
+            format.extension()
+        )))
+        .streaming(exports::export_delivery_outcomes(
+            issue_id,
+            format,
+            db_pool.get_ref().clone(),
+        )))
+}
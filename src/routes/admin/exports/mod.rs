@@ -0,0 +1,2 @@
+pub(crate) mod get;
+pub use get::{export_issue_outcomes, export_subscribers, exports_form};
@@ -1,8 +1,28 @@
-use crate::session_state::TypedSession;
-use actix_web::{http::header::LOCATION, HttpResponse};
+use crate::{
+    authentication::{revoke_user_session, SessionId, UserId},
+    session_state::TypedSession,
+    utils::internal_server_error,
+};
+use actix_web::{http::header::LOCATION, web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use sqlx::MySqlPool;
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+#[utoipa::path(
+    post,
+    path = "/admin/logout",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 303, description = "Redirects to the login form"))
+)]
+pub async fn log_out(
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    session_id: web::ReqData<SessionId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    revoke_user_session(session_id.0, **user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
     session.purge();
 
     FlashMessage::info("You have successfully logged out").send();
@@ -1,9 +1,23 @@
-mod dashboard;
-mod logout;
-mod newsletter;
-mod password;
+pub(crate) mod dashboard;
+pub(crate) mod exports;
+pub(crate) mod logout;
+pub(crate) mod newsletter;
+pub(crate) mod password;
+pub(crate) mod sessions;
+pub(crate) mod subscribers;
+pub(crate) mod tokens;
+pub(crate) mod two_factor;
+pub(crate) mod users;
+pub(crate) mod webhooks;
 
 pub use dashboard::admin_dashboard;
+pub use exports::*;
 pub use logout::log_out;
 pub use newsletter::*;
 pub use password::*;
+pub use sessions::*;
+pub use subscribers::*;
+pub use tokens::*;
+pub use two_factor::*;
+pub use users::*;
+pub use webhooks::*;
@@ -1,13 +1,31 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use crate::authentication::CsrfToken;
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
 use std::fmt::Write;
 
-pub async fn publish_newsletter_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+#[utoipa::path(
+    get,
+    path = "/admin/newsletter",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["newsletters:publish"])),
+    responses((status = 200, description = "Form to publish a newsletter issue", content_type = "text/html"))
+)]
+pub async fn publish_newsletter_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+) -> HttpResponse {
     let mut message_html = String::new();
 
     for message in flash_messages.iter() {
-        write!(message_html, "<p><i>{}</i></p>", message.content()).unwrap();
+        write!(
+            message_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
     }
+    let csrf_field = csrf_token.form_field();
 
     let idempotency_key = uuid::Uuid::new_v4();
 
@@ -23,6 +41,7 @@ pub async fn publish_newsletter_form(flash_messages: IncomingFlashMessages) -> H
             <body>
                 <ul>{message_html}</ul>
                 <form action="/admin/newsletter" method="post">
+                    {csrf_field}
                     <input hidden="hidden" type="text" name="idempotency_key" value="{idempotency_key}" />
                     <label for="title">
                         Newsletter title:
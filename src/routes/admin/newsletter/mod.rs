@@ -1,5 +1,5 @@
-mod get;
-mod post;
+pub(crate) mod get;
+pub(crate) mod post;
 
 pub use get::publish_newsletter_form;
 pub use post::publish_newsletter;
@@ -1,14 +1,16 @@
 use crate::{
     authentication::UserId,
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    newsletter_issues::{enqueue_delivery_task, insert_newsletter_issue, publish_newsletter_issue},
     utils::{bad_request, internal_server_error, see_other},
 };
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
 use sqlx::MySqlPool;
+use utoipa::ToSchema;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct FormData {
     title: String,
     text_content: String,
@@ -16,6 +18,14 @@ pub struct FormData {
     idempotency_key: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/admin/newsletter",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["newsletters:publish"])),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects back to the form once the issue is queued for delivery"))
+)]
 #[tracing::instrument(
     name = "Publish a newsletter issue",
     skip_all,
@@ -69,6 +79,10 @@ pub async fn publish_newsletter(
         .await
         .context("Failed to store newletter issue details")
         .map_err(internal_server_error)?;
+    publish_newsletter_issue(&mut transaction, issue_id)
+        .await
+        .context("Failed to publish the newsletter issue")
+        .map_err(internal_server_error)?;
     enqueue_delivery_task(&mut transaction, issue_id)
         .await
         .context("Failed to enqueue delivery tasks")
@@ -90,44 +104,3 @@ fn success_message() -> FlashMessage {
         and emails will be sent out shortly",
     )
 }
-
-#[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
-    transaction: &mut sqlx::Transaction<'_, sqlx::MySql>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
-) -> Result<uuid::Uuid, sqlx::Error> {
-    let newsletter_issue_id = uuid::Uuid::new_v4();
-    sqlx::query!(
-        r#"INSERT INTO `newsletter_issues` (
-            `newsletter_issue_id`, `title`, `text_content`, `html_content`, `published_at`
-        ) VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP())"#,
-        newsletter_issue_id,
-        title,
-        text_content,
-        html_content,
-    )
-    .execute(transaction)
-    .await?;
-
-    Ok(newsletter_issue_id)
-}
-
-#[tracing::instrument(skip_all)]
-async fn enqueue_delivery_task(
-    transaction: &mut sqlx::Transaction<'_, sqlx::MySql>,
-    newsletter_issue_id: uuid::Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"INSERT INTO `issue_delivery_queue` (
-            `newsletter_issue_id`, `subscriber_email`
-        ) SELECT ?, `email` FROM `subscriptions`
-           WHERE `status`="confirmed""#,
-        newsletter_issue_id,
-    )
-    .execute(transaction)
-    .await?;
-
-    Ok(())
-}
@@ -1,14 +1,30 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use crate::authentication::CsrfToken;
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
 use std::fmt::Write;
 
+#[utoipa::path(
+    get,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 200, description = "Form to change the password", content_type = "text/html"))
+)]
 pub async fn change_password_form(
     flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for message in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", message.content()).unwrap();
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
     }
+    let csrf_field = csrf_token.form_field();
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -22,6 +38,7 @@ pub async fn change_password_form(
         <body>
             {msg_html}
             <form action="/admin/password" method="post">
+                {csrf_field}
                 <label>Current Password:
                     <input type="password" placeholder="Enter current password" name="current_password" />
                 </label>
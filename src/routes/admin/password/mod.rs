@@ -1,4 +1,4 @@
-mod get;
+pub(crate) mod get;
 pub use get::change_password_form;
-mod post;
+pub(crate) mod post;
 pub use post::change_password;
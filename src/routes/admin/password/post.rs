@@ -1,5 +1,9 @@
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials, UserId},
+    authentication::{
+        validate_credentials, validate_password_length, AuthError, Credentials, DummyPasswordHash,
+        SessionId, UserId,
+    },
+    configuration::PasswordHashSettings,
     routes::admin::dashboard::get_username,
     utils::{internal_server_error, see_other},
 };
@@ -7,18 +11,33 @@ use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::MySqlPool;
+use utoipa::ToSchema;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct FormData {
+    #[schema(value_type = String, format = Password)]
     current_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password_check: Secret<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects back to the form with the outcome"))
+)]
 pub async fn change_password(
     form_data: web::Form<FormData>,
     user_id: web::ReqData<UserId>,
+    session_id: web::ReqData<SessionId>,
     db_pool: web::Data<MySqlPool>,
+    password_hash_settings: web::Data<PasswordHashSettings>,
+    dummy_password_hash: web::Data<DummyPasswordHash>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
 
@@ -29,8 +48,8 @@ pub async fn change_password(
         return Ok(see_other("/admin/password"));
     }
 
-    if new_password.len() < 12 || new_password.len() > 128 {
-        FlashMessage::error("Password must be between 12 and 128 characters long").send();
+    if let Err(e) = validate_password_length(&form_data.0.new_password) {
+        FlashMessage::error(e).send();
 
         return Ok(see_other("/admin/password"));
     }
@@ -44,7 +63,14 @@ pub async fn change_password(
         password: form_data.0.current_password,
     };
 
-    if let Err(e) = validate_credentials(credentials, &db_pool).await {
+    if let Err(e) = validate_credentials(
+        credentials,
+        &password_hash_settings,
+        &dummy_password_hash,
+        &db_pool,
+    )
+    .await
+    {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
@@ -54,9 +80,15 @@ pub async fn change_password(
         };
     }
 
-    crate::authentication::change_password(*user_id, form_data.0.new_password, &db_pool)
-        .await
-        .map_err(internal_server_error)?;
+    crate::authentication::change_password(
+        *user_id,
+        form_data.0.new_password,
+        Some(session_id.0),
+        &password_hash_settings,
+        &db_pool,
+    )
+    .await
+    .map_err(internal_server_error)?;
 
     FlashMessage::info("Your password was successfully changed").send();
 
@@ -0,0 +1,93 @@
+use crate::{
+    authentication::{list_user_sessions, CsrfToken, SessionId, UserId},
+    utils::internal_server_error,
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+
+#[utoipa::path(
+    get,
+    path = "/admin/sessions",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 200, description = "Active sessions of the current user", content_type = "text/html"))
+)]
+pub async fn list_sessions(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    user_id: web::ReqData<UserId>,
+    current_session_id: web::ReqData<SessionId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    let sessions = list_user_sessions(**user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    let mut sessions_html = String::new();
+    for session in sessions {
+        let session_id = session.session_id;
+        let created_at = session.created_at.format("%Y-%m-%d %H:%M:%S");
+        let last_seen_at = session.last_seen_at.format("%Y-%m-%d %H:%M:%S");
+        let ip = encode_minimal(session.ip.as_deref().unwrap_or("unknown"));
+        let user_agent = encode_minimal(session.user_agent.as_deref().unwrap_or("unknown"));
+        let action_html = if session_id == current_session_id.0 {
+            "This session".to_owned()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/{session_id}/revoke" method="post">
+                        {csrf_field}
+                        <button type="submit">Revoke</button>
+                    </form>"#
+            )
+        };
+        writeln!(
+            sessions_html,
+            r#"<tr>
+                <td>{created_at}</td>
+                <td>{last_seen_at}</td>
+                <td>{ip}</td>
+                <td>{user_agent}</td>
+                <td>{action_html}</td>
+            </tr>"#
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Active sessions</title>
+        </head>
+        <body>
+            {msg_html}
+            <table>
+                <tr><th>Started</th><th>Last seen</th><th>IP address</th><th>Browser</th><th></th></tr>
+                {sessions_html}
+            </table>
+            <form action="/admin/sessions/revoke-others" method="post">
+                {csrf_field}
+                <button type="submit">Revoke all other sessions</button>
+            </form>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
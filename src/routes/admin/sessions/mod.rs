@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::list_sessions;
+pub(crate) mod post;
+pub use post::{revoke_other_sessions, revoke_session};
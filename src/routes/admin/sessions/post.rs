@@ -0,0 +1,53 @@
+use crate::{
+    authentication::{revoke_user_session, revoke_user_sessions, SessionId, UserId},
+    utils::{internal_server_error, see_other},
+};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/admin/sessions/{session_id}/revoke",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("session_id" = Uuid, Path, description = "Identifier of the session")),
+    responses((status = 303, description = "Redirects to the session list"))
+)]
+#[tracing::instrument(name = "Revoke a session", skip(user_id, db_pool))]
+pub async fn revoke_session(
+    session_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    revoke_user_session(session_id.into_inner(), **user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    FlashMessage::info("The session has been revoked").send();
+
+    Ok(see_other("/admin/sessions"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/sessions/revoke-others",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 303, description = "Redirects to the session list"))
+)]
+#[tracing::instrument(name = "Revoke all other sessions", skip_all)]
+pub async fn revoke_other_sessions(
+    user_id: web::ReqData<UserId>,
+    current_session_id: web::ReqData<SessionId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    revoke_user_sessions(**user_id, Some(current_session_id.0), db_pool.get_ref())
+        .await
+        .map_err(internal_server_error)?;
+
+    FlashMessage::info("All other sessions have been revoked").send();
+
+    Ok(see_other("/admin/sessions"))
+}
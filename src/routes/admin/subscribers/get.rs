@@ -0,0 +1,236 @@
+use crate::{
+    authentication::CsrfToken,
+    domain::SubscriptionStatus,
+    subscribers::{search_subscribers, SubscriberFilter, SubscriberSort},
+    utils::{bad_request, internal_server_error},
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+use utoipa::IntoParams;
+
+const PAGE_SIZE: u32 = 25;
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    /// Text to look for in the email or name of subscribers
+    q: Option<String>,
+    status: Option<String>,
+    sort: Option<String>,
+    /// Page number, starting from 1
+    page: Option<u32>,
+}
+
+fn status_options(selected: Option<SubscriptionStatus>) -> String {
+    let mut options_html = String::from(r#"<option value="">All statuses</option>"#);
+    for status in SubscriptionStatus::ALL {
+        let selected = if Some(status) == selected {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            options_html,
+            r#"<option value="{status}"{selected}>{status}</option>"#
+        )
+        .unwrap();
+    }
+
+    options_html
+}
+
+fn sort_options(selected: SubscriberSort) -> String {
+    let mut options_html = String::new();
+    for sort in SubscriberSort::ALL {
+        let selected = if sort == selected { " selected" } else { "" };
+        write!(
+            options_html,
+            r#"<option value="{}"{selected}>{}</option>"#,
+            sort.as_str(),
+            sort.label()
+        )
+        .unwrap();
+    }
+
+    options_html
+}
+
+fn actions_html(subscriber_id: &str, status: SubscriptionStatus, csrf_field: &str) -> String {
+    let mut actions = Vec::new();
+    if status == SubscriptionStatus::PendingConfirmation {
+        actions.push(("resend-confirmation", "Resend confirmation"));
+    }
+    if status != SubscriptionStatus::Confirmed {
+        actions.push(("confirm", "Confirm"));
+    }
+    if status != SubscriptionStatus::Unsubscribed {
+        actions.push(("unsubscribe", "Unsubscribe"));
+    }
+    actions.push(("delete", "Delete"));
+
+    let mut actions_html = String::new();
+    for (action, label) in actions {
+        write!(
+            actions_html,
+            r#"<form action="/admin/subscribers/{subscriber_id}/{action}" method="post">
+                        {csrf_field}
+                        <button type="submit">{label}</button>
+                    </form>"#
+        )
+        .unwrap();
+    }
+
+    actions_html
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(Parameters),
+    responses((status = 200, description = "A page of subscribers, with forms to manage them", content_type = "text/html"))
+)]
+#[tracing::instrument(name = "Browse subscribers", skip_all)]
+pub async fn browse_subscribers(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Parameters {
+        q,
+        status,
+        sort,
+        page,
+    } = parameters.into_inner();
+    let search = q.map(|q| q.trim().to_owned()).filter(|q| !q.is_empty());
+    let status = status
+        .filter(|status| !status.is_empty())
+        .as_deref()
+        .map(SubscriptionStatus::parse)
+        .transpose()
+        .map_err(bad_request)?;
+    let sort = sort
+        .as_deref()
+        .map(SubscriberSort::parse)
+        .transpose()
+        .map_err(bad_request)?
+        .unwrap_or_default();
+    let page = page.unwrap_or(1).max(1);
+
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    let filter = SubscriberFilter {
+        status,
+        search: search.clone(),
+        ..Default::default()
+    };
+    let (subscribers, total) = search_subscribers(
+        &filter,
+        sort,
+        PAGE_SIZE,
+        (page - 1).saturating_mul(PAGE_SIZE),
+        &db_pool,
+    )
+    .await
+    .map_err(internal_server_error)?;
+
+    let mut subscribers_html = String::new();
+    for subscriber in subscribers {
+        let subscriber_id = subscriber.subscriber_id.to_string();
+        let email = encode_minimal(&subscriber.email);
+        let name = encode_minimal(&subscriber.name);
+        let subscribed_at = subscriber.subscribed_at.format("%Y-%m-%d %H:%M:%S");
+        let status = subscriber.status;
+        let actions_html = actions_html(&subscriber_id, status, &csrf_field);
+        writeln!(
+            subscribers_html,
+            r#"<tr>
+                <td>{email}</td>
+                <td>{name}</td>
+                <td>{status}</td>
+                <td>{subscribed_at}</td>
+                <td>{actions_html}</td>
+            </tr>"#
+        )
+        .unwrap();
+    }
+
+    let page_link = |page: u32| {
+        let mut query = vec![
+            ("sort", sort.as_str().to_owned()),
+            ("page", page.to_string()),
+        ];
+        if let Some(search) = &search {
+            query.push(("q", search.clone()));
+        }
+        if let Some(status) = status {
+            query.push(("status", status.as_str().to_owned()));
+        }
+        let query = serde_urlencoded::to_string(query).unwrap();
+
+        encode_minimal(&format!("/admin/subscribers?{}", query))
+    };
+    let mut pagination_html = String::new();
+    if page > 1 {
+        write!(
+            pagination_html,
+            r#"<a href="{}">&lt; Previous</a> "#,
+            page_link(page - 1)
+        )
+        .unwrap();
+    }
+    if i64::from(page) * i64::from(PAGE_SIZE) < total {
+        write!(
+            pagination_html,
+            r#"<a href="{}">Next &gt;</a>"#,
+            page_link(page + 1)
+        )
+        .unwrap();
+    }
+
+    let search_value = encode_minimal(search.as_deref().unwrap_or_default());
+    let status_options_html = status_options(status);
+    let sort_options_html = sort_options(sort);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Subscribers</title>
+        </head>
+        <body>
+            {msg_html}
+            <form action="/admin/subscribers" method="get">
+                <input type="search" placeholder="Email or name" name="q" value="{search_value}" />
+                <select name="status">{status_options_html}</select>
+                <select name="sort">{sort_options_html}</select>
+                <button type="submit">Search</button>
+            </form>
+            <p>{total} subscriber(s) - <a href="/admin/subscribers/import">Import from a CSV file</a></p>
+            <table>
+                <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed</th><th></th></tr>
+                {subscribers_html}
+            </table>
+            <p>{pagination_html}</p>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
@@ -0,0 +1,58 @@
+use crate::authentication::CsrfToken;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use std::fmt::Write;
+
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers/import",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    responses((status = 200, description = "Form to upload a CSV file of subscribers", content_type = "text/html"))
+)]
+pub async fn import_subscribers_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+) -> HttpResponse {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Import subscribers</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>The CSV file needs a header row with <code>email</code> and <code>name</code> columns.</p>
+            <form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+                {csrf_field}
+                <label>CSV file:
+                    <input type="file" name="file" accept=".csv,text/csv" />
+                </label>
+                <br>
+                <label>
+                    <input type="checkbox" name="confirmed" value="true" />
+                    They already confirmed their subscription
+                </label>
+                <br>
+                <button type="submit">Import</button>
+            </form>
+            <p><a href="/admin/subscribers">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        ))
+}
@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::import_subscribers_form;
+pub(crate) mod post;
+pub use post::upload_subscribers;
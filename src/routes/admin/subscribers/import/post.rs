@@ -0,0 +1,132 @@
+use crate::{
+    subscriber_import::{import_subscribers, ImportError, ImportOptions},
+    utils::{internal_server_error, see_other},
+};
+use actix_multipart::Multipart;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::TryStreamExt;
+use sqlx::MySqlPool;
+use std::{
+    fs::File,
+    io::{BufReader, Seek, Write},
+};
+
+/// Largest file imported through the admin pages, bigger lists are imported from the command line.
+const MAX_FILE_SIZE: usize = 20 * 1024 * 1024;
+const WRITE_BUFFER_SIZE: usize = 256 * 1024;
+
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/import",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    request_body(
+        content = String,
+        content_type = "multipart/form-data",
+        description = "A `file` field holding the CSV file, and an optional `confirmed` field. \
+            When logged in through the browser, the first field has to be `csrf_token`"
+    ),
+    responses(
+        (status = 200, description = "Summary of the import, with the rejected rows as a CSV download", content_type = "text/html"),
+        (status = 303, description = "Redirects back to the form when the file cannot be imported")
+    )
+)]
+#[tracing::instrument(name = "Upload subscribers", skip_all)]
+pub async fn upload_subscribers(
+    mut payload: Multipart,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // The upload is spooled to disk rather than memory, since lists can be large
+    let mut file = web::block(tempfile::tempfile)
+        .await?
+        .map_err(internal_server_error)?;
+    let mut has_file = false;
+    let mut file_size = 0;
+    let mut options = ImportOptions {
+        mark_as_confirmed: false,
+    };
+    while let Some(mut field) = payload.try_next().await? {
+        match field.name() {
+            Some("file") => {
+                has_file = true;
+                let mut buffer = Vec::new();
+                while let Some(chunk) = field.try_next().await? {
+                    file_size += chunk.len();
+                    if file_size > MAX_FILE_SIZE {
+                        FlashMessage::error(format!(
+                            "The file is larger than {} MB, import it from the command line instead",
+                            MAX_FILE_SIZE / 1024 / 1024
+                        ))
+                        .send();
+
+                        return Ok(see_other("/admin/subscribers/import"));
+                    }
+                    buffer.extend_from_slice(&chunk);
+                    if buffer.len() >= WRITE_BUFFER_SIZE {
+                        file = write_to_file(file, std::mem::take(&mut buffer)).await?;
+                    }
+                }
+                file = write_to_file(file, buffer).await?;
+            }
+            Some("confirmed") => {
+                options.mark_as_confirmed = true;
+                while field.try_next().await?.is_some() {}
+            }
+            _ => while field.try_next().await?.is_some() {},
+        }
+    }
+    if !has_file {
+        FlashMessage::error("Please choose a CSV file to import").send();
+
+        return Ok(see_other("/admin/subscribers/import"));
+    }
+    file.rewind().map_err(internal_server_error)?;
+
+    let report = match import_subscribers(BufReader::new(file), &options, &db_pool).await {
+        Ok(report) => report,
+        Err(ImportError::InvalidFile(message)) => {
+            FlashMessage::error(message).send();
+
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+        Err(e) => return Err(internal_server_error(e)),
+    };
+
+    let imported = report.imported;
+    let rejected = report.rejected.len();
+    let report_html = if rejected == 0 {
+        String::new()
+    } else {
+        let report_csv = report.rejected_rows_csv().map_err(internal_server_error)?;
+        format!(
+            r#"<p><a download="import-errors.csv" href="data:text/csv;base64,{}">Download the rejected rows</a></p>"#,
+            STANDARD.encode(report_csv)
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Import subscribers</title>
+        </head>
+        <body>
+            <p>{imported} subscriber(s) imported, {rejected} row(s) rejected.</p>
+            {report_html}
+            <p><a href="/admin/subscribers">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
+
+/// Appends to the spooled upload on the blocking thread pool.
+async fn write_to_file(mut file: File, data: Vec<u8>) -> Result<File, actix_web::Error> {
+    web::block(move || file.write_all(&data).map(|()| file))
+        .await?
+        .map_err(internal_server_error)
+}
@@ -0,0 +1,9 @@
+pub(crate) mod get;
+pub use get::browse_subscribers;
+pub(crate) mod import;
+pub use import::{import_subscribers_form, upload_subscribers};
+pub(crate) mod post;
+pub use post::{
+    confirm_subscriber_manually, delete_subscriber_manually, resend_confirmation,
+    unsubscribe_subscriber_manually,
+};
@@ -0,0 +1,174 @@
+use crate::{
+    confirmation_emails::enqueue_confirmation_email,
+    domain::{SubscriberEmail, SubscriberName, SubscriptionStatus},
+    routes::subscriptions::{generate_subscription_token, persist_token},
+    subscribers::{delete_subscriber, get_subscriber, set_subscription_status, Subscriber},
+    utils::{internal_server_error, see_other},
+};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+/// Loads the subscriber an action applies to, or explains why it cannot be applied.
+async fn load_subscriber(
+    subscriber_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<Option<Subscriber>, actix_web::Error> {
+    let subscriber = get_subscriber(subscriber_id, db_pool)
+        .await
+        .map_err(internal_server_error)?;
+    if subscriber.is_none() {
+        FlashMessage::error("The subscriber does not exist").send();
+    }
+
+    Ok(subscriber)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/resend-confirmation",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(("subscriber_id" = Uuid, Path, description = "Identifier of the subscriber")),
+    responses((status = 303, description = "Redirects to the subscriber list"))
+)]
+#[tracing::instrument(name = "Resend a confirmation email", skip(db_pool))]
+pub async fn resend_confirmation(
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = load_subscriber(subscriber_id, &db_pool).await? else {
+        return Ok(see_other("/admin/subscribers"));
+    };
+    if subscriber.status != SubscriptionStatus::PendingConfirmation {
+        FlashMessage::error("Only pending subscribers can be sent a confirmation email").send();
+
+        return Ok(see_other("/admin/subscribers"));
+    }
+    // Rows stored before the current validation rules may not be deliverable
+    if SubscriberEmail::parse(&subscriber.email).is_err()
+        || SubscriberName::parse(&subscriber.name).is_err()
+    {
+        FlashMessage::error("The stored details of the subscriber are invalid").send();
+
+        return Ok(see_other("/admin/subscribers"));
+    };
+
+    let subscription_token = generate_subscription_token();
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")
+        .map_err(internal_server_error)?;
+    persist_token(&mut transaction, subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token for a subscriber.")
+        .map_err(internal_server_error)?;
+    enqueue_confirmation_email(&mut transaction, subscriber_id, &subscription_token)
+        .await
+        .context("Failed to queue the confirmation email of a subscriber.")
+        .map_err(internal_server_error)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction.")
+        .map_err(internal_server_error)?;
+
+    FlashMessage::info("The confirmation email will be sent again shortly").send();
+
+    Ok(see_other("/admin/subscribers"))
+}
+
+async fn change_status(
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+    db_pool: &MySqlPool,
+) -> Result<bool, actix_web::Error> {
+    if load_subscriber(subscriber_id, db_pool).await?.is_none() {
+        return Ok(false);
+    }
+    set_subscription_status(subscriber_id, status, db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    Ok(true)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/confirm",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(("subscriber_id" = Uuid, Path, description = "Identifier of the subscriber")),
+    responses((status = 303, description = "Redirects to the subscriber list"))
+)]
+#[tracing::instrument(name = "Confirm a subscriber manually", skip(db_pool))]
+pub async fn confirm_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if change_status(
+        subscriber_id.into_inner(),
+        SubscriptionStatus::Confirmed,
+        &db_pool,
+    )
+    .await?
+    {
+        FlashMessage::info("The subscriber has been confirmed").send();
+    }
+
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/unsubscribe",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(("subscriber_id" = Uuid, Path, description = "Identifier of the subscriber")),
+    responses((status = 303, description = "Redirects to the subscriber list"))
+)]
+#[tracing::instrument(name = "Unsubscribe a subscriber manually", skip(db_pool))]
+pub async fn unsubscribe_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if change_status(
+        subscriber_id.into_inner(),
+        SubscriptionStatus::Unsubscribed,
+        &db_pool,
+    )
+    .await?
+    {
+        FlashMessage::info("The subscriber has been unsubscribed").send();
+    }
+
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/delete",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(("subscriber_id" = Uuid, Path, description = "Identifier of the subscriber")),
+    responses((status = 303, description = "Redirects to the subscriber list"))
+)]
+#[tracing::instrument(name = "Delete a subscriber", skip(db_pool))]
+pub async fn delete_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    if load_subscriber(subscriber_id, &db_pool).await?.is_some() {
+        delete_subscriber(subscriber_id, &db_pool)
+            .await
+            .map_err(internal_server_error)?;
+        FlashMessage::info("The subscriber has been deleted").send();
+    }
+
+    Ok(see_other("/admin/subscribers"))
+}
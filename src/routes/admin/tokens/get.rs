@@ -0,0 +1,115 @@
+use crate::{
+    authentication::{list_api_tokens, CsrfToken, Permission, Role, UserId},
+    utils::internal_server_error,
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+
+#[utoipa::path(
+    get,
+    path = "/admin/tokens",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 200, description = "API tokens of the current user", content_type = "text/html"))
+)]
+pub async fn api_tokens_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    let tokens = list_api_tokens(**user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    let mut tokens_html = String::new();
+    for token in tokens {
+        let token_id = token.token_id;
+        let name = encode_minimal(&token.name);
+        let scopes = token
+            .scopes
+            .iter()
+            .map(Permission::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let created_at = token.created_at.format("%Y-%m-%d %H:%M:%S");
+        let last_used_at = match token.last_used_at {
+            Some(last_used_at) => last_used_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => "never".to_owned(),
+        };
+        writeln!(
+            tokens_html,
+            r#"<tr>
+                <td>{name}</td>
+                <td>{scopes}</td>
+                <td>{created_at}</td>
+                <td>{last_used_at}</td>
+                <td>
+                    <form action="/admin/tokens/{token_id}/revoke" method="post">
+                        {csrf_field}
+                        <button type="submit">Revoke</button>
+                    </form>
+                </td>
+            </tr>"#
+        )
+        .unwrap();
+    }
+
+    // Tokens can only be granted permissions the user already has
+    let mut scopes_html = String::new();
+    for permission in Permission::ALL {
+        if role.has_permission(permission) {
+            let scope = permission.as_str();
+            writeln!(
+                scopes_html,
+                r#"<label><input type="checkbox" name="scope" value="{scope}" /> {permission}</label><br>"#
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>API tokens</title>
+        </head>
+        <body>
+            {msg_html}
+            <table>
+                <tr><th>Name</th><th>Scopes</th><th>Created</th><th>Last used</th><th></th></tr>
+                {tokens_html}
+            </table>
+            <h2>Create a token</h2>
+            <form action="/admin/tokens" method="post">
+                {csrf_field}
+                <label>Name:
+                    <input type="text" placeholder="What is the token for?" name="name" />
+                </label>
+                <br>
+                {scopes_html}
+                <button type="submit">Create token</button>
+            </form>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
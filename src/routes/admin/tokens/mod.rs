@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::api_tokens_form;
+pub(crate) mod post;
+pub use post::{issue_api_token, revoke_token};
@@ -0,0 +1,115 @@
+use crate::{
+    authentication::{create_api_token, revoke_api_token, Permission, Role, UserId},
+    utils::{internal_server_error, see_other},
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+
+// The form carries one `scope` field per checked box, which a struct cannot capture
+type FormData = Vec<(String, String)>;
+
+#[utoipa::path(
+    post,
+    path = "/admin/tokens",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(
+        content = String,
+        content_type = "application/x-www-form-urlencoded",
+        description = "A `name` field, and one `scope` field per granted permission"
+    ),
+    responses((status = 200, description = "The new token, shown only once", content_type = "text/html"))
+)]
+#[tracing::instrument(name = "Issue an API token", skip_all)]
+pub async fn issue_api_token(
+    form_data: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut name = String::new();
+    let mut scopes = Vec::new();
+    for (key, value) in form_data.0 {
+        match key.as_str() {
+            "name" => name = value,
+            "scope" => match Permission::parse(&value) {
+                Ok(permission) if role.has_permission(permission) => scopes.push(permission),
+                _ => {
+                    FlashMessage::error(format!("The scope `{}` cannot be granted", value)).send();
+
+                    return Ok(see_other("/admin/tokens"));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+        FlashMessage::error(format!(
+            "The token name must be between 1 and {} characters long",
+            MAX_TOKEN_NAME_LENGTH
+        ))
+        .send();
+
+        return Ok(see_other("/admin/tokens"));
+    }
+
+    if scopes.is_empty() {
+        FlashMessage::error("The token must be granted at least one scope").send();
+
+        return Ok(see_other("/admin/tokens"));
+    }
+
+    let token = create_api_token(**user_id, name, &scopes, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+    let name = encode_minimal(name);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>API token created</title>
+        </head>
+        <body>
+            <p>The token <b>{name}</b> has been created.</p>
+            <p>Copy it now and send it in the <code>Authorization: Bearer</code> header,
+                it will not be shown again.</p>
+            <p><code>{token}</code></p>
+            <p><a href="/admin/tokens">Back to API tokens</a></p>
+        </body>
+    </html>"#
+        )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/tokens/{token_id}/revoke",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    params(("token_id" = Uuid, Path, description = "Identifier of the API token")),
+    responses((status = 303, description = "Redirects to the token list"))
+)]
+#[tracing::instrument(name = "Revoke an API token", skip(user_id, db_pool))]
+pub async fn revoke_token(
+    token_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    revoke_api_token(token_id.into_inner(), **user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    FlashMessage::info("The API token has been revoked").send();
+
+    Ok(see_other("/admin/tokens"))
+}
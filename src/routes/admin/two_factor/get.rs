@@ -0,0 +1,110 @@
+use crate::{
+    authentication::{generate_totp_secret, is_two_factor_enabled, totp, CsrfToken, UserId},
+    routes::admin::dashboard::get_username,
+    session_state::TypedSession,
+    utils::internal_server_error,
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::MySqlPool;
+use std::fmt::Write;
+
+#[utoipa::path(
+    get,
+    path = "/admin/two-factor",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 200, description = "Two-factor authentication settings", content_type = "text/html"))
+)]
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    let is_enabled = is_two_factor_enabled(*user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    let body_html = if is_enabled {
+        format!(
+            r#"<p>Two-factor authentication is enabled.</p>
+            <form action="/admin/two-factor/disable" method="post">
+                {csrf_field}
+                <label>Authentication code:
+                    <input type="text" inputmode="numeric" autocomplete="one-time-code" name="code" />
+                </label>
+                <button type="submit">Disable two-factor authentication</button>
+            </form>"#
+        )
+    } else {
+        // The secret only becomes active once the user proves their app generates valid codes
+        let secret = match session
+            .get_pending_totp_secret()
+            .map_err(internal_server_error)?
+        {
+            Some(secret) => Secret::new(secret),
+            None => {
+                let secret = generate_totp_secret();
+                session
+                    .insert_pending_totp_secret(secret.expose_secret())
+                    .map_err(internal_server_error)?;
+                secret
+            }
+        };
+        let username = get_username(*user_id, &db_pool)
+            .await
+            .map_err(internal_server_error)?;
+        let totp = totp(&secret, &username).map_err(internal_server_error)?;
+        let qr_code = totp.get_qr_base64().map_err(internal_server_error)?;
+        let otpauth_url = encode_minimal(&totp.get_url());
+        let secret = secret.expose_secret();
+
+        format!(
+            r#"<p>Scan the QR code with your authenticator app, then enter the code it shows.</p>
+            <img src="data:image/png;base64,{qr_code}" alt="TOTP QR code" />
+            <p>Alternatively, open <a href="{otpauth_url}">{otpauth_url}</a>
+                or type in the secret <code>{secret}</code>.</p>
+            <form action="/admin/two-factor" method="post">
+                {csrf_field}
+                <label>Authentication code:
+                    <input type="text" inputmode="numeric" autocomplete="one-time-code" name="code" />
+                </label>
+                <button type="submit">Enable two-factor authentication</button>
+            </form>"#
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Two-factor authentication</title>
+        </head>
+        <body>
+            {msg_html}
+            {body_html}
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::two_factor_form;
+pub(crate) mod post;
+pub use post::{disable_two_factor, enable_two_factor};
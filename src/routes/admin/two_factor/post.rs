@@ -0,0 +1,118 @@
+use crate::{
+    authentication::{self, verify_second_factor, verify_totp_code, TotpCipher, UserId},
+    session_state::TypedSession,
+    utils::{internal_server_error, see_other},
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::Secret;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    code: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/two-factor",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects back to the settings with the outcome"))
+)]
+#[tracing::instrument(name = "Enable two-factor authentication", skip_all)]
+pub async fn enable_two_factor(
+    form_data: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    totp_cipher: web::Data<TotpCipher>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    let secret = match session
+        .get_pending_totp_secret()
+        .map_err(internal_server_error)?
+    {
+        Some(secret) => Secret::new(secret),
+        None => return Ok(see_other("/admin/two-factor")),
+    };
+
+    let used_step =
+        match verify_totp_code(&secret, &form_data.0.code).map_err(internal_server_error)? {
+            Some(step) => step,
+            None => {
+                FlashMessage::error("The authentication code is incorrect").send();
+
+                return Ok(see_other("/admin/two-factor"));
+            }
+        };
+
+    let recovery_codes =
+        authentication::enable_two_factor(*user_id, &secret, used_step, &totp_cipher, &db_pool)
+            .await
+            .map_err(internal_server_error)?;
+    session.remove_pending_totp_secret();
+
+    let mut codes_html = String::new();
+    for code in recovery_codes {
+        writeln!(codes_html, "<li><code>{}</code></li>", code).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Recovery codes</title>
+        </head>
+        <body>
+            <p>Two-factor authentication is now enabled.</p>
+            <p>Store these recovery codes somewhere safe. Each of them can be used once
+                instead of an authentication code, and they will not be shown again.</p>
+            <ul>{codes_html}</ul>
+            <p><a href="/admin/dashboard">Continue to the dashboard</a></p>
+        </body>
+    </html>"#
+        )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/two-factor/disable",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects back to the settings with the outcome"))
+)]
+#[tracing::instrument(name = "Disable two-factor authentication", skip_all)]
+pub async fn disable_two_factor(
+    form_data: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    totp_cipher: web::Data<TotpCipher>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    let is_valid = verify_second_factor(*user_id, &form_data.0.code, &totp_cipher, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+    if !is_valid {
+        FlashMessage::error("The authentication code is incorrect").send();
+
+        return Ok(see_other("/admin/two-factor"));
+    }
+
+    authentication::disable_two_factor(*user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    FlashMessage::info("Two-factor authentication has been disabled").send();
+
+    Ok(see_other("/admin/two-factor"))
+}
@@ -0,0 +1,121 @@
+use crate::{
+    authentication::{list_users, CsrfToken, Role, UserStatus},
+    utils::internal_server_error,
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+
+fn role_options(selected: Role) -> String {
+    let mut options_html = String::new();
+    for role in Role::ALL {
+        let selected = if role == selected { " selected" } else { "" };
+        write!(
+            options_html,
+            r#"<option value="{role}"{selected}>{role}</option>"#
+        )
+        .unwrap();
+    }
+
+    options_html
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["users:manage"])),
+    responses((status = 200, description = "List of users, with forms to manage them", content_type = "text/html"))
+)]
+pub async fn manage_users_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    let users = list_users(&db_pool).await.map_err(internal_server_error)?;
+
+    let mut users_html = String::new();
+    for user in users {
+        let user_id = user.user_id;
+        let username = encode_minimal(&user.username);
+        let status = user.status.as_str();
+        let options_html = role_options(user.role);
+        let deactivate_html = match user.status {
+            UserStatus::Pending | UserStatus::Active => format!(
+                r#"<form action="/admin/users/{user_id}/deactivate" method="post">
+                        {csrf_field}
+                        <button type="submit">Deactivate</button>
+                    </form>"#
+            ),
+            UserStatus::Deactivated => String::new(),
+        };
+        writeln!(
+            users_html,
+            r#"<tr>
+                <td>{username}</td>
+                <td>{status}</td>
+                <td>
+                    <form action="/admin/users/{user_id}/role" method="post">
+                        {csrf_field}
+                        <select name="role">{options_html}</select>
+                        <button type="submit">Change role</button>
+                    </form>
+                </td>
+                <td>{deactivate_html}</td>
+            </tr>"#
+        )
+        .unwrap();
+    }
+
+    let new_user_options_html = role_options(Role::Viewer);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Manage users</title>
+        </head>
+        <body>
+            {msg_html}
+            <table>
+                <tr><th>Username</th><th>Status</th><th>Role</th><th></th></tr>
+                {users_html}
+            </table>
+            <h2>Invite a user</h2>
+            <form action="/admin/users/invite" method="post">
+                {csrf_field}
+                <label>Username:
+                    <input type="text" placeholder="Enter username" name="username" />
+                </label>
+                <br>
+                <label>Email:
+                    <input type="email" placeholder="Enter email address" name="email" />
+                </label>
+                <br>
+                <label>Role:
+                    <select name="role">{new_user_options_html}</select>
+                </label>
+                <br>
+                <button type="submit">Send invitation</button>
+            </form>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::manage_users_form;
+pub(crate) mod post;
+pub use post::{change_user_role, deactivate_user, invite_user};
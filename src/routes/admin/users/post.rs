@@ -0,0 +1,195 @@
+use crate::{
+    authentication::{
+        self, create_pending_user, delete_pending_user, InvitationToken, Role, UserId,
+    },
+    domain::SubscriberEmail,
+    email_client::{EmailClient, SendEmailError},
+    startup::{ApplicationBaseUrl, HmacSecret},
+    utils::{bad_request, internal_server_error, see_other},
+};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::MySqlPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct InvitationFormData {
+    username: String,
+    email: String,
+    role: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/invite",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["users:manage"])),
+    request_body(content = inline(InvitationFormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the user list once the invitation is sent"))
+)]
+#[tracing::instrument(
+    name = "Invite a new user",
+    skip(form_data, db_pool, email_client, base_url, hmac_secret),
+    fields(username = %form_data.username, role = %form_data.role)
+)]
+pub async fn invite_user(
+    form_data: web::Form<InvitationFormData>,
+    db_pool: web::Data<MySqlPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let InvitationFormData {
+        username,
+        email,
+        role,
+    } = form_data.0;
+    let role = Role::parse(&role).map_err(bad_request)?;
+
+    let username = username.trim();
+    if username.is_empty() {
+        FlashMessage::error("The username must not be empty").send();
+
+        return Ok(see_other("/admin/users"));
+    }
+
+    let email = match SubscriberEmail::parse(&email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    let Some(user_id) = create_pending_user(username, &email, role, &db_pool)
+        .await
+        .map_err(internal_server_error)?
+    else {
+        FlashMessage::error(format!("The username `{}` is already taken", username)).send();
+
+        return Ok(see_other("/admin/users"));
+    };
+    let token = InvitationToken::new(user_id).sign(&hmac_secret.0);
+
+    if let Err(e) = send_invitation_email(&email_client, &email, role, &base_url.0, &token).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send an invitation email",
+        );
+        // Free the username so that the invitation can be sent again.
+        delete_pending_user(user_id, &db_pool)
+            .await
+            .map_err(internal_server_error)?;
+        FlashMessage::error("Failed to send the invitation email, please try again later").send();
+
+        return Ok(see_other("/admin/users"));
+    }
+
+    FlashMessage::info(format!("An invitation has been sent to {}", email)).send();
+
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name = "Send an invitation email to a new user",
+    skip(email_client, email, base_url, token)
+)]
+async fn send_invitation_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    role: Role,
+    base_url: &str,
+    token: &str,
+) -> Result<(), SendEmailError> {
+    let setup_link = format!("{}/invitations/accept?token={}", base_url, token);
+    let plain_body = format!(
+        "You have been invited to manage our newsletter as {}.\n\
+        Visit {} to choose your password.",
+        role, setup_link
+    );
+    let html_body = format!(
+        "You have been invited to manage our newsletter as {}.<br />\
+        Click <a href=\"{}\">here</a> to choose your password.",
+        role, setup_link
+    );
+
+    email_client
+        .send_email(email, "Your invitation", &html_body, &plain_body)
+        .await
+}
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct RoleFormData {
+    role: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/role",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["users:manage"])),
+    params(("user_id" = Uuid, Path, description = "Identifier of the user")),
+    request_body(content = inline(RoleFormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the user list"))
+)]
+#[tracing::instrument(
+    name = "Change the role of a user",
+    skip(form_data, current_user_id, db_pool)
+)]
+pub async fn change_user_role(
+    user_id: web::Path<Uuid>,
+    form_data: web::Form<RoleFormData>,
+    current_user_id: web::ReqData<UserId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let role = Role::parse(&form_data.0.role).map_err(bad_request)?;
+
+    if user_id == **current_user_id && role != Role::Owner {
+        FlashMessage::error("You cannot remove the owner role from your own account").send();
+
+        return Ok(see_other("/admin/users"));
+    }
+
+    authentication::set_user_role(user_id, role, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    FlashMessage::info("The user role has been changed").send();
+
+    Ok(see_other("/admin/users"))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/deactivate",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["users:manage"])),
+    params(("user_id" = Uuid, Path, description = "Identifier of the user")),
+    responses((status = 303, description = "Redirects to the user list"))
+)]
+#[tracing::instrument(name = "Deactivate a user", skip(current_user_id, db_pool))]
+pub async fn deactivate_user(
+    user_id: web::Path<Uuid>,
+    current_user_id: web::ReqData<UserId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    if user_id == **current_user_id {
+        FlashMessage::error("You cannot deactivate your own account").send();
+
+        return Ok(see_other("/admin/users"));
+    }
+
+    authentication::deactivate_user(user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    FlashMessage::info("The user has been deactivated").send();
+
+    Ok(see_other("/admin/users"))
+}
@@ -0,0 +1,118 @@
+use crate::{
+    utils::{bad_request, internal_server_error},
+    webhooks::list_webhook_deliveries,
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+use utoipa::IntoParams;
+
+const RECENT_DELIVERIES: u32 = 100;
+const DELIVERY_STATUSES: [&str; 3] = ["pending", "delivered", "failed"];
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    /// One of `pending`, `delivered` or `failed`
+    status: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    tag = "admin",
+    security(("session_cookie" = []), ("api_token" = ["subscribers:manage"])),
+    params(Parameters),
+    responses(
+        (status = 200, description = "The most recent webhook deliveries, with their last error", content_type = "text/html"),
+        (status = 400, description = "Unknown delivery status", content_type = "text/plain"),
+    )
+)]
+#[tracing::instrument(name = "Show webhook deliveries", skip_all)]
+pub async fn webhook_deliveries(
+    parameters: web::Query<Parameters>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let status = parameters
+        .into_inner()
+        .status
+        .filter(|status| !status.is_empty());
+    if let Some(status) = &status {
+        if !DELIVERY_STATUSES.contains(&status.as_str()) {
+            return Err(bad_request(format!(
+                "{} is not a valid delivery status",
+                status
+            )));
+        }
+    }
+
+    let deliveries = list_webhook_deliveries(status.as_deref(), RECENT_DELIVERIES, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    let mut status_options = String::from(r#"<option value="">All statuses</option>"#);
+    for option in DELIVERY_STATUSES {
+        let selected = if status.as_deref() == Some(option) {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            status_options,
+            r#"<option value="{option}"{selected}>{option}</option>"#
+        )
+        .unwrap();
+    }
+
+    let mut deliveries_html = String::new();
+    for delivery in &deliveries {
+        let created_at = delivery.created_at.format("%Y-%m-%d %H:%M:%S");
+        let last_attempted_at = delivery
+            .last_attempted_at
+            .map(|attempted_at| attempted_at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        writeln!(
+            deliveries_html,
+            r#"<tr>
+                <td>{created_at}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{last_attempted_at}</td>
+                <td>{}</td>
+            </tr>"#,
+            encode_minimal(&delivery.event),
+            encode_minimal(&delivery.endpoint_url),
+            encode_minimal(&delivery.status),
+            delivery.n_attempts,
+            encode_minimal(delivery.last_error.as_deref().unwrap_or_default()),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Webhook deliveries</title>
+        </head>
+        <body>
+            <p>Endpoints are set in the <code>webhooks</code> section of the configuration file.</p>
+            <form action="/admin/webhooks" method="get">
+                <select name="status">{status_options}</select>
+                <button type="submit">Filter</button>
+            </form>
+            <table>
+                <tr><th>Created</th><th>Event</th><th>Endpoint</th><th>Status</th><th>Attempts</th><th>Last attempt</th><th>Last error</th></tr>
+                {deliveries_html}
+            </table>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+    </html>"#
+        )))
+}
@@ -0,0 +1,2 @@
+pub(crate) mod get;
+pub use get::webhook_deliveries;
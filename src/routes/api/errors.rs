@@ -0,0 +1,94 @@
+use crate::{authentication::Permission, errors::error_chain_fmt};
+use actix_web::{
+    error::{JsonPayloadError, PathError, QueryPayloadError},
+    http::{header::WWW_AUTHENTICATE, StatusCode},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use utoipa::ToSchema;
+
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("A valid API token is required")]
+    Unauthorized,
+    #[error("The API token is not allowed to {0}")]
+    Forbidden(Permission),
+    #[error("The requested resource does not exist")]
+    NotFound,
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Something went wrong while processing the request")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Body of every error returned by the API.
+#[derive(serde::Serialize, ToSchema)]
+pub struct ErrorResponse {
+    error: ErrorDetails,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct ErrorDetails {
+    /// Machine readable error code, such as `not_found`
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::NotFound => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::UnexpectedError(_) => "internal_error",
+        }
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Self::Unauthorized = self {
+            response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+
+        response.json(ErrorResponse {
+            error: ErrorDetails {
+                code: self.code(),
+                message: self.to_string(),
+            },
+        })
+    }
+}
+
+pub fn json_error_handler(error: JsonPayloadError, _request: &HttpRequest) -> actix_web::Error {
+    ApiError::InvalidRequest(error.to_string()).into()
+}
+
+pub fn query_error_handler(error: QueryPayloadError, _request: &HttpRequest) -> actix_web::Error {
+    ApiError::InvalidRequest(error.to_string()).into()
+}
+
+pub fn path_error_handler(_error: PathError, _request: &HttpRequest) -> actix_web::Error {
+    ApiError::NotFound.into()
+}
@@ -0,0 +1,71 @@
+use super::ApiError;
+use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use actix_web::{HttpRequest, HttpResponse};
+use anyhow::Context;
+use sqlx::{MySql, MySqlPool, Transaction};
+use uuid::Uuid;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+pub enum IdempotentAction {
+    StartProcessing(Transaction<'static, MySql>, Option<IdempotencyKey>),
+    ReturnSavedResponse(HttpResponse),
+}
+
+/// Starts the transaction for a mutating request, replaying the saved response instead if the
+/// client has already sent a request with the same `Idempotency-Key` header.
+pub async fn begin_idempotent_request(
+    request: &HttpRequest,
+    user_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<IdempotentAction, ApiError> {
+    let idempotency_key = match request.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => {
+            let value = value.to_str().map_err(|_| {
+                ApiError::InvalidRequest("The idempotency key must be valid ASCII".into())
+            })?;
+            let key: IdempotencyKey = value
+                .to_owned()
+                .try_into()
+                .map_err(|e: anyhow::Error| ApiError::InvalidRequest(e.to_string()))?;
+            Some(key)
+        }
+        None => None,
+    };
+
+    match idempotency_key {
+        Some(key) => match try_processing(db_pool, &key, user_id).await? {
+            NextAction::StartProcessing(transaction) => {
+                Ok(IdempotentAction::StartProcessing(transaction, Some(key)))
+            }
+            NextAction::ReturnSavedResponse(response) => {
+                Ok(IdempotentAction::ReturnSavedResponse(response))
+            }
+        },
+        None => {
+            let transaction = db_pool
+                .begin()
+                .await
+                .context("Failed to acquire a database connection from the pool.")?;
+            Ok(IdempotentAction::StartProcessing(transaction, None))
+        }
+    }
+}
+
+pub async fn finish_idempotent_request(
+    transaction: Transaction<'static, MySql>,
+    idempotency_key: Option<IdempotencyKey>,
+    user_id: Uuid,
+    response: HttpResponse,
+) -> Result<HttpResponse, ApiError> {
+    match idempotency_key {
+        Some(key) => Ok(save_response(transaction, &key, user_id, response).await?),
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit the database transaction.")?;
+            Ok(response)
+        }
+    }
+}
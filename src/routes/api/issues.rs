@@ -0,0 +1,293 @@
+use super::{
+    idempotency::{begin_idempotent_request, finish_idempotent_request, IdempotentAction},
+    ApiError, ErrorResponse,
+};
+use crate::{
+    authentication::UserId,
+    newsletter_issues::{
+        enqueue_delivery_task, get_delivery_status, get_newsletter_issue, insert_newsletter_issue,
+        list_newsletter_issues, publish_newsletter_issue, NewsletterIssue,
+    },
+};
+use actix_web::{http::header::LOCATION, web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::MySqlPool;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct IssueResponse {
+    id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    /// Either `draft` or `published`
+    status: &'static str,
+    created_at: DateTime<Utc>,
+    published_at: Option<DateTime<Utc>>,
+}
+
+impl From<NewsletterIssue> for IssueResponse {
+    fn from(issue: NewsletterIssue) -> Self {
+        Self {
+            id: issue.newsletter_issue_id,
+            status: match issue.published_at {
+                Some(_) => "published",
+                None => "draft",
+            },
+            title: issue.title,
+            text_content: issue.text_content,
+            html_content: issue.html_content,
+            created_at: issue.created_at,
+            published_at: issue.published_at,
+        }
+    }
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct IssueListResponse {
+    items: Vec<IssueResponse>,
+    page: u32,
+    per_page: u32,
+    total: i64,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct DeliveryStatusResponse {
+    pending: i64,
+    delivered: i64,
+    failed: i64,
+    skipped: i64,
+}
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct NewIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    /// Page number, starting from 1
+    page: Option<u32>,
+    /// Number of issues per page, 20 by default and at most 100
+    per_page: Option<u32>,
+}
+
+async fn load_issue(issue_id: Uuid, db_pool: &MySqlPool) -> Result<IssueResponse, ApiError> {
+    get_newsletter_issue(issue_id, db_pool)
+        .await?
+        .map(IssueResponse::from)
+        .ok_or(ApiError::NotFound)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues",
+    tag = "issues",
+    security(("api_token" = ["newsletters:publish"])),
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the original response when reused")),
+    request_body = NewIssue,
+    responses(
+        (status = 201, description = "The draft issue", body = IssueResponse),
+        (status = 400, description = "A field is missing or empty", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(name = "Create a draft issue", skip_all, fields(user_id = %&*user_id))]
+pub async fn create_issue(
+    request: HttpRequest,
+    body: web::Json<NewIssue>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let NewIssue {
+        title,
+        text_content,
+        html_content,
+    } = body.0;
+    for (field, value) in [
+        ("title", &title),
+        ("text_content", &text_content),
+        ("html_content", &html_content),
+    ] {
+        if value.trim().is_empty() {
+            return Err(ApiError::InvalidRequest(format!(
+                "The `{}` field must not be empty",
+                field
+            )));
+        }
+    }
+
+    let (mut transaction, idempotency_key) =
+        match begin_idempotent_request(&request, **user_id, &db_pool).await? {
+            IdempotentAction::StartProcessing(transaction, key) => (transaction, key),
+            IdempotentAction::ReturnSavedResponse(response) => return Ok(response),
+        };
+
+    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
+        .await
+        .context("Failed to store newsletter issue details")?;
+
+    let issue = get_newsletter_issue(issue_id, &mut transaction)
+        .await?
+        .context("The newly created issue could not be found")?;
+    let response = HttpResponse::Created()
+        .insert_header((LOCATION, format!("/api/v1/issues/{}", issue_id)))
+        .json(IssueResponse::from(issue));
+
+    finish_idempotent_request(transaction, idempotency_key, **user_id, response).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues/{issue_id}/publish",
+    tag = "issues",
+    security(("api_token" = ["newsletters:publish"])),
+    params(
+        ("issue_id" = Uuid, Path, description = "Identifier of the issue"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays the original response when reused")
+    ),
+    responses(
+        (status = 200, description = "The published issue, now queued for delivery", body = IssueResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+        (status = 404, description = "The issue does not exist", body = ErrorResponse),
+        (status = 409, description = "The issue has already been published", body = ErrorResponse)
+    )
+)]
+#[tracing::instrument(name = "Publish an issue", skip_all, fields(user_id = %&*user_id))]
+pub async fn publish_issue(
+    request: HttpRequest,
+    issue_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issue_id = issue_id.into_inner();
+    load_issue(issue_id, &db_pool).await?;
+    let (mut transaction, idempotency_key) =
+        match begin_idempotent_request(&request, **user_id, &db_pool).await? {
+            IdempotentAction::StartProcessing(transaction, key) => (transaction, key),
+            IdempotentAction::ReturnSavedResponse(response) => return Ok(response),
+        };
+
+    let is_published = publish_newsletter_issue(&mut transaction, issue_id)
+        .await
+        .context("Failed to publish the newsletter issue")?;
+    if !is_published {
+        return Err(ApiError::Conflict(
+            "The issue has already been published".into(),
+        ));
+    }
+    enqueue_delivery_task(&mut transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+
+    let issue = get_newsletter_issue(issue_id, &mut transaction)
+        .await?
+        .context("The published issue could not be found")?;
+    let response = HttpResponse::Ok().json(IssueResponse::from(issue));
+
+    finish_idempotent_request(transaction, idempotency_key, **user_id, response).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{issue_id}",
+    tag = "issues",
+    security(("api_token" = ["newsletters:publish"])),
+    params(("issue_id" = Uuid, Path, description = "Identifier of the issue")),
+    responses(
+        (status = 200, description = "The issue", body = IssueResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+        (status = 404, description = "The issue does not exist", body = ErrorResponse)
+    )
+)]
+#[tracing::instrument(name = "Get an issue", skip(db_pool))]
+pub async fn get_issue(
+    issue_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issue = load_issue(issue_id.into_inner(), &db_pool).await?;
+
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues",
+    tag = "issues",
+    security(("api_token" = ["newsletters:publish"])),
+    params(Pagination),
+    responses(
+        (status = 200, description = "A page of issues, most recent first", body = IssueListResponse),
+        (status = 400, description = "The pagination parameters are out of range", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(name = "List issues", skip_all)]
+pub async fn list_issues(
+    pagination: web::Query<Pagination>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let page = pagination.page.unwrap_or(1);
+    let per_page = pagination.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page == 0 {
+        return Err(ApiError::InvalidRequest("Pages are numbered from 1".into()));
+    }
+    if per_page == 0 || per_page > MAX_PAGE_SIZE {
+        return Err(ApiError::InvalidRequest(format!(
+            "The page size must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+
+    let offset = (page - 1).saturating_mul(per_page);
+    let (issues, total) = list_newsletter_issues(per_page, offset, &db_pool).await?;
+
+    Ok(HttpResponse::Ok().json(IssueListResponse {
+        items: issues.into_iter().map(IssueResponse::from).collect(),
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{issue_id}/delivery",
+    tag = "issues",
+    security(("api_token" = ["newsletters:publish"])),
+    params(("issue_id" = Uuid, Path, description = "Identifier of the issue")),
+    responses(
+        (status = 200, description = "Number of deliveries in each state", body = DeliveryStatusResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+        (status = 404, description = "The issue does not exist", body = ErrorResponse)
+    )
+)]
+#[tracing::instrument(name = "Get issue delivery status", skip(db_pool))]
+pub async fn get_issue_delivery_status(
+    issue_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issue_id = issue_id.into_inner();
+    load_issue(issue_id, &db_pool).await?;
+    let status = get_delivery_status(issue_id, &db_pool).await?;
+
+    Ok(HttpResponse::Ok().json(DeliveryStatusResponse {
+        pending: status.pending,
+        delivered: status.delivered,
+        failed: status.failed,
+        skipped: status.skipped,
+    }))
+}
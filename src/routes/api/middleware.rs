@@ -0,0 +1,45 @@
+use super::ApiError;
+use crate::authentication::{authenticate_bearer_token, is_permitted, BearerAuthError, Permission};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+};
+use actix_web_lab::middleware::Next;
+
+/// The API is only available to API tokens: without cookies there is no CSRF to worry about.
+pub async fn authenticate_api_clients(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    authenticate_bearer_token(req, next, true, |e| match e {
+        BearerAuthError::UnexpectedError(e) => ApiError::from(e).into(),
+        _ => ApiError::Unauthorized.into(),
+    })
+    .await
+}
+
+pub async fn reject_non_publisher_api_clients(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require_api_permission(req, next, Permission::PublishNewsletters).await
+}
+
+pub async fn reject_non_subscriber_manager_api_clients(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require_api_permission(req, next, Permission::ManageSubscribers).await
+}
+
+async fn require_api_permission(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+    permission: Permission,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if !is_permitted(&req, permission) {
+        return Err(ApiError::Forbidden(permission).into());
+    }
+
+    next.call(req).await
+}
@@ -0,0 +1,13 @@
+mod errors;
+mod idempotency;
+mod issues;
+mod middleware;
+mod subscribers;
+
+pub use errors::{
+    json_error_handler, path_error_handler, query_error_handler, ApiError, ErrorDetails,
+    ErrorResponse,
+};
+pub use issues::*;
+pub use middleware::*;
+pub use subscribers::*;
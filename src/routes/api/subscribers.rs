@@ -0,0 +1,215 @@
+use super::{ApiError, ErrorResponse};
+use crate::{
+    domain::SubscriptionStatus,
+    subscribers::{
+        get_subscriber, list_subscribers, set_subscription_status, Subscriber, SubscriberCursor,
+        SubscriberFilter,
+    },
+};
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::MySqlPool;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct SubscriberResponse {
+    id: Uuid,
+    email: String,
+    name: String,
+    /// One of `pending_confirmation`, `confirmed` or `unsubscribed`
+    status: &'static str,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl From<Subscriber> for SubscriberResponse {
+    fn from(subscriber: Subscriber) -> Self {
+        Self {
+            id: subscriber.subscriber_id,
+            email: subscriber.email,
+            name: subscriber.name,
+            status: subscriber.status.as_str(),
+            subscribed_at: subscriber.subscribed_at,
+        }
+    }
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct SubscriberListResponse {
+    items: Vec<SubscriberResponse>,
+    /// Pass it as `cursor` to fetch the next page, `null` on the last page
+    next_cursor: Option<String>,
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscriberQuery {
+    /// Only list subscribers with this status
+    status: Option<String>,
+    /// Only list subscribers whose email contains this text
+    email: Option<String>,
+    /// Cursor returned with the previous page
+    cursor: Option<String>,
+    /// Number of subscribers per page, 50 by default and at most 200
+    limit: Option<u32>,
+}
+
+async fn load_subscriber(subscriber_id: Uuid, db_pool: &MySqlPool) -> Result<Subscriber, ApiError> {
+    get_subscriber(subscriber_id, db_pool)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:manage"])),
+    params(SubscriberQuery),
+    responses(
+        (status = 200, description = "A page of subscribers, most recent first", body = SubscriberListResponse),
+        (status = 400, description = "The status, cursor or limit is invalid", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(name = "List subscribers", skip_all)]
+pub async fn list_subscribers_api(
+    query: web::Query<SubscriberQuery>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let SubscriberQuery {
+        status,
+        email,
+        cursor,
+        limit,
+    } = query.into_inner();
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(ApiError::InvalidRequest(format!(
+            "The limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    let filter = SubscriberFilter {
+        status: status
+            .as_deref()
+            .map(SubscriptionStatus::parse)
+            .transpose()
+            .map_err(ApiError::InvalidRequest)?,
+        email_contains: email.filter(|email| !email.is_empty()),
+        search: None,
+    };
+    let cursor = cursor
+        .as_deref()
+        .map(SubscriberCursor::decode)
+        .transpose()
+        .map_err(ApiError::InvalidRequest)?;
+
+    let (subscribers, next_cursor) =
+        list_subscribers(&filter, cursor.as_ref(), limit, &db_pool).await?;
+
+    Ok(HttpResponse::Ok().json(SubscriberListResponse {
+        items: subscribers
+            .into_iter()
+            .map(SubscriberResponse::from)
+            .collect(),
+        next_cursor: next_cursor.map(|cursor| cursor.encode()),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:manage"])),
+    params(("subscriber_id" = Uuid, Path, description = "Identifier of the subscriber")),
+    responses(
+        (status = 200, description = "The subscriber", body = SubscriberResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+        (status = 404, description = "The subscriber does not exist", body = ErrorResponse)
+    )
+)]
+#[tracing::instrument(name = "Get a subscriber", skip(db_pool))]
+pub async fn get_subscriber_api(
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber = load_subscriber(subscriber_id.into_inner(), &db_pool).await?;
+
+    Ok(HttpResponse::Ok().json(SubscriberResponse::from(subscriber)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscribers/{subscriber_id}/confirm",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:manage"])),
+    params(("subscriber_id" = Uuid, Path, description = "Identifier of the subscriber")),
+    responses(
+        (status = 200, description = "The confirmed subscriber", body = SubscriberResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+        (status = 404, description = "The subscriber does not exist", body = ErrorResponse)
+    )
+)]
+#[tracing::instrument(name = "Confirm a subscriber", skip(db_pool))]
+pub async fn confirm_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    change_status(
+        subscriber_id.into_inner(),
+        SubscriptionStatus::Confirmed,
+        &db_pool,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscribers/{subscriber_id}/unsubscribe",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:manage"])),
+    params(("subscriber_id" = Uuid, Path, description = "Identifier of the subscriber")),
+    responses(
+        (status = 200, description = "The unsubscribed subscriber, whose pending deliveries are dropped", body = SubscriberResponse),
+        (status = 401, description = "Missing or invalid API token", body = ErrorResponse),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorResponse),
+        (status = 404, description = "The subscriber does not exist", body = ErrorResponse)
+    )
+)]
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(db_pool))]
+pub async fn unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    change_status(
+        subscriber_id.into_inner(),
+        SubscriptionStatus::Unsubscribed,
+        &db_pool,
+    )
+    .await
+}
+
+async fn change_status(
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+    db_pool: &MySqlPool,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber = load_subscriber(subscriber_id, db_pool).await?;
+    if subscriber.status != status {
+        set_subscription_status(subscriber_id, status, db_pool).await?;
+    }
+
+    Ok(
+        HttpResponse::Ok().json(SubscriberResponse::from(Subscriber {
+            status,
+            ..subscriber
+        })),
+    )
+}
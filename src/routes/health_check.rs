@@ -1,5 +1,11 @@
 use actix_web::{HttpResponse, Responder};
 
+#[utoipa::path(
+    get,
+    path = "/health_check",
+    tag = "health",
+    responses((status = 204, description = "The application is running"))
+)]
 pub async fn health_check() -> impl Responder {
     HttpResponse::NoContent()
 }
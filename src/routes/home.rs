@@ -1,5 +1,11 @@
 use actix_web::{http::header::ContentType, HttpResponse, Responder};
 
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "pages",
+    responses((status = 200, description = "Home page", content_type = "text/html"))
+)]
 pub async fn home() -> impl Responder {
     HttpResponse::Ok()
         .content_type(ContentType::html())
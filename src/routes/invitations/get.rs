@@ -0,0 +1,83 @@
+use crate::{
+    authentication::{get_pending_username, CsrfToken, InvitationToken},
+    startup::HmacSecret,
+    utils::{bad_request, internal_server_error},
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+use utoipa::IntoParams;
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    token: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/invitations/accept",
+    tag = "authentication",
+    params(Parameters),
+    responses((status = 200, description = "Form to accept an invitation", content_type = "text/html"))
+)]
+#[tracing::instrument(name = "Show the invitation form", skip_all)]
+pub async fn accept_invitation_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    db_pool: web::Data<MySqlPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = parameters.0.token;
+    let invitation = InvitationToken::verify(&token, &hmac_secret.0).map_err(bad_request)?;
+    let username = get_pending_username(invitation.user_id, &db_pool)
+        .await
+        .map_err(internal_server_error)?
+        .ok_or_else(|| bad_request("The invitation has already been used"))?;
+
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(message.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    let username = encode_minimal(&username);
+    let token = encode_minimal(&token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+    <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+            <title>Accept invitation</title>
+        </head>
+        <body>
+            {msg_html}
+            <p>Welcome {username}! Choose a password to finish setting up your account.</p>
+            <form action="/invitations/accept" method="post">
+                {csrf_field}
+                <input hidden="hidden" type="text" name="token" value="{token}" />
+                <label>Password:
+                    <input type="password" placeholder="Enter password" name="password" />
+                </label>
+                <br>
+                <label>Confirm Password:
+                    <input type="password" placeholder="Repeat password" name="password_check" />
+                </label>
+                <br>
+                <button type="submit">Set password</button>
+            </form>
+        </body>
+    </html>"#
+        )))
+}
@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::accept_invitation_form;
+pub(crate) mod post;
+pub use post::accept_invitation;
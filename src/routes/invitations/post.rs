@@ -0,0 +1,77 @@
+use crate::{
+    authentication::{activate_invited_user, validate_password_length, InvitationToken},
+    configuration::PasswordHashSettings,
+    startup::HmacSecret,
+    utils::{bad_request, internal_server_error, see_other},
+};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::MySqlPool;
+use utoipa::ToSchema;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    token: String,
+    #[schema(value_type = String, format = Password)]
+    password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    password_check: Secret<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/invitations/accept",
+    tag = "authentication",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the login form once the account is created"))
+)]
+#[tracing::instrument(
+    name = "Accept an invitation",
+    skip_all,
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn accept_invitation(
+    form_data: web::Form<FormData>,
+    db_pool: web::Data<MySqlPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    password_hash_settings: web::Data<PasswordHashSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        token,
+        password,
+        password_check,
+    } = form_data.0;
+    let invitation = InvitationToken::verify(&token, &hmac_secret.0).map_err(bad_request)?;
+    tracing::Span::current().record("user_id", &tracing::field::display(&invitation.user_id));
+
+    let invitation_form = format!("/invitations/accept?token={}", urlencoding::encode(&token));
+
+    if password.expose_secret() != password_check.expose_secret() {
+        FlashMessage::error("Passwords do not match").send();
+
+        return Ok(see_other(invitation_form));
+    }
+
+    if let Err(e) = validate_password_length(&password) {
+        FlashMessage::error(e).send();
+
+        return Ok(see_other(invitation_form));
+    }
+
+    let activated = activate_invited_user(
+        invitation.user_id,
+        password,
+        &password_hash_settings,
+        &db_pool,
+    )
+    .await
+    .map_err(internal_server_error)?;
+    if !activated {
+        return Err(bad_request("The invitation has already been used"));
+    }
+
+    FlashMessage::info("Your account is ready, you can now log in").send();
+
+    Ok(see_other("/login"))
+}
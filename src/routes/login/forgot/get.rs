@@ -0,0 +1,51 @@
+use crate::authentication::CsrfToken;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use std::fmt::Write;
+
+#[utoipa::path(
+    get,
+    path = "/login/forgot",
+    tag = "authentication",
+    responses((status = 200, description = "Form to request a password reset link", content_type = "text/html"))
+)]
+pub async fn forgot_password_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+) -> HttpResponse {
+    let mut message_html = String::new();
+    for m in flash_messages.iter() {
+        write!(
+            message_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html>
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+        <title>Forgot password</title>
+    </head>
+    <body>
+        {message_html}
+        <form action="/login/forgot" method="post">
+            {csrf_field}
+            <label>
+                Username
+                <input type="text" placeholder="Enter Username" name="username" />
+            </label>
+            <button type="submit">Send reset link</button>
+        </form>
+        <p><a href="/login">&lt;- Back to login</a></p>
+    </body>
+</html>"#,
+        ))
+}
@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::forgot_password_form;
+pub(crate) mod post;
+pub use post::forgot_password;
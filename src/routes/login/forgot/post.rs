@@ -0,0 +1,122 @@
+use crate::{
+    authentication::{generate_reset_token, get_active_user_email, store_reset_token},
+    domain::SubscriberEmail,
+    email_client::{EmailClient, SendEmailError},
+    startup::ApplicationBaseUrl,
+    utils::{internal_server_error, see_other},
+};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::MySqlPool;
+use std::sync::Arc;
+use tracing::Instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    username: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/forgot",
+    tag = "authentication",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects back to the form, whether or not the user exists"))
+)]
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, db_pool, email_client, base_url),
+    fields(username = %form.username, user_id = tracing::field::Empty)
+)]
+pub async fn forgot_password(
+    form: web::Form<FormData>,
+    db_pool: web::Data<MySqlPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let recipient = get_active_user_email(&form.0.username, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    // The response must be the same, and take as long, whether the user exists or not, so the
+    // link is stored and sent in the background and failures are only logged.
+    if let Some((user_id, email)) = recipient {
+        tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+        tokio::spawn(
+            issue_reset_link(
+                user_id,
+                email,
+                db_pool.get_ref().clone(),
+                email_client.into_inner(),
+                base_url.0.clone(),
+            )
+            .in_current_span(),
+        );
+    }
+
+    FlashMessage::info(
+        "If the username is known, a password reset link has been sent to its email address",
+    )
+    .send();
+
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument(
+    name = "Issue a password reset link",
+    skip(email, db_pool, email_client, base_url)
+)]
+async fn issue_reset_link(
+    user_id: Uuid,
+    email: SubscriberEmail,
+    db_pool: MySqlPool,
+    email_client: Arc<EmailClient>,
+    base_url: String,
+) {
+    let outcome: Result<(), anyhow::Error> = async {
+        let token = generate_reset_token();
+        store_reset_token(user_id, &token, &db_pool).await?;
+        send_reset_email(&email_client, &email, &base_url, &token).await?;
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = outcome {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a password reset email",
+        );
+    }
+}
+
+#[tracing::instrument(
+    name = "Send a password reset email",
+    skip(email_client, email, base_url, token)
+)]
+async fn send_reset_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    base_url: &str,
+    token: &str,
+) -> Result<(), SendEmailError> {
+    let reset_link = format!("{}/login/reset?token={}", base_url, token);
+    let plain_body = format!(
+        "Somebody asked to reset your password.\n\
+        Visit {} to choose a new one. The link is valid for one hour.",
+        reset_link
+    );
+    let html_body = format!(
+        "Somebody asked to reset your password.<br />\
+        Click <a href=\"{}\">here</a> to choose a new one. The link is valid for one hour.",
+        reset_link
+    );
+
+    email_client
+        .send_email(email, "Reset your password", &html_body, &plain_body)
+        .await
+}
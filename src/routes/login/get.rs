@@ -1,13 +1,30 @@
-use actix_web::{http::header::ContentType, HttpResponse, Responder};
+use crate::authentication::CsrfToken;
+use actix_web::{http::header::ContentType, web, HttpResponse, Responder};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
 use std::fmt::Write;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> impl Responder {
+#[utoipa::path(
+    get,
+    path = "/login",
+    tag = "authentication",
+    responses((status = 200, description = "Login form", content_type = "text/html"))
+)]
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+) -> impl Responder {
     let mut message_html = String::new();
 
     for m in flash_messages.iter() {
-        write!(message_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        write!(
+            message_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(m.content())
+        )
+        .unwrap();
     }
+    let csrf_field = csrf_token.form_field();
 
     HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -21,6 +38,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> impl Responder
     <body>
         {message_html}
         <form action="/login" method="post">
+            {csrf_field}
             <label>
                 Username
                 <input type="text" placeholder="Enter Username" name="username" />
@@ -31,6 +49,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> impl Responder
             </label>
             <button type="submit">Login</button>
         </form>
+        <p><a href="/login/forgot">Forgot your password?</a></p>
     </body>
 </html>"#,
         ))
@@ -1,5 +1,11 @@
-mod get;
-mod post;
+pub(crate) mod forgot;
+pub(crate) mod get;
+pub(crate) mod post;
+pub(crate) mod reset;
+pub(crate) mod two_factor;
 
+pub use forgot::*;
 pub use get::login_form;
 pub use post::login;
+pub use reset::*;
+pub use two_factor::*;
@@ -1,16 +1,32 @@
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    audit::{record_audit_event, AuditEvent},
+    authentication::{
+        create_user_session, is_two_factor_enabled, lockout_message, validate_credentials,
+        AuthError, Credentials, DummyPasswordHash, Lockout, LoginThrottle, ThrottleDecision,
+    },
+    configuration::PasswordHashSettings,
     errors::error_chain_fmt,
     session_state::TypedSession,
+    utils::see_other,
+};
+use actix_web::{
+    error::InternalError,
+    http::header::{LOCATION, USER_AGENT},
+    web, HttpRequest, HttpResponse, Responder,
 };
-use actix_web::{error::InternalError, http::header::LOCATION, web, HttpResponse, Responder};
 use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
 use secrecy::Secret;
 use sqlx::MySqlPool;
+use std::{net::IpAddr, time::Duration};
+use utoipa::ToSchema;
+use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct FormData {
     username: String,
+    #[schema(value_type = String, format = Password)]
     password: Secret<String>,
 }
 
@@ -18,6 +34,8 @@ pub struct FormData {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("{}", lockout_message(*.0))]
+    LockedOut(Duration),
     #[error("Something went wrond")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -38,38 +56,153 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     InternalError::from_response(e, response)
 }
 
+/// Logs the user in once every authentication factor has been checked.
+pub(super) async fn start_session(
+    session: &TypedSession,
+    user_id: Uuid,
+    request: &HttpRequest,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    let ip = request.peer_addr().map(|address| address.ip().to_string());
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok());
+    let session_id = create_user_session(user_id, ip, user_agent, db_pool).await?;
+
+    session.renew();
+    session.remove_pending_user_id();
+    session.insert_user_id(user_id)?;
+    session.insert_session_id(session_id)?;
+    let now = Utc::now().timestamp();
+    session.insert_logged_in_at(now)?;
+    session.insert_last_active_at(now)?;
+
+    Ok(())
+}
+
+/// Records a failed login attempt, returning how long the caller is locked out for if this
+/// attempt used up the last one.
+pub(super) async fn record_failed_attempt(
+    login_throttle: &LoginThrottle,
+    db_pool: &MySqlPool,
+    account: &str,
+    ip: Option<IpAddr>,
+) -> Result<Option<Duration>, anyhow::Error> {
+    let lockouts = login_throttle.record_failure(account, ip).await?;
+    for lockout in &lockouts {
+        let event = match lockout {
+            Lockout::Account => AuditEvent::AccountLockedOut { account, ip },
+            Lockout::Address => AuditEvent::AddressLockedOut {
+                account,
+                ip: ip.context("An address lockout requires an address.")?,
+            },
+        };
+        tracing::warn!(?event, "Locking out after too many failed login attempts");
+        record_audit_event(event, db_pool).await?;
+    }
+
+    Ok((!lockouts.is_empty()).then(|| login_throttle.lockout_duration()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "authentication",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the dashboard, to the two-factor form, or back to the login form on failure"))
+)]
 #[tracing::instrument(
     name = "Login",
-    skip(form, db_pool, session),
+    skip(
+        form,
+        db_pool,
+        session,
+        login_throttle,
+        password_hash_settings,
+        dummy_password_hash,
+        request
+    ),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty),
 )]
 pub async fn login(
     db_pool: web::Data<MySqlPool>,
     form: web::Form<FormData>,
     session: TypedSession,
+    login_throttle: web::Data<LoginThrottle>,
+    password_hash_settings: web::Data<PasswordHashSettings>,
+    dummy_password_hash: web::Data<DummyPasswordHash>,
+    request: HttpRequest,
 ) -> Result<impl Responder, InternalError<LoginError>> {
+    let username = form.0.username;
+    let ip = request.peer_addr().map(|address| address.ip());
+    tracing::Span::current().record("username", &tracing::field::display(&username));
+
+    // Locked out attempts are rejected before paying for a password hash verification
+    match login_throttle
+        .check(&username, ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        ThrottleDecision::LockedOut { retry_after } => {
+            return Err(login_redirect(LoginError::LockedOut(retry_after)));
+        }
+        ThrottleDecision::Allowed { delay } => {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
     let credentials = Credentials {
-        username: form.0.username,
+        username: username.clone(),
         password: form.0.password,
     };
-
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
-    match validate_credentials(credentials, &db_pool).await {
+    match validate_credentials(
+        credentials,
+        &password_hash_settings,
+        &dummy_password_hash,
+        &db_pool,
+    )
+    .await
+    {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
 
-            session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            login_throttle
+                .record_success(&username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+
+            let has_two_factor = is_two_factor_enabled(user_id, &db_pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            if has_two_factor {
+                session.renew();
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+
+                return Ok(see_other("/login/two-factor"));
+            }
+
+            start_session(&session, user_id, &request, &db_pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
 
-            Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/admin/dashboard"))
-                .finish())
+            Ok(see_other("/admin/dashboard"))
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    let lockout = record_failed_attempt(&login_throttle, &db_pool, &username, ip)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    match lockout {
+                        Some(retry_after) => LoginError::LockedOut(retry_after),
+                        None => LoginError::AuthError(e.into()),
+                    }
+                }
                 _ => LoginError::UnexpectedError(e.into()),
             };
 
@@ -0,0 +1,82 @@
+use crate::{
+    authentication::{is_reset_token_valid, CsrfToken},
+    utils::{bad_request, internal_server_error},
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::MySqlPool;
+use std::fmt::Write;
+use utoipa::IntoParams;
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    token: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/login/reset",
+    tag = "authentication",
+    params(Parameters),
+    responses((status = 200, description = "Form to choose a new password", content_type = "text/html"))
+)]
+#[tracing::instrument(name = "Show the password reset form", skip_all)]
+pub async fn reset_password_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = parameters.0.token;
+    let is_valid = is_reset_token_valid(&token, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+    if !is_valid {
+        return Err(bad_request(
+            "The password reset link is invalid or has expired",
+        ));
+    }
+
+    let mut message_html = String::new();
+    for m in flash_messages.iter() {
+        write!(
+            message_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    let token = encode_minimal(&token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html>
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+        <title>Reset password</title>
+    </head>
+    <body>
+        {message_html}
+        <form action="/login/reset" method="post">
+            {csrf_field}
+            <input hidden="hidden" type="text" name="token" value="{token}" />
+            <label>New Password:
+                <input type="password" placeholder="Enter the new password" name="new_password" />
+            </label>
+            <br>
+            <label>Confirm New Password:
+                <input type="password" placeholder="Repeat new password" name="new_password_check" />
+            </label>
+            <br>
+            <button type="submit">Reset password</button>
+        </form>
+    </body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::reset_password_form;
+pub(crate) mod post;
+pub use post::reset_password;
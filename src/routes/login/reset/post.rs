@@ -0,0 +1,67 @@
+use crate::{
+    authentication::{reset_password_with_token, validate_password_length},
+    configuration::PasswordHashSettings,
+    utils::{bad_request, internal_server_error, see_other},
+};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::MySqlPool;
+use utoipa::ToSchema;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    token: String,
+    #[schema(value_type = String, format = Password)]
+    new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    new_password_check: Secret<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/reset",
+    tag = "authentication",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the login form once the password is changed"))
+)]
+#[tracing::instrument(
+    name = "Reset a forgotten password",
+    skip_all,
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn reset_password(
+    form_data: web::Form<FormData>,
+    db_pool: web::Data<MySqlPool>,
+    password_hash_settings: web::Data<PasswordHashSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        token,
+        new_password,
+        new_password_check,
+    } = form_data.0;
+    let reset_form = format!("/login/reset?token={}", urlencoding::encode(&token));
+
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error("New passwords do not match").send();
+
+        return Ok(see_other(reset_form));
+    }
+
+    if let Err(e) = validate_password_length(&new_password) {
+        FlashMessage::error(e).send();
+
+        return Ok(see_other(reset_form));
+    }
+
+    let user_id =
+        reset_password_with_token(&token, new_password, &password_hash_settings, &db_pool)
+            .await
+            .map_err(internal_server_error)?
+            .ok_or_else(|| bad_request("The password reset link is invalid or has expired"))?;
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    FlashMessage::info("Your password has been reset, you can now log in").send();
+
+    Ok(see_other("/login"))
+}
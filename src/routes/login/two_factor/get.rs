@@ -0,0 +1,63 @@
+use crate::{
+    authentication::CsrfToken,
+    session_state::TypedSession,
+    utils::{internal_server_error, see_other},
+};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use std::fmt::Write;
+
+#[utoipa::path(
+    get,
+    path = "/login/two-factor",
+    tag = "authentication",
+    responses((status = 200, description = "Form asking for the two-factor code", content_type = "text/html"))
+)]
+pub async fn two_factor_login_form(
+    flash_messages: IncomingFlashMessages,
+    csrf_token: web::ReqData<CsrfToken>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session
+        .get_pending_user_id()
+        .map_err(internal_server_error)?
+        .is_none()
+    {
+        return Ok(see_other("/login"));
+    }
+
+    let mut message_html = String::new();
+    for m in flash_messages.iter() {
+        write!(
+            message_html,
+            "<p><i>{}</i></p>",
+            encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html>
+    <head>
+        <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+        <title>Two-factor authentication</title>
+    </head>
+    <body>
+        {message_html}
+        <form action="/login/two-factor" method="post">
+            {csrf_field}
+            <label>
+                Authentication code or recovery code
+                <input type="text" autocomplete="one-time-code" name="code" />
+            </label>
+            <button type="submit">Verify</button>
+        </form>
+    </body>
+</html>"#,
+        )))
+}
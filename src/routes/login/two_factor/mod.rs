@@ -0,0 +1,4 @@
+pub(crate) mod get;
+pub use get::two_factor_login_form;
+pub(crate) mod post;
+pub use post::two_factor_login;
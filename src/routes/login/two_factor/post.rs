@@ -0,0 +1,93 @@
+use super::super::post::{record_failed_attempt, start_session};
+use crate::{
+    authentication::{
+        lockout_message, verify_second_factor, LoginThrottle, ThrottleDecision, TotpCipher,
+    },
+    session_state::TypedSession,
+    utils::{internal_server_error, see_other},
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::MySqlPool;
+use utoipa::ToSchema;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    code: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/two-factor",
+    tag = "authentication",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects to the dashboard, or back to the form on failure"))
+)]
+#[tracing::instrument(
+    name = "Verify the second login factor",
+    skip(form, session, db_pool, totp_cipher, login_throttle, request),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn two_factor_login(
+    form: web::Form<FormData>,
+    session: TypedSession,
+    db_pool: web::Data<MySqlPool>,
+    totp_cipher: web::Data<TotpCipher>,
+    login_throttle: web::Data<LoginThrottle>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = match session
+        .get_pending_user_id()
+        .map_err(internal_server_error)?
+    {
+        Some(user_id) => user_id,
+        None => return Ok(see_other("/login")),
+    };
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    // Second factors are throttled separately from passwords, keyed by the user id
+    let account = format!("two-factor:{}", user_id);
+    let ip = request.peer_addr().map(|address| address.ip());
+    match login_throttle
+        .check(&account, ip)
+        .await
+        .map_err(internal_server_error)?
+    {
+        ThrottleDecision::LockedOut { retry_after } => {
+            FlashMessage::error(lockout_message(retry_after)).send();
+
+            return Ok(see_other("/login/two-factor"));
+        }
+        ThrottleDecision::Allowed { delay } => {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    let is_valid = verify_second_factor(user_id, &form.0.code, &totp_cipher, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+    if !is_valid {
+        let lockout = record_failed_attempt(&login_throttle, &db_pool, &account, ip)
+            .await
+            .map_err(internal_server_error)?;
+        match lockout {
+            Some(retry_after) => FlashMessage::error(lockout_message(retry_after)).send(),
+            None => FlashMessage::error("The authentication code is incorrect").send(),
+        }
+
+        return Ok(see_other("/login/two-factor"));
+    }
+
+    login_throttle
+        .record_success(&account)
+        .await
+        .map_err(internal_server_error)?;
+
+    start_session(&session, user_id, &request, &db_pool)
+        .await
+        .map_err(internal_server_error)?;
+
+    Ok(see_other("/admin/dashboard"))
+}
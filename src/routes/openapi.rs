@@ -0,0 +1,112 @@
+use actix_web::HttpResponse;
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "zero2prod",
+        description = "Newsletter delivery service. Pages under `/admin` are meant for browsers, \
+            `/api/v1` is the JSON API for integrators."
+    ),
+    paths(
+        super::openapi_spec,
+        super::health_check,
+        super::home,
+        super::subscribe,
+        super::confirm,
+        super::login::get::login_form,
+        super::login::post::login,
+        super::login::forgot::get::forgot_password_form,
+        super::login::forgot::post::forgot_password,
+        super::login::reset::get::reset_password_form,
+        super::login::reset::post::reset_password,
+        super::login::two_factor::get::two_factor_login_form,
+        super::login::two_factor::post::two_factor_login,
+        super::invitations::get::accept_invitation_form,
+        super::invitations::post::accept_invitation,
+        super::list_issues,
+        super::create_issue,
+        super::get_issue,
+        super::publish_issue,
+        super::get_issue_delivery_status,
+        super::list_subscribers_api,
+        super::get_subscriber_api,
+        super::confirm_subscriber,
+        super::unsubscribe_subscriber,
+        super::admin::newsletter::get::publish_newsletter_form,
+        super::admin::newsletter::post::publish_newsletter,
+        super::admin::users::get::manage_users_form,
+        super::admin::users::post::invite_user,
+        super::admin::users::post::change_user_role,
+        super::admin::users::post::deactivate_user,
+        super::admin::subscribers::get::browse_subscribers,
+        super::admin::subscribers::import::get::import_subscribers_form,
+        super::admin::subscribers::import::post::upload_subscribers,
+        super::admin::subscribers::post::resend_confirmation,
+        super::admin::subscribers::post::confirm_subscriber_manually,
+        super::admin::subscribers::post::unsubscribe_subscriber_manually,
+        super::admin::subscribers::post::delete_subscriber_manually,
+        super::admin::exports::get::exports_form,
+        super::admin::exports::get::export_subscribers,
+        super::admin::exports::get::export_issue_outcomes,
+        super::admin::webhooks::get::webhook_deliveries,
+        super::admin::dashboard::admin_dashboard,
+        super::admin::password::get::change_password_form,
+        super::admin::password::post::change_password,
+        super::admin::logout::log_out,
+        super::admin::sessions::get::list_sessions,
+        super::admin::sessions::post::revoke_other_sessions,
+        super::admin::sessions::post::revoke_session,
+        super::admin::tokens::get::api_tokens_form,
+        super::admin::tokens::post::issue_api_token,
+        super::admin::tokens::post::revoke_token,
+        super::admin::two_factor::get::two_factor_form,
+        super::admin::two_factor::post::enable_two_factor,
+        super::admin::two_factor::post::disable_two_factor,
+    ),
+    components(schemas(
+        super::ErrorResponse,
+        super::ErrorDetails,
+        super::NewIssue,
+        super::IssueResponse,
+        super::IssueListResponse,
+        super::DeliveryStatusResponse,
+        super::SubscriberResponse,
+        super::SubscriberListResponse,
+    )),
+    modifiers(&SecuritySchemes)
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi
+            .components
+            .as_mut()
+            .expect("The schemas are registered as components");
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        // The cookie set by `SessionMiddleware` once logged in
+        components.add_security_scheme(
+            "session_cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("id"))),
+        );
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "health",
+    responses((status = 200, description = "This OpenAPI document", content_type = "application/json"))
+)]
+pub async fn openapi_spec() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
@@ -1,30 +1,61 @@
-use actix_web::{http::StatusCode, web, HttpResponse, Responder, ResponseError};
+use actix_web::{
+    guard::GuardContext, http::header::ContentType, http::StatusCode, web, HttpResponse, Responder,
+    ResponseError,
+};
 use anyhow::Context;
 use chrono::Utc;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use sqlx::{MySql, MySqlPool, Transaction};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
+    confirmation_emails::enqueue_confirmation_email,
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
-    email_client::EmailClient,
     errors::error_chain_fmt,
-    startup::ApplicationBaseUrl,
+    webhooks::{record_subscriber_events, WebhookEvent},
 };
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct FormData {
     name: String,
     email: String,
 }
 
+/// Validation failures, reported for each field of the signup form.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct FieldErrors {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+}
+
+impl FormData {
+    fn validate(self) -> Result<NewSubscriber, FieldErrors> {
+        match (
+            SubscriberName::parse(&self.name),
+            SubscriberEmail::parse(&self.email),
+        ) {
+            (Ok(name), Ok(email)) => Ok(NewSubscriber { name, email }),
+            (name, email) => Err(FieldErrors {
+                name: name.err(),
+                email: email.err(),
+            }),
+        }
+    }
+}
+
 impl TryFrom<FormData> for NewSubscriber {
     type Error = String;
 
     fn try_from(form: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(&form.name)?;
-        let email = SubscriberEmail::parse(&form.email)?;
-        Ok(NewSubscriber { name, email })
+        form.validate().map_err(|errors| {
+            errors
+                .name
+                .or(errors.email)
+                .unwrap_or_else(|| "The subscriber details are invalid".into())
+        })
     }
 }
 
@@ -32,6 +63,8 @@ impl TryFrom<FormData> for NewSubscriber {
 pub enum SubscribeError {
     #[error("{0}")]
     Validation(String),
+    #[error("The subscriber details are invalid")]
+    InvalidFields(FieldErrors),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -45,10 +78,34 @@ impl std::fmt::Debug for SubscribeError {
 impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
-            Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::Validation(_) | Self::InvalidFields(_) => StatusCode::BAD_REQUEST,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::InvalidFields(fields) => {
+                HttpResponse::build(self.status_code()).json(serde_json::json!({
+                    "error": {
+                        "code": "invalid_request",
+                        "message": self.to_string(),
+                        "fields": fields,
+                    }
+                }))
+            }
+            _ => HttpResponse::build(self.status_code())
+                .content_type(ContentType::plaintext())
+                .body(self.to_string()),
+        }
+    }
+}
+
+/// Routes requests sent by the signup widget, which posts JSON rather than a form.
+pub fn is_json_request(ctx: &GuardContext) -> bool {
+    ctx.header::<ContentType>().is_some_and(|content_type| {
+        content_type.essence_str() == mime::APPLICATION_JSON.essence_str()
+    })
 }
 
 #[tracing::instrument(name = "Persisting subscriber", skip(new_subscriber, db_transaction))]
@@ -73,37 +130,7 @@ async fn persist_subscriber(
     Ok(subscriber_id)
 }
 
-#[tracing::instrument(
-    name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
-)]
-async fn send_confirmation_email(
-    email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
-    base_url: &str,
-    subscription_token: &str,
-) -> Result<(), reqwest::Error> {
-    let confirmation_link = format!(
-        "{}/subscriptions/confirm?subscription_token={}",
-        base_url, subscription_token,
-    );
-    let plain_body = format!(
-        "Welcome to our newsletter\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
-    let html_body = format!(
-        "Welcome to our newsletter!<br />\
-                Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
-    email_client
-        .send_email(&new_subscriber.email, "Welcome", &html_body, &plain_body)
-        .await?;
-
-    Ok(())
-}
-
-fn generate_subscription_token() -> String {
+pub(crate) fn generate_subscription_token() -> String {
     let rng = thread_rng();
     rng.sample_iter(Alphanumeric)
         .map(char::from)
@@ -115,7 +142,7 @@ fn generate_subscription_token() -> String {
     name = "Store subscription token in the database",
     skip(subscription_token, db_transaction)
 )]
-async fn persist_token(
+pub(crate) async fn persist_token(
     db_transaction: &mut Transaction<'_, MySql>,
     subscriber_id: Uuid,
     subscription_token: &str,
@@ -129,9 +156,23 @@ async fn persist_token(
     Ok(())
 }
 
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(
+        content = inline(FormData),
+        content_type = "application/x-www-form-urlencoded",
+        description = "Also accepted as `application/json`, in which case responses have a JSON body"
+    ),
+    responses(
+        (status = 200, description = "The subscriber is stored, and a confirmation email queued for them"),
+        (status = 400, description = "The name or email is invalid; JSON requests get an `error` object with one message per invalid field")
+    )
+)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, db_pool, email_client, base_url),
+    skip(form, db_pool),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name,
@@ -140,10 +181,37 @@ async fn persist_token(
 pub async fn subscribe(
     form: web::Form<FormData>,
     db_pool: web::Data<MySqlPool>,
-    email_client: web::Data<EmailClient>,
-    base_url: web::Data<ApplicationBaseUrl>,
 ) -> Result<impl Responder, SubscribeError> {
     let new_subscriber = form.0.try_into().map_err(SubscribeError::Validation)?;
+    register_subscriber(new_subscriber, &db_pool).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    name = "Adding a new subscriber from JSON",
+    skip(body, db_pool),
+    fields(
+        subscriber_email = %body.email,
+        subscriber_name = %body.name,
+    )
+)]
+pub async fn subscribe_json(
+    body: web::Json<FormData>,
+    db_pool: web::Data<MySqlPool>,
+) -> Result<impl Responder, SubscribeError> {
+    let new_subscriber = body.0.validate().map_err(SubscribeError::InvalidFields)?;
+    register_subscriber(new_subscriber, &db_pool).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "pending_confirmation" })))
+}
+
+/// Stores the subscriber along with their confirmation email, which the background worker sends
+/// so that a slow or failing email service does not fail the signup.
+async fn register_subscriber(
+    new_subscriber: NewSubscriber,
+    db_pool: &MySqlPool,
+) -> Result<(), SubscribeError> {
     let mut db_transaction = db_pool
         .begin()
         .await
@@ -155,18 +223,20 @@ pub async fn subscribe(
     persist_token(&mut db_transaction, subscriber_id, subscription_token)
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
+    enqueue_confirmation_email(&mut db_transaction, subscriber_id, subscription_token)
+        .await
+        .context("Failed to queue the confirmation email of a new subscriber.")?;
+    record_subscriber_events(
+        &mut db_transaction,
+        WebhookEvent::SubscriberCreated,
+        &[subscriber_id],
+    )
+    .await
+    .context("Failed to record a subscriber.created webhook event.")?;
     db_transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction.")?;
-    send_confirmation_email(
-        email_client.as_ref(),
-        new_subscriber,
-        &base_url.0,
-        subscription_token,
-    )
-    .await
-    .context("Failed to send a confirmation email.")?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(())
 }
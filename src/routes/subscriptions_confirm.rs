@@ -1,11 +1,16 @@
 use actix_web::{http::StatusCode, web, HttpResponse, Responder, ResponseError};
 use anyhow::Context;
 use sqlx::MySqlPool;
+use utoipa::IntoParams;
 use uuid::Uuid;
 
-use crate::errors::error_chain_fmt;
+use crate::{
+    errors::error_chain_fmt,
+    webhooks::{record_subscriber_events, WebhookEvent},
+};
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Parameters {
     subscription_token: String,
 }
@@ -64,21 +69,49 @@ async fn get_subscriber_id_from_token(
 }
 
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(db_pool, subscriber_id))]
-async fn confirm_subscriber(db_pool: &MySqlPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE `subscriptions` SET `status`='confirmed' WHERE `id`=?"#,
+async fn confirm_subscriber(db_pool: &MySqlPool, subscriber_id: Uuid) -> Result<(), anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    let confirmed_rows = sqlx::query!(
+        r#"UPDATE `subscriptions` SET `status`='confirmed' WHERE `id`=? AND `status`<>'confirmed'"#,
         subscriber_id,
     )
-    .execute(db_pool)
+    .execute(&mut transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
+    .context("Failed to execute query to confirm the subscriber.")?
+    .rows_affected();
+    // Following the confirmation link again must not notify the confirmation twice
+    if confirmed_rows > 0 {
+        record_subscriber_events(
+            &mut transaction,
+            WebhookEvent::SubscriberConfirmed,
+            &[subscriber_id],
+        )
+        .await
+        .context("Failed to record a subscriber.confirmed webhook event.")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction.")?;
 
     Ok(())
 }
 
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The subscription is confirmed"),
+        (status = 401, description = "The subscription token is unknown")
+    )
+)]
 #[tracing::instrument(name = "Confirm a pending subscription", skip(parameters, db_pool))]
 pub async fn confirm(
     parameters: web::Query<Parameters>,
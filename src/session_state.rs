@@ -8,6 +8,12 @@ pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_KEY_ID: &'static str = "user_id";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const LAST_ACTIVE_AT_KEY: &'static str = "last_active_at";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const PENDING_TOTP_SECRET_KEY: &'static str = "pending_totp_secret";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -21,6 +27,62 @@ impl TypedSession {
         self.0.get(Self::USER_KEY_ID)
     }
 
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    pub fn insert_logged_in_at(&self, timestamp: i64) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LOGGED_IN_AT_KEY, timestamp)
+    }
+
+    pub fn get_logged_in_at(&self) -> Result<Option<i64>, SessionGetError> {
+        self.0.get(Self::LOGGED_IN_AT_KEY)
+    }
+
+    pub fn insert_last_active_at(&self, timestamp: i64) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LAST_ACTIVE_AT_KEY, timestamp)
+    }
+
+    pub fn get_last_active_at(&self) -> Result<Option<i64>, SessionGetError> {
+        self.0.get(Self::LAST_ACTIVE_AT_KEY)
+    }
+
+    pub fn insert_csrf_token(&self, token: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::CSRF_TOKEN_KEY, token)
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+    }
+
+    pub fn insert_pending_totp_secret(&self, secret: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_TOTP_SECRET_KEY, secret)
+    }
+
+    pub fn get_pending_totp_secret(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::PENDING_TOTP_SECRET_KEY)
+    }
+
+    pub fn remove_pending_totp_secret(&self) {
+        self.0.remove(Self::PENDING_TOTP_SECRET_KEY);
+    }
+
     pub fn purge(&self) {
         self.0.purge()
     }
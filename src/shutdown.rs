@@ -0,0 +1,54 @@
+use tokio::sync::watch;
+
+pub struct Shutdown(watch::Sender<bool>);
+
+#[derive(Clone)]
+pub struct ShutdownListener(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self(watch::channel(false).0)
+    }
+
+    pub fn listener(&self) -> ShutdownListener {
+        ShutdownListener(self.0.subscribe())
+    }
+
+    pub fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownListener {
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    pub async fn requested(&mut self) {
+        // The sender going away without triggering is treated as a shutdown request too.
+        let _ = self.0.wait_for(|requested| *requested).await;
+    }
+}
+
+#[cfg(unix)]
+pub async fn wait_for_signal() -> Result<(), std::io::Error> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    tokio::select! {
+        outcome = tokio::signal::ctrl_c() => outcome,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn wait_for_signal() -> Result<(), std::io::Error> {
+    tokio::signal::ctrl_c().await
+}
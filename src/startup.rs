@@ -1,14 +1,36 @@
 use crate::{
-    authentication::reject_anonymous_users,
-    configuration::{DatabaseSettings, Settings},
-    email_client::EmailClient,
+    authentication::{
+        authenticate_api_tokens, reject_anonymous_users, reject_api_tokens,
+        reject_invalid_csrf_tokens, reject_non_publishers, reject_non_subscriber_managers,
+        reject_non_user_managers, DummyPasswordHash, LoginThrottle, TotpCipher,
+    },
+    configuration::{DatabaseSettings, Settings, WorkerSettings},
     routes::{
-        admin_dashboard, change_password, change_password_form, confirm, health_check, home,
-        log_out, login, login_form, publish_newsletter, publish_newsletter_form, subscribe,
+        accept_invitation, accept_invitation_form, admin_dashboard, api_tokens_form,
+        authenticate_api_clients, browse_subscribers, change_password, change_password_form,
+        change_user_role, confirm, confirm_subscriber, confirm_subscriber_manually, create_issue,
+        deactivate_user, delete_subscriber_manually, disable_two_factor, enable_two_factor,
+        export_issue_outcomes, export_subscribers, exports_form, forgot_password,
+        forgot_password_form, get_issue, get_issue_delivery_status, get_subscriber_api,
+        health_check, home, import_subscribers_form, invite_user, is_json_request, issue_api_token,
+        json_error_handler, list_issues, list_sessions, list_subscribers_api, log_out, login,
+        login_form, manage_users_form, openapi_spec, path_error_handler, publish_issue,
+        publish_newsletter, publish_newsletter_form, query_error_handler,
+        reject_non_publisher_api_clients, reject_non_subscriber_manager_api_clients,
+        resend_confirmation, reset_password, reset_password_form, revoke_other_sessions,
+        revoke_session, revoke_token, subscribe, subscribe_json, two_factor_form, two_factor_login,
+        two_factor_login_form, unsubscribe_subscriber, unsubscribe_subscriber_manually,
+        upload_subscribers, webhook_deliveries,
     },
 };
-use actix_session::{storage::RedisSessionStore, SessionMiddleware};
-use actix_web::{cookie::Key, dev::Server, web, App, HttpServer};
+use actix_session::{config::PersistentSession, storage::RedisSessionStore, SessionMiddleware};
+use actix_web::{
+    cookie::{self, Key},
+    dev::{Server, ServerHandle},
+    guard,
+    http::Method,
+    web, App, HttpServer, Route,
+};
 use actix_web_flash_messages::{storage::CookieMessageStore, FlashMessagesFramework};
 use actix_web_lab::middleware::from_fn;
 use secrecy::{ExposeSecret, Secret};
@@ -31,8 +53,6 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> MySqlPool {
 
 impl Application {
     pub async fn build(configuration: Settings) -> anyhow::Result<Self> {
-        let db_connection_pool = get_connection_pool(&configuration.database);
-
         let address = format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
@@ -40,17 +60,7 @@ impl Application {
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
 
-        let email_client = configuration.email.client();
-
-        let server = run(
-            listener,
-            db_connection_pool,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
-        )
-        .await?;
+        let server = run(listener, &configuration).await?;
 
         Ok(Self { server, port })
     }
@@ -59,57 +69,419 @@ impl Application {
         self.port
     }
 
+    pub fn handle(&self) -> ServerHandle {
+        self.server.handle()
+    }
+
+    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
+        self.server.await
+    }
+}
+
+pub struct HealthCheckServer {
+    port: u16,
+    server: Server,
+}
+
+impl HealthCheckServer {
+    pub fn build(
+        configuration: &WorkerSettings,
+        shutdown_timeout_seconds: u64,
+    ) -> Result<Self, std::io::Error> {
+        let address = format!("{}:{}", configuration.host, configuration.port);
+        let listener = TcpListener::bind(address)?;
+        let port = listener.local_addr().unwrap().port();
+
+        let server = HttpServer::new(|| {
+            App::new()
+                .wrap(TracingLogger::default())
+                .route("/health_check", web::get().to(health_check))
+        })
+        .workers(1)
+        .shutdown_timeout(shutdown_timeout_seconds)
+        .disable_signals()
+        .listen(listener)?
+        .run();
+
+        Ok(Self { port, server })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn handle(&self) -> ServerHandle {
+        self.server.handle()
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
         self.server.await
     }
 }
 
+/// The part of the application a route belongs to, each mounted with its own middleware.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RouteGroup {
+    Public,
+    Login,
+    Invitations,
+    ApiIssues,
+    ApiSubscribers,
+    AdminNewsletter,
+    AdminUsers,
+    AdminSubscribers,
+    AdminExports,
+    AdminWebhooks,
+    /// The pages managing the account itself, only available to browser sessions
+    AdminAccount,
+}
+
+impl RouteGroup {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::Public => "",
+            Self::Login => "/login",
+            Self::Invitations => "/invitations",
+            Self::ApiIssues => "/api/v1/issues",
+            Self::ApiSubscribers => "/api/v1/subscribers",
+            Self::AdminNewsletter => "/admin/newsletter",
+            Self::AdminUsers => "/admin/users",
+            Self::AdminSubscribers => "/admin/subscribers",
+            Self::AdminExports => "/admin/exports",
+            Self::AdminWebhooks => "/admin/webhooks",
+            Self::AdminAccount => "/admin",
+        }
+    }
+}
+
+pub struct AppRoute {
+    pub group: RouteGroup,
+    pub method: Method,
+    /// Relative to the prefix of the group
+    pub path: &'static str,
+    handler: fn(Route) -> Route,
+}
+
+impl AppRoute {
+    fn new(
+        group: RouteGroup,
+        method: Method,
+        path: &'static str,
+        handler: fn(Route) -> Route,
+    ) -> Self {
+        Self {
+            group,
+            method,
+            path,
+            handler,
+        }
+    }
+
+    pub fn full_path(&self) -> String {
+        format!("{}{}", self.group.prefix(), self.path)
+    }
+
+    fn route(&self) -> Route {
+        (self.handler)(web::method(self.method.clone()))
+    }
+}
+
+/// Every route served by the application, in the order they are matched. The OpenAPI document
+/// is checked against this table, so that no route goes undocumented.
+pub fn app_routes() -> Vec<AppRoute> {
+    use RouteGroup::*;
+
+    vec![
+        AppRoute::new(Public, Method::GET, "/health_check", |r| r.to(health_check)),
+        AppRoute::new(Public, Method::GET, "/", |r| r.to(home)),
+        AppRoute::new(Public, Method::GET, "/api/openapi.json", |r| {
+            r.to(openapi_spec)
+        }),
+        AppRoute::new(Public, Method::POST, "/subscriptions", |r| {
+            r.guard(guard::fn_guard(is_json_request)).to(subscribe_json)
+        }),
+        AppRoute::new(Public, Method::POST, "/subscriptions", |r| r.to(subscribe)),
+        AppRoute::new(Public, Method::GET, "/subscriptions/confirm", |r| {
+            r.to(confirm)
+        }),
+        AppRoute::new(Login, Method::GET, "", |r| r.to(login_form)),
+        AppRoute::new(Login, Method::POST, "", |r| r.to(login)),
+        AppRoute::new(Login, Method::GET, "/forgot", |r| {
+            r.to(forgot_password_form)
+        }),
+        AppRoute::new(Login, Method::POST, "/forgot", |r| r.to(forgot_password)),
+        AppRoute::new(Login, Method::GET, "/reset", |r| r.to(reset_password_form)),
+        AppRoute::new(Login, Method::POST, "/reset", |r| r.to(reset_password)),
+        AppRoute::new(Login, Method::GET, "/two-factor", |r| {
+            r.to(two_factor_login_form)
+        }),
+        AppRoute::new(Login, Method::POST, "/two-factor", |r| {
+            r.to(two_factor_login)
+        }),
+        AppRoute::new(Invitations, Method::GET, "/accept", |r| {
+            r.to(accept_invitation_form)
+        }),
+        AppRoute::new(Invitations, Method::POST, "/accept", |r| {
+            r.to(accept_invitation)
+        }),
+        AppRoute::new(ApiIssues, Method::GET, "", |r| r.to(list_issues)),
+        AppRoute::new(ApiIssues, Method::POST, "", |r| r.to(create_issue)),
+        AppRoute::new(ApiIssues, Method::GET, "/{issue_id}", |r| r.to(get_issue)),
+        AppRoute::new(ApiIssues, Method::POST, "/{issue_id}/publish", |r| {
+            r.to(publish_issue)
+        }),
+        AppRoute::new(ApiIssues, Method::GET, "/{issue_id}/delivery", |r| {
+            r.to(get_issue_delivery_status)
+        }),
+        AppRoute::new(ApiSubscribers, Method::GET, "", |r| {
+            r.to(list_subscribers_api)
+        }),
+        AppRoute::new(ApiSubscribers, Method::GET, "/{subscriber_id}", |r| {
+            r.to(get_subscriber_api)
+        }),
+        AppRoute::new(
+            ApiSubscribers,
+            Method::POST,
+            "/{subscriber_id}/confirm",
+            |r| r.to(confirm_subscriber),
+        ),
+        AppRoute::new(
+            ApiSubscribers,
+            Method::POST,
+            "/{subscriber_id}/unsubscribe",
+            |r| r.to(unsubscribe_subscriber),
+        ),
+        AppRoute::new(AdminNewsletter, Method::GET, "", |r| {
+            r.to(publish_newsletter_form)
+        }),
+        AppRoute::new(AdminNewsletter, Method::POST, "", |r| {
+            r.to(publish_newsletter)
+        }),
+        AppRoute::new(AdminUsers, Method::GET, "", |r| r.to(manage_users_form)),
+        AppRoute::new(AdminUsers, Method::POST, "/invite", |r| r.to(invite_user)),
+        AppRoute::new(AdminUsers, Method::POST, "/{user_id}/role", |r| {
+            r.to(change_user_role)
+        }),
+        AppRoute::new(AdminUsers, Method::POST, "/{user_id}/deactivate", |r| {
+            r.to(deactivate_user)
+        }),
+        AppRoute::new(AdminSubscribers, Method::GET, "", |r| {
+            r.to(browse_subscribers)
+        }),
+        AppRoute::new(AdminSubscribers, Method::GET, "/import", |r| {
+            r.to(import_subscribers_form)
+        }),
+        AppRoute::new(AdminSubscribers, Method::POST, "/import", |r| {
+            r.to(upload_subscribers)
+        }),
+        AppRoute::new(
+            AdminSubscribers,
+            Method::POST,
+            "/{subscriber_id}/resend-confirmation",
+            |r| r.to(resend_confirmation),
+        ),
+        AppRoute::new(
+            AdminSubscribers,
+            Method::POST,
+            "/{subscriber_id}/confirm",
+            |r| r.to(confirm_subscriber_manually),
+        ),
+        AppRoute::new(
+            AdminSubscribers,
+            Method::POST,
+            "/{subscriber_id}/unsubscribe",
+            |r| r.to(unsubscribe_subscriber_manually),
+        ),
+        AppRoute::new(
+            AdminSubscribers,
+            Method::POST,
+            "/{subscriber_id}/delete",
+            |r| r.to(delete_subscriber_manually),
+        ),
+        AppRoute::new(AdminExports, Method::GET, "", |r| r.to(exports_form)),
+        AppRoute::new(AdminExports, Method::GET, "/subscribers", |r| {
+            r.to(export_subscribers)
+        }),
+        AppRoute::new(AdminExports, Method::GET, "/issues/{issue_id}", |r| {
+            r.to(export_issue_outcomes)
+        }),
+        AppRoute::new(AdminWebhooks, Method::GET, "", |r| r.to(webhook_deliveries)),
+        AppRoute::new(AdminAccount, Method::GET, "/dashboard", |r| {
+            r.to(admin_dashboard)
+        }),
+        AppRoute::new(AdminAccount, Method::GET, "/password", |r| {
+            r.to(change_password_form)
+        }),
+        AppRoute::new(AdminAccount, Method::POST, "/password", |r| {
+            r.to(change_password)
+        }),
+        AppRoute::new(AdminAccount, Method::POST, "/logout", |r| r.to(log_out)),
+        AppRoute::new(AdminAccount, Method::GET, "/sessions", |r| {
+            r.to(list_sessions)
+        }),
+        AppRoute::new(AdminAccount, Method::POST, "/sessions/revoke-others", |r| {
+            r.to(revoke_other_sessions)
+        }),
+        AppRoute::new(
+            AdminAccount,
+            Method::POST,
+            "/sessions/{session_id}/revoke",
+            |r| r.to(revoke_session),
+        ),
+        AppRoute::new(AdminAccount, Method::GET, "/tokens", |r| {
+            r.to(api_tokens_form)
+        }),
+        AppRoute::new(AdminAccount, Method::POST, "/tokens", |r| {
+            r.to(issue_api_token)
+        }),
+        AppRoute::new(
+            AdminAccount,
+            Method::POST,
+            "/tokens/{token_id}/revoke",
+            |r| r.to(revoke_token),
+        ),
+        AppRoute::new(AdminAccount, Method::GET, "/two-factor", |r| {
+            r.to(two_factor_form)
+        }),
+        AppRoute::new(AdminAccount, Method::POST, "/two-factor", |r| {
+            r.to(enable_two_factor)
+        }),
+        AppRoute::new(AdminAccount, Method::POST, "/two-factor/disable", |r| {
+            r.to(disable_two_factor)
+        }),
+    ]
+}
+
+/// Mounts the routes of a group in the scope serving its prefix.
+fn configure_routes(group: RouteGroup) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |config| {
+        for route in app_routes()
+            .into_iter()
+            .filter(|route| route.group == group)
+        {
+            if group == RouteGroup::AdminAccount {
+                // These pages share the `/admin` scope with the other admin pages, so each of
+                // them keeps API tokens away on its own
+                config.service(
+                    web::resource(route.path)
+                        .guard(guard::Method(route.method.clone()))
+                        .wrap(from_fn(reject_api_tokens))
+                        .route(route.route()),
+                );
+            } else {
+                config.route(route.path, route.route());
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
-pub async fn run(
-    listener: TcpListener,
-    db_connection_pool: MySqlPool,
-    email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
-) -> anyhow::Result<Server> {
-    let connection = web::Data::new(db_connection_pool);
-    let email_client = web::Data::new(email_client);
-    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
-    let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
+pub async fn run(listener: TcpListener, configuration: &Settings) -> anyhow::Result<Server> {
+    let application = &configuration.application;
+    let connection = web::Data::new(get_connection_pool(&configuration.database));
+    let email_client = web::Data::new(configuration.email.clone().client());
+    let base_url = web::Data::new(ApplicationBaseUrl(application.base_url.clone()));
+    let secret_key = Key::from(application.hmac_secret.expose_secret().as_bytes());
+    let hmac_secret = web::Data::new(HmacSecret(application.hmac_secret.clone()));
+    let totp_cipher = web::Data::new(TotpCipher::new(&application.totp_encryption_key));
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_store = RedisSessionStore::new(configuration.redis_uri.expose_secret()).await?;
+    let login_throttle = web::Data::new(
+        LoginThrottle::new(&configuration.redis_uri, application.login_throttle.clone()).await?,
+    );
+    // Computed once, it is as slow to verify as the hashes of real users
+    let dummy_password_hash = web::Data::new(DummyPasswordHash::new(&application.password_hash)?);
+    let password_hash_settings = web::Data::new(application.password_hash.clone());
+    // Sessions expire in Redis, and their cookie in the browser, with their maximum lifetime
+    let session_ttl = cookie::time::Duration::seconds(
+        i64::try_from(application.session.max_lifetime_seconds).unwrap_or(i64::MAX),
+    );
+    let session_settings = web::Data::new(application.session.clone());
     Ok(HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
             .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
-            .route("/health_check", web::get().to(health_check))
-            .route("/", web::get().to(home))
-            .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
+            .wrap(
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .session_lifecycle(PersistentSession::default().session_ttl(session_ttl))
+                    .build(),
+            )
+            // JSON bodies are only accepted by the API and by the subscription form
+            .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+            .configure(configure_routes(RouteGroup::Public))
+            .service(
+                web::scope("/login")
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .configure(configure_routes(RouteGroup::Login)),
+            )
+            .service(
+                web::scope("/invitations")
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .configure(configure_routes(RouteGroup::Invitations)),
+            )
+            .service(
+                web::scope("/api/v1")
+                    .wrap(from_fn(authenticate_api_clients))
+                    .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+                    .app_data(web::PathConfig::default().error_handler(path_error_handler))
+                    .service(
+                        web::scope("/issues")
+                            .wrap(from_fn(reject_non_publisher_api_clients))
+                            .configure(configure_routes(RouteGroup::ApiIssues)),
+                    )
+                    .service(
+                        web::scope("/subscribers")
+                            .wrap(from_fn(reject_non_subscriber_manager_api_clients))
+                            .configure(configure_routes(RouteGroup::ApiSubscribers)),
+                    ),
+            )
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
                     .wrap(from_fn(reject_anonymous_users))
-                    .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
-                    .route("/logout", web::post().to(log_out))
-                    .route("/newsletter", web::get().to(publish_newsletter_form))
-                    .route("/newsletter", web::post().to(publish_newsletter)),
+                    .wrap(from_fn(authenticate_api_tokens))
+                    .service(
+                        web::scope("/newsletter")
+                            .wrap(from_fn(reject_non_publishers))
+                            .configure(configure_routes(RouteGroup::AdminNewsletter)),
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(from_fn(reject_non_user_managers))
+                            .configure(configure_routes(RouteGroup::AdminUsers)),
+                    )
+                    .service(
+                        web::scope("/subscribers")
+                            .wrap(from_fn(reject_non_subscriber_managers))
+                            .configure(configure_routes(RouteGroup::AdminSubscribers)),
+                    )
+                    .service(
+                        web::scope("/exports")
+                            .wrap(from_fn(reject_non_subscriber_managers))
+                            .configure(configure_routes(RouteGroup::AdminExports)),
+                    )
+                    .service(
+                        web::scope("/webhooks")
+                            .wrap(from_fn(reject_non_subscriber_managers))
+                            .configure(configure_routes(RouteGroup::AdminWebhooks)),
+                    )
+                    .configure(configure_routes(RouteGroup::AdminAccount)),
             )
             .app_data(connection.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(hmac_secret.clone())
+            .app_data(totp_cipher.clone())
+            .app_data(login_throttle.clone())
+            .app_data(password_hash_settings.clone())
+            .app_data(dummy_password_hash.clone())
+            .app_data(session_settings.clone())
     })
+    .shutdown_timeout(application.shutdown_timeout_seconds)
+    .disable_signals()
     .listen(listener)?
     .run())
 }
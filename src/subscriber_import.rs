@@ -0,0 +1,383 @@
+use crate::{
+    domain::{SubscriberEmail, SubscriberName, SubscriptionStatus},
+    errors::error_chain_fmt,
+    routes::generate_subscription_token,
+    webhooks::{record_subscriber_events, WebhookEvent},
+};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{MySql, MySqlPool, QueryBuilder, Transaction};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Number of rows inserted by each transaction.
+const BATCH_SIZE: usize = 500;
+
+pub struct ImportOptions {
+    /// Imports subscribers as confirmed rather than pending confirmation, for lists that were
+    /// already double opt-in with the previous provider
+    pub mark_as_confirmed: bool,
+}
+
+/// A row of the CSV file that was not imported.
+pub struct RejectedRow {
+    pub line: u64,
+    pub email: String,
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Default)]
+pub struct ImportReport {
+    pub imported: u64,
+    pub rejected: Vec<RejectedRow>,
+}
+
+impl ImportReport {
+    fn reject(&mut self, line: u64, email: &str, name: &str, reason: impl Into<String>) {
+        self.rejected.push(RejectedRow {
+            line,
+            email: email.to_owned(),
+            name: name.to_owned(),
+            reason: reason.into(),
+        });
+    }
+
+    /// The rejected rows as CSV, for them to be fixed and imported again.
+    pub fn rejected_rows_csv(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["line", "email", "name", "error"])?;
+        for row in &self.rejected {
+            writer.write_record([&row.line.to_string(), &row.email, &row.name, &row.reason])?;
+        }
+
+        writer
+            .into_inner()
+            .context("Failed to write the import report")
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum ImportError {
+    #[error("{0}")]
+    InvalidFile(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+struct ValidRow {
+    line: u64,
+    email: SubscriberEmail,
+    name: SubscriberName,
+}
+
+/// Imports the subscribers listed in a CSV file with `email` and `name` columns, reading it one
+/// row at a time.
+///
+/// Rows are skipped when they are invalid, repeat an earlier row, match an existing subscriber,
+/// or match an address that unsubscribed: those are suppressed and never subscribed again by an
+/// import. Subscribers imported as pending are queued a confirmation email.
+#[tracing::instrument(name = "Import subscribers", skip(reader, options, db_pool))]
+pub async fn import_subscribers<R>(
+    reader: R,
+    options: &ImportOptions,
+    db_pool: &MySqlPool,
+) -> Result<ImportReport, ImportError>
+where
+    R: std::io::Read + Send + 'static,
+{
+    // Reading the file blocks, so it happens on its own thread, handing valid rows over in batches
+    let (sender, mut batches) = mpsc::channel(1);
+    let reading = tokio::task::spawn_blocking(move || read_rows(reader, sender));
+
+    let mut report = ImportReport::default();
+    while let Some(batch) = batches.recv().await {
+        import_batch(batch, options, &mut report, db_pool).await?;
+    }
+    let read_report = reading.await.context("Failed to read the CSV file")??;
+
+    report.rejected.extend(read_report.rejected);
+    report.rejected.sort_by_key(|row| row.line);
+
+    Ok(report)
+}
+
+/// Validates the rows of the CSV file, returning a report of the rejected ones.
+fn read_rows<R>(
+    reader: R,
+    batches: mpsc::Sender<Vec<ValidRow>>,
+) -> Result<ImportReport, ImportError>
+where
+    R: std::io::Read,
+{
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+    let headers = reader
+        .headers()
+        .map_err(|e| ImportError::InvalidFile(format!("The CSV header cannot be read: {}", e)))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                ImportError::InvalidFile(format!("The CSV file has no `{}` column", name))
+            })
+    };
+    let email_column = column("email")?;
+    let name_column = column("name")?;
+
+    let mut report = ImportReport::default();
+    let mut seen_emails = HashSet::new();
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) if e.is_io_error() => {
+                return Err(anyhow::Error::new(e)
+                    .context("Failed to read the CSV file")
+                    .into())
+            }
+            Err(e) => {
+                let line = e.position().map_or(0, |position| position.line());
+                report.reject(line, "", "", e.to_string());
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let email = record.get(email_column).unwrap_or_default();
+        let name = record.get(name_column).unwrap_or_default();
+
+        match (SubscriberEmail::parse(email), SubscriberName::parse(name)) {
+            (Ok(valid_email), Ok(valid_name)) => {
+                if !seen_emails.insert(email.to_lowercase()) {
+                    report.reject(line, email, name, "The email appears earlier in the file");
+                    continue;
+                }
+                batch.push(ValidRow {
+                    line,
+                    email: valid_email,
+                    name: valid_name,
+                });
+                // The import failed on the other end when nobody is receiving batches anymore
+                if batch.len() == BATCH_SIZE
+                    && batches.blocking_send(std::mem::take(&mut batch)).is_err()
+                {
+                    return Ok(report);
+                }
+            }
+            (email_result, name_result) => {
+                let reason = [email_result.err(), name_result.err()]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                report.reject(line, email, name, reason);
+            }
+        }
+    }
+    if !batch.is_empty() {
+        let _ = batches.blocking_send(batch);
+    }
+
+    Ok(report)
+}
+
+async fn import_batch(
+    rows: Vec<ValidRow>,
+    options: &ImportOptions,
+    report: &mut ImportReport,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT `email`, `status` FROM `subscriptions` WHERE `email` IN (",
+    );
+    let mut emails = query.separated(", ");
+    for row in &rows {
+        emails.push_bind(row.email.as_ref());
+    }
+    emails.push_unseparated(")");
+    let existing: HashMap<String, String> = query
+        .build_query_as::<(String, String)>()
+        .fetch_all(&mut transaction)
+        .await
+        .context("Failed to perform a query to find existing subscribers.")?
+        .into_iter()
+        .map(|(email, status)| (email.to_lowercase(), status))
+        .collect();
+
+    let mut new_rows = Vec::with_capacity(rows.len());
+    for row in rows {
+        match existing.get(&row.email.as_ref().to_lowercase()) {
+            Some(status) if status == SubscriptionStatus::Unsubscribed.as_str() => report.reject(
+                row.line,
+                row.email.as_ref(),
+                row.name.as_ref(),
+                "The address has unsubscribed and is suppressed",
+            ),
+            Some(_) => report.reject(
+                row.line,
+                row.email.as_ref(),
+                row.name.as_ref(),
+                "The address is already subscribed",
+            ),
+            None => new_rows.push((Uuid::new_v4(), row)),
+        }
+    }
+
+    if !new_rows.is_empty() {
+        let status = if options.mark_as_confirmed {
+            SubscriptionStatus::Confirmed
+        } else {
+            SubscriptionStatus::PendingConfirmation
+        };
+        let subscribed_at = Utc::now();
+        // The same addresses may be subscribing while the batch is imported, those rows are
+        // skipped rather than failing the whole batch
+        let inserted_rows = {
+            let mut query = QueryBuilder::<MySql>::new(
+                "INSERT IGNORE INTO `subscriptions` (`id`, `email`, `name`, `subscribed_at`, `status`) ",
+            );
+            query.push_values(&new_rows, |mut values, (id, row)| {
+                values
+                    .push_bind(id)
+                    .push_bind(row.email.as_ref())
+                    .push_bind(row.name.as_ref())
+                    .push_bind(subscribed_at)
+                    .push_bind(status.as_str());
+            });
+            query
+                .build()
+                .execute(&mut transaction)
+                .await
+                .context("Failed to insert a batch of subscribers.")?
+                .rows_affected()
+        };
+        if inserted_rows < new_rows.len() as u64 {
+            let inserted_ids = inserted_subscriber_ids(&new_rows, &mut transaction).await?;
+            new_rows.retain(|(id, row)| {
+                let is_inserted = inserted_ids.contains(id);
+                if !is_inserted {
+                    report.reject(
+                        row.line,
+                        row.email.as_ref(),
+                        row.name.as_ref(),
+                        "The address is already subscribed",
+                    );
+                }
+                is_inserted
+            });
+        }
+    }
+
+    if !new_rows.is_empty() {
+        let subscriber_ids: Vec<_> = new_rows.iter().map(|(id, _)| *id).collect();
+        record_subscriber_events(
+            &mut transaction,
+            WebhookEvent::SubscriberCreated,
+            &subscriber_ids,
+        )
+        .await
+        .context("Failed to record subscriber.created webhook events.")?;
+        if !options.mark_as_confirmed {
+            queue_confirmation_emails(&subscriber_ids, &mut transaction).await?;
+        }
+        report.imported += new_rows.len() as u64;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction.")?;
+
+    Ok(())
+}
+
+async fn inserted_subscriber_ids(
+    rows: &[(Uuid, ValidRow)],
+    transaction: &mut Transaction<'_, MySql>,
+) -> Result<HashSet<Uuid>, anyhow::Error> {
+    let mut query = QueryBuilder::<MySql>::new("SELECT `id` FROM `subscriptions` WHERE `id` IN (");
+    let mut ids = query.separated(", ");
+    for (id, _) in rows {
+        ids.push_bind(id);
+    }
+    ids.push_unseparated(")");
+
+    query
+        .build_query_as::<(String,)>()
+        .fetch_all(transaction)
+        .await
+        .context("Failed to perform a query to find the imported subscribers.")?
+        .iter()
+        .map(|(id,)| {
+            Uuid::parse_str(id).context("Failed to parse subscriber UUID loaded from the database.")
+        })
+        .collect()
+}
+
+/// Stores a confirmation token for each subscriber and queues the email carrying it, exactly like
+/// a subscription through the form does.
+async fn queue_confirmation_emails(
+    subscriber_ids: &[Uuid],
+    transaction: &mut Transaction<'_, MySql>,
+) -> Result<(), anyhow::Error> {
+    let tokens: Vec<_> = subscriber_ids
+        .iter()
+        .map(|id| (generate_subscription_token(), *id))
+        .collect();
+
+    for table in ["subscription_tokens", "confirmation_email_outbox"] {
+        let mut query = QueryBuilder::<MySql>::new(format!(
+            "INSERT INTO `{}` (`subscription_token`, `subscriber_id`) ",
+            table
+        ));
+        query.push_values(&tokens, |mut values, (token, id)| {
+            values.push_bind(token).push_bind(id);
+        });
+        query
+            .build()
+            .execute(&mut *transaction)
+            .await
+            .with_context(|| format!("Failed to insert a batch of rows into `{}`.", table))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::ImportReport;
+
+    #[test]
+    fn report_quotes_fields_containing_separators() {
+        let mut report = ImportReport::default();
+        report.reject(
+            3,
+            "a@example.com",
+            "Doe, John",
+            "The address is already subscribed",
+        );
+
+        let csv = String::from_utf8(report.rejected_rows_csv().unwrap()).unwrap();
+
+        assert_eq!(
+            "line,email,name,error\n3,a@example.com,\"Doe, John\",The address is already subscribed\n",
+            csv
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use crate::{
+    domain::SubscriptionStatus,
+    webhooks::{record_subscriber_events, WebhookEvent},
+};
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+/// A stored subscriber. Their email and name are kept as stored, since rows that predate the
+/// current validation rules must still be listed and managed.
+pub struct Subscriber {
+    pub subscriber_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Default)]
+pub struct SubscriberFilter {
+    pub status: Option<SubscriptionStatus>,
+    pub email_contains: Option<String>,
+    /// Matches subscribers whose email or name contains the text
+    pub search: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SubscriberSort {
+    #[default]
+    Newest,
+    Oldest,
+    EmailAscending,
+    EmailDescending,
+    NameAscending,
+    NameDescending,
+}
+
+impl SubscriberSort {
+    pub const ALL: [Self; 6] = [
+        Self::Newest,
+        Self::Oldest,
+        Self::EmailAscending,
+        Self::EmailDescending,
+        Self::NameAscending,
+        Self::NameDescending,
+    ];
+
+    pub fn parse(sort: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == sort)
+            .ok_or_else(|| format!("{} is not a valid sort order", sort))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+            Self::EmailAscending => "email_asc",
+            Self::EmailDescending => "email_desc",
+            Self::NameAscending => "name_asc",
+            Self::NameDescending => "name_desc",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Newest => "Newest first",
+            Self::Oldest => "Oldest first",
+            Self::EmailAscending => "Email (A-Z)",
+            Self::EmailDescending => "Email (Z-A)",
+            Self::NameAscending => "Name (A-Z)",
+            Self::NameDescending => "Name (Z-A)",
+        }
+    }
+}
+
+/// Position right after the last subscriber of a page, subscribers being listed from the most
+/// recent one.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SubscriberCursor {
+    subscribed_at: DateTime<Utc>,
+    subscriber_id: Uuid,
+}
+
+impl SubscriberCursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.subscribed_at.timestamp(),
+            self.subscriber_id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        let invalid = || "The cursor is invalid".to_owned();
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (timestamp, subscriber_id) = decoded.split_once(':').ok_or_else(invalid)?;
+        let timestamp = timestamp.parse().map_err(|_| invalid())?;
+
+        Ok(Self {
+            subscribed_at: Utc
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .ok_or_else(invalid)?,
+            subscriber_id: Uuid::parse_str(subscriber_id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+struct SubscriberRow {
+    id: String,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl TryFrom<SubscriberRow> for Subscriber {
+    type Error = anyhow::Error;
+
+    fn try_from(row: SubscriberRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            subscriber_id: Uuid::parse_str(&row.id)
+                .context("Failed to parse subscriber UUID loaded from the database.")?,
+            email: row.email,
+            name: row.name,
+            status: SubscriptionStatus::parse(&row.status).map_err(anyhow::Error::msg)?,
+            subscribed_at: row.subscribed_at,
+        })
+    }
+}
+
+// Matches the term literally, rather than letting `%` and `_` act as wildcards
+fn like_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+/// Lists up to `limit` subscribers, along with the cursor of the next page if there is one.
+#[tracing::instrument(skip(filter, cursor, db_pool))]
+pub async fn list_subscribers(
+    filter: &SubscriberFilter,
+    cursor: Option<&SubscriberCursor>,
+    limit: u32,
+    db_pool: &MySqlPool,
+) -> Result<(Vec<Subscriber>, Option<SubscriberCursor>), anyhow::Error> {
+    let status = filter.status.map(|status| status.as_str());
+    let email_pattern = filter.email_contains.as_deref().map(like_pattern);
+    let search_pattern = filter.search.as_deref().map(like_pattern);
+    let cursor_subscribed_at = cursor.map(|cursor| cursor.subscribed_at);
+    let cursor_subscriber_id = cursor.map(|cursor| cursor.subscriber_id);
+
+    let rows = sqlx::query_as!(
+        SubscriberRow,
+        r#"SELECT `id`, `email`, `name`, `status`, `subscribed_at`
+             FROM `subscriptions`
+            WHERE (? IS NULL OR `status` = ?)
+              AND (? IS NULL OR `email` LIKE ?)
+              AND (? IS NULL OR `email` LIKE ? OR `name` LIKE ?)
+              AND (? IS NULL OR `subscribed_at` < ? OR (`subscribed_at` = ? AND `id` < ?))
+            ORDER BY `subscribed_at` DESC, `id` DESC
+            LIMIT ?"#,
+        status,
+        status,
+        email_pattern,
+        email_pattern,
+        search_pattern,
+        search_pattern,
+        search_pattern,
+        cursor_subscribed_at,
+        cursor_subscribed_at,
+        cursor_subscribed_at,
+        cursor_subscriber_id,
+        // One more than requested, to find out whether there is a next page
+        limit + 1,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to list subscribers.")?;
+
+    let mut subscribers = rows
+        .into_iter()
+        .map(Subscriber::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let next_cursor = if subscribers.len() > limit as usize {
+        subscribers.truncate(limit as usize);
+        subscribers.last().map(|subscriber| SubscriberCursor {
+            subscribed_at: subscriber.subscribed_at,
+            subscriber_id: subscriber.subscriber_id,
+        })
+    } else {
+        None
+    };
+
+    Ok((subscribers, next_cursor))
+}
+
+/// Lists a page of subscribers in the requested order, along with the number of subscribers
+/// matching the filter.
+#[tracing::instrument(skip(filter, db_pool))]
+pub async fn search_subscribers(
+    filter: &SubscriberFilter,
+    sort: SubscriberSort,
+    limit: u32,
+    offset: u32,
+    db_pool: &MySqlPool,
+) -> Result<(Vec<Subscriber>, i64), anyhow::Error> {
+    let status = filter.status.map(|status| status.as_str());
+    let email_pattern = filter.email_contains.as_deref().map(like_pattern);
+    let search_pattern = filter.search.as_deref().map(like_pattern);
+    let sort = sort.as_str();
+
+    let total = sqlx::query!(
+        r#"SELECT COUNT(*) AS `total!: i64`
+             FROM `subscriptions`
+            WHERE (? IS NULL OR `status` = ?)
+              AND (? IS NULL OR `email` LIKE ?)
+              AND (? IS NULL OR `email` LIKE ? OR `name` LIKE ?)"#,
+        status,
+        status,
+        email_pattern,
+        email_pattern,
+        search_pattern,
+        search_pattern,
+        search_pattern,
+    )
+    .fetch_one(db_pool)
+    .await
+    .context("Failed to perform a query to count subscribers.")?
+    .total;
+
+    // The sort order cannot be bound as a parameter, hence one `CASE` per column and direction
+    let rows = sqlx::query_as!(
+        SubscriberRow,
+        r#"SELECT `id`, `email`, `name`, `status`, `subscribed_at`
+             FROM `subscriptions`
+            WHERE (? IS NULL OR `status` = ?)
+              AND (? IS NULL OR `email` LIKE ?)
+              AND (? IS NULL OR `email` LIKE ? OR `name` LIKE ?)
+            ORDER BY CASE ? WHEN 'email_asc' THEN `email` WHEN 'name_asc' THEN `name` END ASC,
+                     CASE ? WHEN 'email_desc' THEN `email` WHEN 'name_desc' THEN `name` END DESC,
+                     CASE ? WHEN 'oldest' THEN `subscribed_at` END ASC,
+                     `subscribed_at` DESC,
+                     `id` DESC
+            LIMIT ? OFFSET ?"#,
+        status,
+        status,
+        email_pattern,
+        email_pattern,
+        search_pattern,
+        search_pattern,
+        search_pattern,
+        sort,
+        sort,
+        sort,
+        limit,
+        offset,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to search subscribers.")?;
+
+    let subscribers = rows
+        .into_iter()
+        .map(Subscriber::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((subscribers, total))
+}
+
+#[tracing::instrument(skip(db_pool))]
+pub async fn get_subscriber(
+    subscriber_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        SubscriberRow,
+        r#"SELECT `id`, `email`, `name`, `status`, `subscribed_at`
+             FROM `subscriptions`
+            WHERE `id` = ?"#,
+        subscriber_id
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve a subscriber.")?;
+
+    row.map(Subscriber::try_from).transpose()
+}
+
+/// Changes the status of a subscriber, dropping the deliveries still queued for them when they
+/// are no longer confirmed. Webhooks are only notified when the status actually changes.
+#[tracing::instrument(skip(db_pool))]
+pub async fn set_subscription_status(
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    let changed_rows = sqlx::query!(
+        r#"UPDATE `subscriptions` SET `status` = ? WHERE `id` = ? AND `status` <> ?"#,
+        status.as_str(),
+        subscriber_id,
+        status.as_str(),
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to change the subscription status.")?
+    .rows_affected();
+
+    let event = match status {
+        SubscriptionStatus::Confirmed => Some(WebhookEvent::SubscriberConfirmed),
+        SubscriptionStatus::Unsubscribed => Some(WebhookEvent::SubscriberUnsubscribed),
+        SubscriptionStatus::PendingConfirmation => None,
+    };
+    if let Some(event) = event.filter(|_| changed_rows > 0) {
+        record_subscriber_events(&mut transaction, event, &[subscriber_id])
+            .await
+            .context("Failed to record a webhook event.")?;
+    }
+
+    if status != SubscriptionStatus::Confirmed {
+        sqlx::query!(
+            r#"DELETE FROM `issue_delivery_queue`
+                WHERE `subscriber_email` = (SELECT `email` FROM `subscriptions` WHERE `id` = ?)"#,
+            subscriber_id,
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to execute query to drop queued deliveries.")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction.")?;
+
+    Ok(())
+}
+
+/// Removes a subscriber along with their confirmation tokens and queued deliveries, keeping the
+/// outcomes of past deliveries.
+#[tracing::instrument(skip(db_pool))]
+pub async fn delete_subscriber(
+    subscriber_id: Uuid,
+    db_pool: &MySqlPool,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire a database connection from the pool.")?;
+
+    sqlx::query!(
+        r#"DELETE FROM `issue_delivery_queue`
+            WHERE `subscriber_email` = (SELECT `email` FROM `subscriptions` WHERE `id` = ?)"#,
+        subscriber_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to drop queued deliveries.")?;
+
+    sqlx::query!(
+        r#"DELETE FROM `subscription_tokens` WHERE `subscriber_id` = ?"#,
+        subscriber_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to delete subscription tokens.")?;
+
+    sqlx::query!(
+        r#"DELETE FROM `subscriptions` WHERE `id` = ?"#,
+        subscriber_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to execute query to delete a subscriber.")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction.")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{like_pattern, SubscriberCursor, SubscriberSort};
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_ok_eq};
+    use uuid::Uuid;
+
+    #[test]
+    fn cursor_round_trips_through_its_encoded_form() {
+        let cursor = SubscriberCursor {
+            subscribed_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            subscriber_id: Uuid::new_v4(),
+        };
+
+        assert_ok_eq!(SubscriberCursor::decode(&cursor.encode()), cursor);
+    }
+
+    #[test]
+    fn garbage_cursor_is_rejected() {
+        assert_err!(SubscriberCursor::decode("not a cursor"));
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(like_pattern("50%_off"), "%50\\%\\_off%");
+    }
+
+    #[test]
+    fn sort_orders_round_trip_through_their_names() {
+        for sort in SubscriberSort::ALL {
+            assert_ok_eq!(SubscriberSort::parse(sort.as_str()), sort);
+        }
+        assert_err!(SubscriberSort::parse("random"));
+    }
+}
@@ -1,4 +1,5 @@
 use actix_web::{http::header::LOCATION, HttpResponse};
+use std::time::Duration;
 
 pub fn internal_server_error<Error>(error: Error) -> actix_web::Error
 where
@@ -22,3 +23,29 @@ where
 {
     actix_web::error::ErrorBadRequest(error)
 }
+
+/// How long to wait after `n_attempts` failed attempts: `base` after the first one, doubling after
+/// each further one, but never longer than `max`.
+pub fn exponential_backoff(base: Duration, n_attempts: u32, max: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(n_attempts.saturating_sub(1));
+
+    base.saturating_mul(factor).min(max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::exponential_backoff;
+    use std::time::Duration;
+
+    #[test]
+    fn exponential_backoff_doubles_up_to_a_limit() {
+        let base = Duration::from_secs(30);
+        let max = Duration::from_secs(60 * 60);
+
+        assert_eq!(base, exponential_backoff(base, 0, max));
+        assert_eq!(base, exponential_backoff(base, 1, max));
+        assert_eq!(Duration::from_secs(120), exponential_backoff(base, 3, max));
+        assert_eq!(max, exponential_backoff(base, 30, max));
+        assert_eq!(max, exponential_backoff(base, u32::MAX, max));
+    }
+}
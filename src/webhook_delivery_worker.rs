@@ -0,0 +1,387 @@
+use crate::{
+    configuration::WorkerSettings,
+    issue_delivery_worker::ExecutionOutcome,
+    newsletter_issues::mark_next_sent_issue,
+    shutdown::ShutdownListener,
+    webhooks::{SendWebhookError, WebhookClient, WebhookEvent},
+};
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+use std::{sync::Arc, time::Duration};
+use tracing::{field::display, Span};
+use uuid::{fmt::Hyphenated, Uuid};
+
+/// A claimed delivery is given back to the queue if its outcome is not recorded within the
+/// request timeout plus this margin, e.g. because the worker crashed mid-request.
+const CLAIM_MARGIN: Duration = Duration::from_secs(30);
+
+/// Rows removed by one cleanup statement, to keep each of them short.
+const CLEANUP_BATCH_SIZE: u32 = 1000;
+
+/// Runs one step of the whole webhook pipeline, delivering to any endpoint. The background worker
+/// runs these steps in separate loops instead.
+pub async fn try_execute_webhook_task(
+    db_pool: &MySqlPool,
+    webhook_client: &WebhookClient,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    match try_dispatch_webhook_event(db_pool, webhook_client).await? {
+        ExecutionOutcome::EmptyQueue => try_deliver_webhook(db_pool, webhook_client, None).await,
+        outcome => Ok(outcome),
+    }
+}
+
+/// Notices issues that finished sending, then fans events out to the configured endpoints, then
+/// removes deliveries and events older than the retention period.
+#[tracing::instrument(skip_all)]
+pub async fn try_dispatch_webhook_event(
+    db_pool: &MySqlPool,
+    webhook_client: &WebhookClient,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    if mark_next_sent_issue(db_pool).await?.is_some()
+        || dispatch_event(db_pool, webhook_client).await?
+        || delete_expired_webhooks(db_pool, webhook_client).await?
+    {
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    Ok(ExecutionOutcome::EmptyQueue)
+}
+
+/// Attempts one delivery that is due, to the given endpoint or to any of them. The delivery is
+/// claimed in a short transaction so that no lock is held while waiting for the endpoint.
+#[tracing::instrument(
+    skip(db_pool, webhook_client),
+    fields(
+        webhook_event=tracing::field::Empty,
+        endpoint_url=tracing::field::Empty,
+    )
+)]
+pub async fn try_deliver_webhook(
+    db_pool: &MySqlPool,
+    webhook_client: &WebhookClient,
+    endpoint_url: Option<&str>,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some(delivery) = claim_delivery(db_pool, webhook_client, endpoint_url).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    let event = sqlx::query!(
+        r#"SELECT `event`, `payload` FROM `webhook_events` WHERE `event_id` = ?"#,
+        delivery.event_id,
+    )
+    .fetch_one(db_pool)
+    .await?;
+    Span::current()
+        .record("webhook_event", &display(&event.event))
+        .record("endpoint_url", &display(&delivery.endpoint_url));
+
+    let outcome = webhook_client
+        .send(
+            &delivery.endpoint_url,
+            delivery.event_id,
+            &event.event,
+            event.payload,
+        )
+        .await;
+
+    // Only record the outcome if the claim has not expired and been taken over meanwhile.
+    match outcome {
+        Ok(()) => {
+            sqlx::query!(
+                r#"UPDATE `webhook_deliveries`
+                      SET `status` = 'delivered', `last_error` = NULL
+                    WHERE `delivery_id` = ? AND `n_attempts` = ?"#,
+                delivery.delivery_id,
+                delivery.n_attempts,
+            )
+            .execute(db_pool)
+            .await?;
+        }
+        Err(e) => {
+            let gives_up = matches!(e, SendWebhookError::UnknownEndpoint)
+                || delivery.n_attempts as u32 >= webhook_client.max_attempts();
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                n_attempts = delivery.n_attempts,
+                gives_up,
+                "Failed to deliver a webhook event",
+            );
+            let retry_delay = webhook_client.retry_delay(delivery.n_attempts as u32);
+            sqlx::query!(
+                r#"UPDATE `webhook_deliveries`
+                      SET `status` = IF(?, 'failed', 'pending'), `last_error` = ?,
+                          `next_attempt_at` = CURRENT_TIMESTAMP() + INTERVAL ? SECOND
+                    WHERE `delivery_id` = ? AND `n_attempts` = ?"#,
+                gives_up,
+                e.to_string(),
+                retry_delay.as_secs(),
+                delivery.delivery_id,
+                delivery.n_attempts,
+            )
+            .execute(db_pool)
+            .await?;
+        }
+    }
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Creates one delivery per endpoint interested in the oldest event that has not been dispatched
+/// yet, returning `false` if there is none.
+#[tracing::instrument(skip_all)]
+async fn dispatch_event(
+    db_pool: &MySqlPool,
+    webhook_client: &WebhookClient,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = db_pool.begin().await?;
+    let Some(event) = sqlx::query!(
+        r#"SELECT `event_id` AS "event_id: Hyphenated", `event`
+             FROM `webhook_events`
+            WHERE `dispatched_at` IS NULL
+            ORDER BY `created_at`
+            LIMIT 1
+              FOR UPDATE
+             SKIP LOCKED"#
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    else {
+        return Ok(false);
+    };
+    let event_id: Uuid = event.event_id.into();
+
+    let endpoints: Vec<_> = match WebhookEvent::parse(&event.event) {
+        Ok(webhook_event) => webhook_client
+            .endpoints()
+            .iter()
+            .filter(|endpoint| endpoint.receives(webhook_event))
+            .collect(),
+        Err(e) => {
+            tracing::warn!(error.message = %e, "Dropping an unknown webhook event");
+            Vec::new()
+        }
+    };
+    if !endpoints.is_empty() {
+        let mut query = QueryBuilder::<MySql>::new(
+            "INSERT INTO `webhook_deliveries` (`delivery_id`, `event_id`, `endpoint_url`) ",
+        );
+        query.push_values(endpoints, |mut values, endpoint| {
+            values
+                .push_bind(Uuid::new_v4())
+                .push_bind(event_id)
+                .push_bind(endpoint.url.as_str());
+        });
+        query.build().execute(&mut transaction).await?;
+    }
+
+    sqlx::query!(
+        r#"UPDATE `webhook_events` SET `dispatched_at` = CURRENT_TIMESTAMP() WHERE `event_id` = ?"#,
+        event_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+struct ClaimedDelivery {
+    delivery_id: Uuid,
+    event_id: Uuid,
+    endpoint_url: String,
+    /// Including the attempt being made
+    n_attempts: i32,
+}
+
+/// Counts an attempt and postpones the delivery until it is over, committing right away so that
+/// the other workers skip it without waiting.
+#[tracing::instrument(skip(db_pool, webhook_client))]
+async fn claim_delivery(
+    db_pool: &MySqlPool,
+    webhook_client: &WebhookClient,
+    endpoint_url: Option<&str>,
+) -> Result<Option<ClaimedDelivery>, anyhow::Error> {
+    let mut transaction = db_pool.begin().await?;
+    let Some(row) = sqlx::query!(
+        r#"SELECT `delivery_id` AS "delivery_id: Hyphenated", `event_id` AS "event_id: Hyphenated",
+                  `endpoint_url`, `n_attempts`
+             FROM `webhook_deliveries`
+            WHERE `status` = 'pending' AND `next_attempt_at` <= CURRENT_TIMESTAMP()
+              AND (? IS NULL OR `endpoint_url` = ?)
+            ORDER BY `next_attempt_at`
+            LIMIT 1
+              FOR UPDATE
+             SKIP LOCKED"#,
+        endpoint_url,
+        endpoint_url,
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let delivery_id: Uuid = row.delivery_id.into();
+
+    let claim_duration = webhook_client.timeout() + CLAIM_MARGIN;
+    sqlx::query!(
+        r#"UPDATE `webhook_deliveries`
+              SET `n_attempts` = `n_attempts` + 1, `last_attempted_at` = CURRENT_TIMESTAMP(),
+                  `next_attempt_at` = CURRENT_TIMESTAMP() + INTERVAL ? SECOND
+            WHERE `delivery_id` = ?"#,
+        claim_duration.as_secs(),
+        delivery_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(Some(ClaimedDelivery {
+        delivery_id,
+        event_id: row.event_id.into(),
+        endpoint_url: row.endpoint_url,
+        n_attempts: row.n_attempts + 1,
+    }))
+}
+
+/// Deletes settled deliveries, then dispatched events without deliveries, once they are older
+/// than the retention period. Returns `false` if there was nothing to delete.
+#[tracing::instrument(skip_all)]
+async fn delete_expired_webhooks(
+    db_pool: &MySqlPool,
+    webhook_client: &WebhookClient,
+) -> Result<bool, anyhow::Error> {
+    let retention = webhook_client.retention().as_secs();
+    let deleted_deliveries = sqlx::query!(
+        r#"DELETE FROM `webhook_deliveries`
+            WHERE `status` <> 'pending'
+              AND `created_at` < CURRENT_TIMESTAMP() - INTERVAL ? SECOND
+            LIMIT ?"#,
+        retention,
+        CLEANUP_BATCH_SIZE,
+    )
+    .execute(db_pool)
+    .await?
+    .rows_affected();
+    let deleted_events = sqlx::query!(
+        r#"DELETE FROM `webhook_events`
+            WHERE `dispatched_at` < CURRENT_TIMESTAMP() - INTERVAL ? SECOND
+              AND NOT EXISTS (
+                  SELECT 1 FROM `webhook_deliveries` AS `d`
+                   WHERE `d`.`event_id` = `webhook_events`.`event_id`
+              )
+            LIMIT ?"#,
+        retention,
+        CLEANUP_BATCH_SIZE,
+    )
+    .execute(db_pool)
+    .await?
+    .rows_affected();
+
+    Ok(deleted_deliveries + deleted_events > 0)
+}
+
+/// Gives up on the pending deliveries to endpoints that were removed from the configuration,
+/// since no delivery loop picks them up anymore.
+#[tracing::instrument(skip_all)]
+async fn fail_unconfigured_deliveries(
+    db_pool: &MySqlPool,
+    webhook_client: &WebhookClient,
+) -> Result<u64, anyhow::Error> {
+    let mut query = QueryBuilder::<MySql>::new(
+        "UPDATE `webhook_deliveries` SET `status` = 'failed', `last_error` = ",
+    );
+    query.push_bind(SendWebhookError::UnknownEndpoint.to_string());
+    query.push(" WHERE `status` = 'pending'");
+    if !webhook_client.endpoints().is_empty() {
+        query.push(" AND `endpoint_url` NOT IN (");
+        let mut urls = query.separated(", ");
+        for endpoint in webhook_client.endpoints() {
+            urls.push_bind(endpoint.url.as_str());
+        }
+        urls.push_unseparated(")");
+    }
+
+    Ok(query.build().execute(db_pool).await?.rows_affected())
+}
+
+/// Dispatches events to the delivery queue. Deliveries themselves are made by one
+/// [`webhook_delivery_loop`] per endpoint.
+#[tracing::instrument(skip(db_pool, webhook_client, settings, shutdown))]
+pub async fn webhook_dispatch_loop(
+    db_pool: MySqlPool,
+    webhook_client: Arc<WebhookClient>,
+    settings: WorkerSettings,
+    mut shutdown: ShutdownListener,
+) -> Result<u64, anyhow::Error> {
+    match fail_unconfigured_deliveries(&db_pool, &webhook_client).await {
+        Ok(0) => {}
+        Ok(failed_deliveries) => tracing::warn!(
+            failed_deliveries,
+            "Gave up on deliveries to endpoints that are no longer configured",
+        ),
+        Err(e) => tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to give up on deliveries to endpoints that are no longer configured",
+        ),
+    }
+
+    let mut completed_tasks = 0;
+
+    while !shutdown.is_requested() {
+        let sleep_duration = match try_dispatch_webhook_event(&db_pool, &webhook_client).await {
+            Ok(ExecutionOutcome::TaskCompleted) => {
+                completed_tasks += 1;
+                continue;
+            }
+            Ok(ExecutionOutcome::TaskPostponed(wait_time)) => wait_time,
+            Ok(ExecutionOutcome::EmptyQueue) => settings.poll_interval(),
+            _ => settings.error_backoff(),
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_duration) => {}
+            _ = shutdown.requested() => {}
+        }
+    }
+
+    tracing::info!(completed_tasks, "Webhook dispatcher has stopped");
+
+    Ok(completed_tasks)
+}
+
+/// Delivers webhooks to a single endpoint, so that an endpoint that is down or slow only delays
+/// its own deliveries.
+#[tracing::instrument(skip(db_pool, webhook_client, settings, shutdown))]
+pub async fn webhook_delivery_loop(
+    db_pool: MySqlPool,
+    webhook_client: Arc<WebhookClient>,
+    endpoint_url: String,
+    settings: WorkerSettings,
+    mut shutdown: ShutdownListener,
+) -> Result<u64, anyhow::Error> {
+    let mut completed_tasks = 0;
+
+    while !shutdown.is_requested() {
+        let sleep_duration =
+            match try_deliver_webhook(&db_pool, &webhook_client, Some(&endpoint_url)).await {
+                Ok(ExecutionOutcome::TaskCompleted) => {
+                    completed_tasks += 1;
+                    continue;
+                }
+                Ok(ExecutionOutcome::TaskPostponed(wait_time)) => wait_time,
+                Ok(ExecutionOutcome::EmptyQueue) => settings.poll_interval(),
+                _ => settings.error_backoff(),
+            };
+
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_duration) => {}
+            _ = shutdown.requested() => {}
+        }
+    }
+
+    tracing::info!(completed_tasks, "Webhook delivery worker has stopped");
+
+    Ok(completed_tasks)
+}
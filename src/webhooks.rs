@@ -0,0 +1,318 @@
+use crate::{
+    configuration::{WebhookEndpointSettings, WebhookSettings},
+    utils::exponential_backoff,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{MySql, MySqlPool, QueryBuilder, Transaction};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Retries back off exponentially, but never wait longer than this between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "subscriber.created")]
+    SubscriberCreated,
+    #[serde(rename = "subscriber.confirmed")]
+    SubscriberConfirmed,
+    #[serde(rename = "subscriber.unsubscribed")]
+    SubscriberUnsubscribed,
+    /// Every delivery of a published issue has been attempted
+    #[serde(rename = "issue.sent")]
+    IssueSent,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::SubscriberCreated,
+        WebhookEvent::SubscriberConfirmed,
+        WebhookEvent::SubscriberUnsubscribed,
+        WebhookEvent::IssueSent,
+    ];
+
+    pub fn parse(event: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == event)
+            .ok_or_else(|| format!("{} is not a known webhook event", event))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SubscriberCreated => "subscriber.created",
+            Self::SubscriberConfirmed => "subscriber.confirmed",
+            Self::SubscriberUnsubscribed => "subscriber.unsubscribed",
+            Self::IssueSent => "issue.sent",
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Records an event in the transaction making the change it describes, so that it is only sent
+/// out if the change is committed. The delivery worker fans it out to the configured endpoints.
+#[tracing::instrument(skip(transaction, data))]
+pub async fn record_event(
+    transaction: &mut Transaction<'_, MySql>,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    record_events(transaction, event, vec![data]).await
+}
+
+/// Records one event per subscriber, with their current details as data.
+#[tracing::instrument(skip(transaction))]
+pub async fn record_subscriber_events(
+    transaction: &mut Transaction<'_, MySql>,
+    event: WebhookEvent,
+    subscriber_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    if subscriber_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT `id`, `email`, `name`, `status`, `subscribed_at` FROM `subscriptions` WHERE `id` IN (",
+    );
+    let mut ids = query.separated(", ");
+    for subscriber_id in subscriber_ids {
+        ids.push_bind(subscriber_id);
+    }
+    ids.push_unseparated(")");
+    let subscribers = query
+        .build_query_as::<(String, String, String, String, DateTime<Utc>)>()
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let data = subscribers
+        .into_iter()
+        .map(|(id, email, name, status, subscribed_at)| {
+            serde_json::json!({
+                "id": id,
+                "email": email,
+                "name": name,
+                "status": status,
+                "subscribed_at": subscribed_at,
+            })
+        })
+        .collect();
+
+    record_events(transaction, event, data).await
+}
+
+async fn record_events(
+    transaction: &mut Transaction<'_, MySql>,
+    event: WebhookEvent,
+    data: Vec<serde_json::Value>,
+) -> Result<(), sqlx::Error> {
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    let created_at = Utc::now();
+    let mut query = QueryBuilder::<MySql>::new(
+        "INSERT INTO `webhook_events` (`event_id`, `event`, `payload`, `created_at`) ",
+    );
+    query.push_values(data, |mut values, data| {
+        let event_id = Uuid::new_v4();
+        let payload = serde_json::json!({
+            "id": event_id,
+            "type": event.as_str(),
+            "created_at": created_at,
+            "data": data,
+        });
+        values
+            .push_bind(event_id)
+            .push_bind(event.as_str())
+            .push_bind(payload.to_string())
+            .push_bind(created_at);
+    });
+    query.build().execute(transaction).await?;
+
+    Ok(())
+}
+
+pub struct WebhookDelivery {
+    pub delivery_id: Uuid,
+    pub event: String,
+    pub endpoint_url: String,
+    /// `pending` until delivered, or `failed` once every attempt has been used
+    pub status: String,
+    pub n_attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempted_at: Option<DateTime<Utc>>,
+}
+
+/// Lists the most recent deliveries first.
+#[tracing::instrument(skip(db_pool))]
+pub async fn list_webhook_deliveries(
+    status: Option<&str>,
+    limit: u32,
+    db_pool: &MySqlPool,
+) -> Result<Vec<WebhookDelivery>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT `d`.`delivery_id`, `e`.`event`, `d`.`endpoint_url`, `d`.`status`,
+                  `d`.`n_attempts`, `d`.`last_error`, `d`.`created_at`, `d`.`last_attempted_at`
+             FROM `webhook_deliveries` AS `d`
+             JOIN `webhook_events` AS `e` ON `e`.`event_id` = `d`.`event_id`
+            WHERE (? IS NULL OR `d`.`status` = ?)
+            ORDER BY `d`.`created_at` DESC, `d`.`delivery_id`
+            LIMIT ?"#,
+        status,
+        status,
+        limit,
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to perform a query to list webhook deliveries.")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(WebhookDelivery {
+                delivery_id: Uuid::parse_str(&row.delivery_id)
+                    .context("Failed to parse webhook delivery UUID loaded from the database.")?,
+                event: row.event,
+                endpoint_url: row.endpoint_url,
+                status: row.status,
+                n_attempts: row.n_attempts,
+                last_error: row.last_error,
+                created_at: row.created_at,
+                last_attempted_at: row.last_attempted_at,
+            })
+        })
+        .collect()
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendWebhookError {
+    #[error("The endpoint is no longer configured")]
+    UnknownEndpoint,
+    #[error(transparent)]
+    RequestError(#[from] reqwest::Error),
+}
+
+pub struct WebhookClient {
+    http_client: Client,
+    endpoints: Vec<WebhookEndpointSettings>,
+    timeout: Duration,
+    max_attempts: u32,
+    retry_delay: Duration,
+    retention: Duration,
+}
+
+impl WebhookClient {
+    pub fn new(settings: WebhookSettings) -> Self {
+        let http_client = Client::builder()
+            .timeout(settings.timeout())
+            .build()
+            .unwrap();
+        Self {
+            http_client,
+            timeout: settings.timeout(),
+            retry_delay: settings.retry_delay(),
+            retention: settings.retention(),
+            max_attempts: settings.max_attempts.max(1),
+            endpoints: settings.endpoints,
+        }
+    }
+
+    pub fn endpoints(&self) -> &[WebhookEndpointSettings] {
+        &self.endpoints
+    }
+
+    /// How long a single request may take.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// How long settled deliveries and their events are kept.
+    pub fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    /// How long to wait before the next attempt, doubling after each failed one.
+    pub fn retry_delay(&self, n_attempts: u32) -> Duration {
+        exponential_backoff(self.retry_delay, n_attempts, MAX_RETRY_DELAY)
+    }
+
+    /// Posts the payload to the endpoint, signed with its secret. Any status other than a success
+    /// is an error.
+    pub async fn send(
+        &self,
+        endpoint_url: &str,
+        event_id: Uuid,
+        event: &str,
+        payload: String,
+    ) -> Result<(), SendWebhookError> {
+        let endpoint = self
+            .endpoints
+            .iter()
+            .find(|endpoint| endpoint.url == endpoint_url)
+            .ok_or(SendWebhookError::UnknownEndpoint)?;
+        let timestamp = Utc::now().timestamp();
+
+        self.http_client
+            .post(endpoint_url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Id", event_id.to_string())
+            .header("X-Webhook-Event", event)
+            .header("X-Webhook-Timestamp", timestamp.to_string())
+            .header(
+                "X-Webhook-Signature",
+                signature(&endpoint.secret, timestamp, &payload),
+            )
+            .body(payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Signs `{timestamp}.{payload}` so that receivers can check both the origin and the freshness of
+/// a delivery.
+pub fn signature(secret: &Secret<String>, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(payload.as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{signature, WebhookEvent};
+    use secrecy::Secret;
+
+    #[test]
+    fn events_round_trip_through_their_name() {
+        for event in WebhookEvent::ALL {
+            assert_eq!(Ok(event), WebhookEvent::parse(event.as_str()));
+        }
+    }
+
+    #[test]
+    fn signature_covers_the_timestamp() {
+        let secret = Secret::new("secret".to_owned());
+
+        assert_ne!(signature(&secret, 1, "{}"), signature(&secret, 2, "{}"));
+        assert!(signature(&secret, 1, "{}").starts_with("sha256="));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use chrono::Utc;
+use reqwest::StatusCode;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+async fn subscriber_status(test_app: &TestApp, subscriber_id: Uuid) -> Option<String> {
+    sqlx::query!(
+        "SELECT `status` FROM `subscriptions` WHERE `id` = ?",
+        subscriber_id
+    )
+    .fetch_optional(&test_app.db_pool)
+    .await
+    .unwrap()
+    .map(|row| row.status)
+}
+
+#[tokio::test]
+async fn user_must_be_logged_in_to_browse_subscribers() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.get_admin_subscribers("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn non_owners_are_forbidden_from_managing_subscribers() {
+    let test_app = spawn_app().await;
+    let subscriber_id = test_app
+        .add_subscriber("alice@example.com", "Alice", "confirmed", Utc::now())
+        .await;
+
+    for role in ["editor", "viewer"] {
+        let test_user = test_app.add_test_user(role).await;
+        test_user.login(&test_app).await;
+
+        let response = test_app.get_admin_subscribers("").await;
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+        let response = test_app
+            .post_subscriber_action(subscriber_id, "delete")
+            .await;
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+        test_app.post_logout().await;
+    }
+    assert_eq!(
+        Some("confirmed".to_owned()),
+        subscriber_status(&test_app, subscriber_id).await
+    );
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_by_email_or_name_and_filtered_by_status() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    test_app
+        .add_subscriber("alice@example.com", "Alice", "confirmed", Utc::now())
+        .await;
+    test_app
+        .add_subscriber("bob@example.com", "Bob", "pending_confirmation", Utc::now())
+        .await;
+    test_app
+        .add_subscriber(
+            "carol@example.com",
+            "Alice Carol",
+            "unsubscribed",
+            Utc::now(),
+        )
+        .await;
+
+    let html_page = test_app.get_admin_subscribers_html("q=alice").await;
+    assert!(html_page.contains("alice@example.com"));
+    assert!(html_page.contains("carol@example.com"));
+    assert!(!html_page.contains("bob@example.com"));
+
+    let html_page = test_app
+        .get_admin_subscribers_html("q=alice&status=confirmed")
+        .await;
+    assert!(html_page.contains("alice@example.com"));
+    assert!(!html_page.contains("carol@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_stored_before_the_current_validation_rules_are_still_listed() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    test_app
+        .add_subscriber("legacy-address", "", "confirmed", Utc::now())
+        .await;
+
+    let response = test_app.get_admin_subscribers("").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(response.text().await.unwrap().contains("legacy-address"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_sorted() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    test_app
+        .add_subscriber("bob@example.com", "Bob", "confirmed", Utc::now())
+        .await;
+    test_app
+        .add_subscriber("alice@example.com", "Alice", "confirmed", Utc::now())
+        .await;
+
+    let html_page = test_app.get_admin_subscribers_html("sort=email_asc").await;
+    assert!(html_page.find("alice@example.com") < html_page.find("bob@example.com"));
+
+    let html_page = test_app.get_admin_subscribers_html("sort=email_desc").await;
+    assert!(html_page.find("bob@example.com") < html_page.find("alice@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    for i in 0..30 {
+        let email = format!("subscriber{:02}@example.com", i);
+        test_app
+            .add_subscriber(&email, "Subscriber", "confirmed", Utc::now())
+            .await;
+    }
+
+    let first_page = test_app.get_admin_subscribers_html("sort=email_asc").await;
+    let second_page = test_app
+        .get_admin_subscribers_html("sort=email_asc&page=2")
+        .await;
+
+    assert!(first_page.contains("subscriber24@example.com"));
+    assert!(!first_page.contains("subscriber25@example.com"));
+    assert!(first_page.contains("Next &gt;"));
+    assert!(second_page.contains("subscriber25@example.com"));
+    assert!(second_page.contains("subscriber29@example.com"));
+    assert!(!second_page.contains("Next &gt;"));
+}
+
+#[tokio::test]
+async fn owner_can_confirm_unsubscribe_and_delete_subscribers() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let subscriber_id = test_app
+        .add_subscriber(
+            "alice@example.com",
+            "Alice",
+            "pending_confirmation",
+            Utc::now(),
+        )
+        .await;
+
+    let response = test_app
+        .post_subscriber_action(subscriber_id, "confirm")
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(
+        Some("confirmed".to_owned()),
+        subscriber_status(&test_app, subscriber_id).await
+    );
+    let html_page = test_app.get_admin_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>The subscriber has been confirmed</i></p>"));
+
+    test_app
+        .post_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+    assert_eq!(
+        Some("unsubscribed".to_owned()),
+        subscriber_status(&test_app, subscriber_id).await
+    );
+
+    test_app
+        .post_subscriber_action(subscriber_id, "delete")
+        .await;
+    assert_eq!(None, subscriber_status(&test_app, subscriber_id).await);
+    let html_page = test_app.get_admin_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>The subscriber has been deleted</i></p>"));
+}
+
+#[tokio::test]
+async fn confirmation_email_can_be_resent_to_pending_subscribers() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let pending_id = test_app
+        .add_subscriber(
+            "alice@example.com",
+            "Alice",
+            "pending_confirmation",
+            Utc::now(),
+        )
+        .await;
+    let confirmed_id = test_app
+        .add_subscriber("bob@example.com", "Bob", "confirmed", Utc::now())
+        .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_subscriber_action(pending_id, "resend-confirmation")
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = test_app.get_admin_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>The confirmation email will be sent again shortly</i></p>"));
+    test_app.dispatch_all_pending_confirmation_emails().await;
+
+    test_app
+        .post_subscriber_action(confirmed_id, "resend-confirmation")
+        .await;
+    let html_page = test_app.get_admin_subscribers_html("").await;
+    assert!(html_page
+        .contains("<p><i>Only pending subscribers can be sent a confirmation email</i></p>"));
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        Some("confirmed".to_owned()),
+        subscriber_status(&test_app, pending_id).await
+    );
+}
@@ -0,0 +1,322 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestUser};
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+#[tokio::test]
+async fn user_must_be_logged_in_to_manage_users() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.get_manage_users().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn non_owners_are_forbidden_from_managing_users() {
+    let test_app = spawn_app().await;
+
+    for role in ["editor", "viewer"] {
+        let test_user = test_app.add_test_user(role).await;
+        test_user.login(&test_app).await;
+
+        let response = test_app.get_manage_users().await;
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+        let response = test_app
+            .post_invite_user(&serde_json::json!({
+                "username": "intruder",
+                "email": "intruder@example.com",
+                "role": "owner",
+            }))
+            .await;
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+        test_app.post_logout().await;
+    }
+}
+
+#[tokio::test]
+async fn viewers_are_forbidden_from_publishing_newsletters() {
+    let test_app = spawn_app().await;
+    let viewer = test_app.add_test_user("viewer").await;
+    viewer.login(&test_app).await;
+
+    let response = test_app.get_publish_newsletter().await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let response = test_app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+}
+
+#[tokio::test]
+async fn editors_can_access_the_newsletter_form() {
+    let test_app = spawn_app().await;
+    let editor = test_app.add_test_user("editor").await;
+    editor.login(&test_app).await;
+
+    let response = test_app.get_publish_newsletter().await;
+
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn invited_user_can_choose_a_password_and_log_in() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let new_user = TestUser::with_role("editor");
+    let response = test_app
+        .post_invite_user(&serde_json::json!({
+            "username": new_user.username,
+            "email": "colleague@example.com",
+            "role": new_user.role,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_content = test_app.get_manage_users_html().await;
+    assert!(
+        html_content.contains("<p><i>An invitation has been sent to colleague@example.com</i></p>")
+    );
+    test_app.post_logout().await;
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let setup_link = test_app.get_confirmation_links(email_request).html;
+    assert_eq!(setup_link.path(), "/invitations/accept");
+
+    let response = reqwest::get(setup_link.clone()).await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let token = setup_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned();
+    let response = test_app
+        .post_accept_invitation(&serde_json::json!({
+            "token": token,
+            "password": new_user.password,
+            "password_check": new_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    new_user.login(&test_app).await;
+}
+
+#[tokio::test]
+async fn invitation_can_only_be_accepted_once() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_invite_user(&serde_json::json!({
+            "username": "colleague",
+            "email": "colleague@example.com",
+            "role": "viewer",
+        }))
+        .await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let setup_link = test_app.get_confirmation_links(email_request).html;
+    let token = setup_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned();
+
+    let form_data = serde_json::json!({
+        "token": token,
+        "password": "a-long-enough-password",
+        "password_check": "a-long-enough-password",
+    });
+    let response = test_app.post_accept_invitation(&form_data).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = test_app.post_accept_invitation(&form_data).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = reqwest::get(setup_link).await.unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn invitation_with_a_tampered_token_is_rejected() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_accept_invitation(&serde_json::json!({
+            "token": format!("{}.4102444800.deadbeef", test_app.test_user.user_id),
+            "password": "a-long-enough-password",
+            "password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn invitation_is_not_stored_if_the_email_cannot_be_sent() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_invite_user(&serde_json::json!({
+            "username": "colleague",
+            "email": "colleague@example.com",
+            "role": "viewer",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_content = test_app.get_manage_users_html().await;
+    assert!(html_content
+        .contains("<p><i>Failed to send the invitation email, please try again later</i></p>"));
+    assert!(!html_content.contains("<td>colleague</td>"));
+}
+
+#[tokio::test]
+async fn inviting_a_taken_username_is_rejected() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_invite_user(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "email": "colleague@example.com",
+            "role": "viewer",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_content = test_app.get_manage_users_html().await;
+    assert!(html_content.contains(&format!(
+        "<p><i>The username `{}` is already taken</i></p>",
+        test_app.test_user.username
+    )));
+}
+
+#[tokio::test]
+async fn invitation_errors_are_escaped_in_the_page() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    test_app
+        .post_invite_user(&serde_json::json!({
+            "username": "colleague",
+            "email": "<script>alert(1)</script>",
+            "role": "viewer",
+        }))
+        .await;
+
+    let html_content = test_app.get_manage_users_html().await;
+    assert!(html_content.contains("&lt;script&gt;alert(1)&lt;/script&gt; email is invalid"));
+    assert!(!html_content.contains("<script>"));
+}
+
+#[tokio::test]
+async fn owner_can_change_the_role_of_a_user() {
+    let test_app = spawn_app().await;
+    let viewer = test_app.add_test_user("viewer").await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .post_change_user_role(viewer.user_id, &serde_json::json!({ "role": "editor" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    test_app.post_logout().await;
+    viewer.login(&test_app).await;
+    let response = test_app.get_publish_newsletter().await;
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn owner_cannot_demote_themselves() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .post_change_user_role(
+            test_app.test_user.user_id,
+            &serde_json::json!({ "role": "viewer" }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let response = test_app.get_manage_users().await;
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn deactivated_user_cannot_log_in() {
+    let test_app = spawn_app().await;
+    let editor = test_app.add_test_user("editor").await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app.post_deactivate_user(editor.user_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    test_app.post_logout().await;
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": editor.username,
+            "password": editor.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn deactivated_user_loses_access_to_an_existing_session() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    sqlx::query!(
+        "UPDATE `users` SET `status` = 'deactivated' WHERE `id` = ?",
+        test_app.test_user.user_id
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,124 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn post_newsletter_with_token(test_app: &TestApp, token: &str) -> reqwest::Response {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .post(format!("{}/admin/newsletter", test_app.address))
+        .bearer_auth(token)
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        }))
+        .send()
+        .await
+        .expect("Failed to send a request to the app")
+}
+
+#[tokio::test]
+async fn api_token_can_publish_newsletters_without_a_session() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["newsletters:publish"]).await;
+
+    let response = post_newsletter_with_token(&test_app, &token).await;
+
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let issues: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM `newsletter_issues`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(1, issues);
+}
+
+#[tokio::test]
+async fn created_tokens_are_listed_without_their_value() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["newsletters:publish"]).await;
+
+    let html_page = test_app.get_api_tokens_html().await;
+
+    assert!(html_page.contains("test token"));
+    assert!(!html_page.contains(&token));
+}
+
+#[tokio::test]
+async fn invalid_api_token_is_rejected() {
+    let test_app = spawn_app().await;
+
+    let response = post_newsletter_with_token(&test_app, "z2p_not-a-real-token").await;
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn revoked_api_token_is_rejected() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["newsletters:publish"]).await;
+    let token_id: String = sqlx::query_scalar("SELECT `id` FROM `api_tokens`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let response = test_app
+        .api_client
+        .post(format!(
+            "{}/admin/tokens/{}/revoke",
+            test_app.address, token_id
+        ))
+        .form(&serde_json::json!({ "csrf_token": test_app.get_csrf_token().await }))
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+    assert_is_redirect_to(&response, "/admin/tokens");
+
+    let response = post_newsletter_with_token(&test_app, &token).await;
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn api_token_is_limited_to_its_scopes() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["users:manage"]).await;
+
+    let response = post_newsletter_with_token(&test_app, &token).await;
+
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn api_token_cannot_access_session_only_pages() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["newsletters:publish"]).await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/admin/tokens", test_app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn tokens_cannot_be_granted_permissions_the_user_does_not_have() {
+    let test_app = spawn_app().await;
+    let editor = test_app.add_test_user("editor").await;
+    editor.login(&test_app).await;
+
+    let response = test_app
+        .post_create_api_token("test token", &["users:manage"])
+        .await;
+    assert_is_redirect_to(&response, "/admin/tokens");
+
+    let html_page = test_app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The scope `users:manage` cannot be granted</i></p>"));
+}
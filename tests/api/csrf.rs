@@ -0,0 +1,79 @@
+use crate::helpers::{build_api_client, get_csrf_token, spawn_app};
+
+#[tokio::test]
+async fn login_without_a_csrf_token_is_rejected() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .api_client
+        .post(format!("{}/login", test_app.address))
+        .form(&serde_json::json!({
+            "username": test_app.test_user.username,
+            "password": test_app.test_user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn login_with_an_invalid_csrf_token_is_rejected() {
+    let test_app = spawn_app().await;
+    test_app.get_csrf_token().await;
+
+    let response = test_app
+        .api_client
+        .post(format!("{}/login", test_app.address))
+        .form(&serde_json::json!({
+            "username": test_app.test_user.username,
+            "password": test_app.test_user.password,
+            "csrf_token": "not-the-right-token",
+        }))
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn csrf_token_from_another_session_is_rejected() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let other_token = get_csrf_token(&build_api_client(), &test_app.address).await;
+
+    let response = test_app
+        .api_client
+        .post(format!("{}/admin/logout", test_app.address))
+        .form(&serde_json::json!({ "csrf_token": other_token }))
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+    assert_eq!(403, response.status().as_u16());
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn admin_forms_carry_the_session_csrf_token() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let csrf_token = test_app.get_csrf_token().await;
+    let csrf_field = format!(r#"name="csrf_token" value="{}""#, csrf_token);
+
+    assert!(test_app
+        .get_admin_dashboard_html()
+        .await
+        .contains(&csrf_field));
+    assert!(test_app
+        .get_change_password_html()
+        .await
+        .contains(&csrf_field));
+    assert!(test_app
+        .get_publish_newsletter_html()
+        .await
+        .contains(&csrf_field));
+}
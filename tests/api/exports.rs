@@ -0,0 +1,238 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use chrono::{TimeZone, Utc};
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+async fn add_published_issue(test_app: &TestApp) -> Uuid {
+    let issue_id = Uuid::new_v4();
+    sqlx::query(
+        r#"INSERT INTO `newsletter_issues`
+               (`newsletter_issue_id`, `title`, `text_content`, `html_content`, `published_at`)
+           VALUES (?, 'Weekly', 'Text', '<p>Html</p>', CURRENT_TIMESTAMP())"#,
+    )
+    .bind(issue_id)
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    issue_id
+}
+
+async fn add_outcome(test_app: &TestApp, issue_id: Uuid, email: &str, outcome: &str) {
+    sqlx::query(
+        r#"INSERT INTO `issue_delivery_outcomes`
+               (`newsletter_issue_id`, `subscriber_email`, `outcome`, `error_message`)
+           VALUES (?, ?, ?, IF(? = 'failed', 'Mailbox full', NULL))"#,
+    )
+    .bind(issue_id)
+    .bind(email)
+    .bind(outcome)
+    .bind(outcome)
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn add_pending_delivery(test_app: &TestApp, issue_id: Uuid, email: &str) {
+    sqlx::query(
+        r#"INSERT INTO `issue_delivery_queue` (`newsletter_issue_id`, `subscriber_email`)
+           VALUES (?, ?)"#,
+    )
+    .bind(issue_id)
+    .bind(email)
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn user_must_be_logged_in_to_export() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.get_export("/subscribers").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn non_owners_are_forbidden_from_exporting() {
+    let test_app = spawn_app().await;
+    let issue_id = add_published_issue(&test_app).await;
+
+    for role in ["editor", "viewer"] {
+        let test_user = test_app.add_test_user(role).await;
+        test_user.login(&test_app).await;
+
+        for path in [
+            "".to_owned(),
+            "/subscribers".into(),
+            format!("/issues/{issue_id}"),
+        ] {
+            let response = test_app.get_export(&path).await;
+            assert_eq!(StatusCode::FORBIDDEN, response.status());
+        }
+
+        test_app.post_logout().await;
+    }
+}
+
+#[tokio::test]
+async fn export_page_links_to_published_issues() {
+    let test_app = spawn_app().await;
+    let issue_id = add_published_issue(&test_app).await;
+    test_app.test_user.login(&test_app).await;
+
+    let html_page = test_app.get_export("").await.text().await.unwrap();
+
+    assert!(html_page.contains(&format!("/admin/exports/issues/{issue_id}?format=csv")));
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv_most_recent_first() {
+    let test_app = spawn_app().await;
+    test_app
+        .add_subscriber(
+            "old@example.com",
+            "Doe, Jane",
+            "confirmed",
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+        )
+        .await;
+    test_app
+        .add_subscriber(
+            "new@example.com",
+            "Doe, Jane",
+            "unsubscribed",
+            Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap(),
+        )
+        .await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app.get_export("/subscribers").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        "attachment; filename=\"subscribers.csv\"",
+        response.headers()["Content-Disposition"]
+    );
+    let body = response.text().await.unwrap();
+    let lines: Vec<_> = body.lines().collect();
+    assert_eq!("id,email,name,status,subscribed_at", lines[0]);
+    assert_eq!(3, lines.len());
+    assert!(lines[1].contains(",new@example.com,\"Doe, Jane\",unsubscribed,2026-02-01"));
+    assert!(lines[2].contains(",old@example.com,\"Doe, Jane\",confirmed,2026-01-01"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_as_json_by_status() {
+    let test_app = spawn_app().await;
+    test_app
+        .add_subscriber(
+            "old@example.com",
+            "Doe, Jane",
+            "confirmed",
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+        )
+        .await;
+    test_app
+        .add_subscriber(
+            "new@example.com",
+            "Doe, Jane",
+            "unsubscribed",
+            Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap(),
+        )
+        .await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .get_export("/subscribers?format=json&status=confirmed")
+        .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = response.json().await.unwrap();
+    let subscribers = body.as_array().unwrap();
+    assert_eq!(1, subscribers.len());
+    assert_eq!("old@example.com", subscribers[0]["email"]);
+    assert_eq!("confirmed", subscribers[0]["status"]);
+}
+
+#[tokio::test]
+async fn empty_exports_are_still_well_formed() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    let csv = test_app
+        .get_export("/subscribers")
+        .await
+        .text()
+        .await
+        .unwrap();
+    let json: serde_json::Value = test_app
+        .get_export("/subscribers?format=json")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!("id,email,name,status,subscribed_at\n", csv);
+    assert_eq!(serde_json::json!([]), json);
+}
+
+#[tokio::test]
+async fn unknown_formats_and_statuses_are_rejected() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    for query in ["?format=xml", "?status=bounced"] {
+        let response = test_app.get_export(&format!("/subscribers{query}")).await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+}
+
+#[tokio::test]
+async fn delivery_outcomes_include_pending_deliveries() {
+    let test_app = spawn_app().await;
+    let issue_id = add_published_issue(&test_app).await;
+    add_outcome(&test_app, issue_id, "b@example.com", "failed").await;
+    add_outcome(&test_app, issue_id, "c@example.com", "delivered").await;
+    add_pending_delivery(&test_app, issue_id, "a@example.com").await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .get_export(&format!("/issues/{issue_id}?format=json"))
+        .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let outcomes: Vec<serde_json::Value> = response.json().await.unwrap();
+    let summary: Vec<_> = outcomes
+        .iter()
+        .map(|outcome| {
+            (
+                outcome["email"].as_str().unwrap(),
+                outcome["outcome"].as_str().unwrap(),
+                outcome["error_message"].as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            ("a@example.com", "pending", None),
+            ("b@example.com", "failed", Some("Mailbox full")),
+            ("c@example.com", "delivered", None),
+        ],
+        summary
+    );
+    assert!(outcomes[0]["attempted_at"].is_null());
+}
+
+#[tokio::test]
+async fn exporting_a_missing_issue_returns_404() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .get_export(&format!("/issues/{}", Uuid::new_v4()))
+        .await;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
@@ -1,5 +1,6 @@
 use crate::helpers::spawn_app;
 use reqwest::{Client, StatusCode};
+use zero2prod::{configuration::get_configuration, startup::HealthCheckServer};
 
 #[tokio::test]
 async fn health_check_responds_with_204_and_no_content() {
@@ -17,3 +18,23 @@ async fn health_check_responds_with_204_and_no_content() {
     assert_eq!(StatusCode::NO_CONTENT, response.status());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn worker_health_check_responds_with_204_and_no_content() {
+    let configuration = get_configuration("test.yaml").expect("Failed to read test configuration");
+    let health_check = HealthCheckServer::build(&configuration.worker, 0)
+        .expect("Failed to build the worker health check server");
+    let address = format!("http://127.0.0.1:{}", health_check.port());
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(health_check.run_until_stopped());
+
+    let response = Client::new()
+        .get(format!("{}/health_check", address))
+        .send()
+        .await
+        .expect("Failed to send a request to the worker");
+
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    assert_eq!(Some(0), response.content_length());
+}
@@ -1,15 +1,21 @@
 use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+use chrono::{DateTime, Utc};
 use reqwest::{StatusCode, Url};
 use sqlx::{Executor, MySqlPool};
 use tokio::sync::OnceCell;
 use uuid::Uuid;
 use wiremock::MockServer;
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings},
+    configuration::{get_configuration, DatabaseSettings, Settings},
+    confirmation_emails::try_send_confirmation_email,
     email_client::EmailClient,
     issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    migrations::MIGRATOR,
+    rate_limiter::RateLimiter,
     startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
+    webhook_delivery_worker::try_execute_webhook_task,
+    webhooks::WebhookClient,
 };
 
 pub struct ConfirmationLinks {
@@ -19,15 +25,38 @@ pub struct ConfirmationLinks {
 
 pub struct TestApp {
     pub address: String,
+    /// Base of the links sent in emails, which do not include the port of the application
+    pub base_url: String,
     pub db_pool: MySqlPool,
     pub email_server: MockServer,
     pub port: u16,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub rate_limiter: RateLimiter,
+    pub webhook_client: WebhookClient,
 }
 
 impl TestApp {
+    async fn post_form<Body>(&self, url: String, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut form_data = serde_json::to_value(form_data).unwrap();
+        form_data["csrf_token"] = self.get_csrf_token().await.into();
+
+        self.api_client
+            .post(url)
+            .form(&form_data)
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+    }
+
+    pub async fn get_csrf_token(&self) -> String {
+        get_csrf_token(&self.api_client, &self.address).await
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
             .post(&format!("{}/subscriptions", &self.address))
@@ -38,6 +67,15 @@ impl TestApp {
             .expect("Failed to send a request")
     }
 
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/subscriptions", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to send a request")
+    }
+
     pub async fn get_login_html(&self) -> String {
         self.api_client
             .get(format!("{}/login", self.address))
@@ -53,12 +91,8 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.api_client
-            .post(format!("{}/login", self.address))
-            .form(body)
-            .send()
+        self.post_form(format!("{}/login", self.address), body)
             .await
-            .expect("Failed to send a request to the app")
     }
 
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
@@ -110,56 +144,404 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
+        self.post_form(format!("{}/admin/password", self.address), form_data)
+            .await
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.post_form(
+            format!("{}/admin/logout", self.address),
+            &serde_json::json!({}),
+        )
+        .await
+    }
+
+    pub async fn post_publish_newsletter<Body>(&self, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(format!("{}/admin/newsletter", self.address), form_data)
+            .await
+    }
+
+    pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .post(format!("{}/admin/password", self.address))
-            .form(form_data)
+            .get(format!("{}/admin/newsletter", self.address))
             .send()
             .await
             .expect("Failed to send a request to the app")
     }
 
-    pub async fn post_logout(&self) -> reqwest::Response {
+    pub async fn get_publish_newsletter_html(&self) -> String {
+        self.get_publish_newsletter().await.text().await.unwrap()
+    }
+
+    pub async fn get_manage_users(&self) -> reqwest::Response {
         self.api_client
-            .post(format!("{}/admin/logout", self.address))
+            .get(format!("{}/admin/users", self.address))
             .send()
             .await
             .expect("Failed to send a request to the app")
     }
 
-    pub async fn post_publish_newsletter<Body>(&self, form_data: &Body) -> reqwest::Response
+    pub async fn get_manage_users_html(&self) -> String {
+        self.get_manage_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_invite_user<Body>(&self, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(format!("{}/admin/users/invite", self.address), form_data)
+            .await
+    }
+
+    pub async fn post_change_user_role<Body>(
+        &self,
+        user_id: Uuid,
+        form_data: &Body,
+    ) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
+        self.post_form(
+            format!("{}/admin/users/{}/role", self.address, user_id),
+            form_data,
+        )
+        .await
+    }
+
+    pub async fn post_deactivate_user(&self, user_id: Uuid) -> reqwest::Response {
+        self.post_form(
+            format!("{}/admin/users/{}/deactivate", self.address, user_id),
+            &serde_json::json!({}),
+        )
+        .await
+    }
+
+    pub async fn get_admin_subscribers(&self, query: &str) -> reqwest::Response {
         self.api_client
-            .post(format!("{}/admin/newsletter", self.address))
-            .form(form_data)
+            .get(format!("{}/admin/subscribers?{}", self.address, query))
             .send()
             .await
             .expect("Failed to send a request to the app")
     }
 
-    pub async fn get_publish_newsletter(&self) -> reqwest::Response {
+    pub async fn get_admin_subscribers_html(&self, query: &str) -> String {
+        self.get_admin_subscribers(query)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.post_form(
+            format!(
+                "{}/admin/subscribers/{}/{}",
+                self.address, subscriber_id, action
+            ),
+            &serde_json::json!({}),
+        )
+        .await
+    }
+
+    pub async fn get_export(&self, path_and_query: &str) -> reqwest::Response {
         self.api_client
-            .get(format!("{}/admin/newsletter", self.address))
+            .get(format!("{}/admin/exports{}", self.address, path_and_query))
             .send()
             .await
             .expect("Failed to send a request to the app")
     }
 
-    pub async fn get_publish_newsletter_html(&self) -> String {
-        self.get_publish_newsletter().await.text().await.unwrap()
+    pub async fn post_subscriber_import(&self, csv: &str, confirmed: bool) -> reqwest::Response {
+        let file = reqwest::multipart::Part::text(csv.to_owned())
+            .file_name("subscribers.csv")
+            .mime_str("text/csv")
+            .unwrap();
+        let mut form = reqwest::multipart::Form::new()
+            .text("csrf_token", self.get_csrf_token().await)
+            .part("file", file);
+        if confirmed {
+            form = form.text("confirmed", "true");
+        }
+
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(format!("{}/invitations/accept", self.address), form_data)
+            .await
+    }
+
+    pub async fn post_forgot_password<Body>(&self, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(format!("{}/login/forgot", self.address), form_data)
+            .await
+    }
+
+    pub async fn post_reset_password<Body>(&self, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(format!("{}/login/reset", self.address), form_data)
+            .await
+    }
+
+    pub async fn get_two_factor_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/two-factor", self.address))
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_enable_two_factor<Body>(&self, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(format!("{}/admin/two-factor", self.address), form_data)
+            .await
+    }
+
+    pub async fn post_two_factor_login<Body>(&self, form_data: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_form(format!("{}/login/two-factor", self.address), form_data)
+            .await
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/sessions", self.address))
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.post_form(
+            format!("{}/admin/sessions/revoke-others", self.address),
+            &serde_json::json!({}),
+        )
+        .await
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/tokens", self.address))
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_api_token(&self, name: &str, scopes: &[&str]) -> reqwest::Response {
+        let mut form_data = vec![
+            ("name", name.to_owned()),
+            ("csrf_token", self.get_csrf_token().await),
+        ];
+        form_data.extend(scopes.iter().map(|scope| ("scope", scope.to_string())));
+
+        self.api_client
+            .post(format!("{}/admin/tokens", self.address))
+            .form(&form_data)
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+    }
+
+    /// Creates an API token for the logged in user and returns its plain text value.
+    pub async fn create_api_token(&self, scopes: &[&str]) -> String {
+        let html_page = self
+            .post_create_api_token("test token", scopes)
+            .await
+            .text()
+            .await
+            .unwrap();
+
+        let start = html_page
+            .find("<code>z2p_")
+            .expect("No API token was created")
+            + 6;
+        let end = start + html_page[start..].find("</code>").unwrap();
+
+        html_page[start..end].to_owned()
+    }
+
+    pub async fn get_api(&self, token: Option<&str>, path: &str) -> reqwest::Response {
+        let mut request = reqwest::Client::new().get(format!("{}{}", self.address, path));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        request
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+    }
+
+    pub async fn post_api(
+        &self,
+        token: &str,
+        path: &str,
+        idempotency_key: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .post(format!("{}{}", self.address, path))
+            .bearer_auth(token);
+        if let Some(idempotency_key) = idempotency_key {
+            request = request.header("Idempotency-Key", idempotency_key);
+        }
+
+        request
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+    }
+
+    pub async fn post_api_issue(
+        &self,
+        token: &str,
+        body: &serde_json::Value,
+        idempotency_key: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .post(format!("{}/api/v1/issues", self.address))
+            .bearer_auth(token)
+            .json(body);
+        if let Some(idempotency_key) = idempotency_key {
+            request = request.header("Idempotency-Key", idempotency_key);
+        }
+
+        request
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+    }
+
+    pub async fn add_test_user(&self, role: &str) -> TestUser {
+        let test_user = TestUser::with_role(role);
+        test_user.persist(&self.db_pool).await;
+
+        test_user
+    }
+
+    /// Stores a subscriber straight into the database, bypassing the validation of the
+    /// subscription form.
+    pub async fn add_subscriber(
+        &self,
+        email: &str,
+        name: &str,
+        status: &str,
+        subscribed_at: DateTime<Utc>,
+    ) -> Uuid {
+        let subscriber_id = Uuid::new_v4();
+        sqlx::query(
+            r#"INSERT INTO `subscriptions` (`id`, `email`, `name`, `subscribed_at`, `status`)
+               VALUES (?, ?, ?, ?, ?)"#,
+        )
+        .bind(subscriber_id)
+        .bind(email)
+        .bind(name)
+        .bind(subscribed_at)
+        .bind(status)
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to store the test subscriber");
+
+        subscriber_id
+    }
+
+    pub async fn get_webhook_deliveries_html(&self, query: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/webhooks?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to send a request to the app")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn dispatch_all_pending_confirmation_emails(&self) {
+        loop {
+            match try_send_confirmation_email(
+                &self.db_pool,
+                &self.email_client,
+                &self.rate_limiter,
+                &self.base_url,
+            )
+            .await
+            .unwrap()
+            {
+                ExecutionOutcome::EmptyQueue => break,
+                ExecutionOutcome::TaskPostponed(wait_time) => tokio::time::sleep(wait_time).await,
+                ExecutionOutcome::TaskCompleted => {}
+            }
+        }
+    }
+
+    pub async fn dispatch_all_pending_webhooks(&self) {
+        loop {
+            match try_execute_webhook_task(&self.db_pool, &self.webhook_client)
+                .await
+                .unwrap()
+            {
+                ExecutionOutcome::EmptyQueue => break,
+                ExecutionOutcome::TaskPostponed(wait_time) => tokio::time::sleep(wait_time).await,
+                ExecutionOutcome::TaskCompleted => {}
+            }
+        }
     }
 
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            match try_execute_task(&self.db_pool, &self.email_client, &self.rate_limiter)
+                .await
+                .unwrap()
             {
-                break;
+                ExecutionOutcome::EmptyQueue => break,
+                ExecutionOutcome::TaskPostponed(wait_time) => tokio::time::sleep(wait_time).await,
+                ExecutionOutcome::TaskCompleted => {}
+            }
+        }
+    }
+
+    /// Waits for emails that the application sends in the background.
+    pub async fn wait_for_emails(&self, n_emails: usize) -> Vec<wiremock::Request> {
+        for _ in 0..100 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= n_emails {
+                return requests;
             }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
         }
+
+        panic!("Timed out waiting for {} emails", n_emails)
     }
 }
 
@@ -167,14 +549,22 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
+    pub role: String,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::with_role("owner")
+    }
+
+    pub fn with_role(role: &str) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4().as_simple()),
+            role: role.into(),
         }
     }
 
@@ -189,10 +579,15 @@ impl TestUser {
         .unwrap()
         .to_string();
         sqlx::query!(
-            "INSERT INTO `users` (`id`, `username`, `password_hash`) VALUES (?, ?, ?)",
+            r#"
+            INSERT INTO `users` (`id`, `username`, `password_hash`, `email`, `role`)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
             self.user_id,
             self.username,
             password_hash,
+            self.email,
+            self.role,
         )
         .execute(pool)
         .await
@@ -250,7 +645,7 @@ async fn migrate_database(db_settings: &DatabaseSettings) -> MySqlPool {
     let db_pool = MySqlPool::connect_with(db_settings.with_db())
         .await
         .expect("Failed to connect to the database");
-    sqlx::migrate!("./migrations")
+    MIGRATOR
         .run(&db_pool)
         .await
         .expect("Failed to run migrations");
@@ -264,12 +659,42 @@ fn should_display_output() -> bool {
     std::env::args().any(|a| a == *"--nocapture")
 }
 
+/// Loads the login form to make sure the session has a CSRF token and returns it.
+pub async fn get_csrf_token(api_client: &reqwest::Client, address: &str) -> String {
+    let html_page = api_client
+        .get(format!("{}/login", address))
+        .send()
+        .await
+        .expect("Failed to send a request to the app")
+        .text()
+        .await
+        .unwrap();
+
+    let marker = r#"name="csrf_token" value=""#;
+    let start = html_page.find(marker).expect("The page has no CSRF token") + marker.len();
+    let end = start + html_page[start..].find('"').unwrap();
+
+    html_page[start..end].to_owned()
+}
+
+pub fn build_api_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap()
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(StatusCode::SEE_OTHER, response.status());
     assert_eq!(response.headers().get("Location").unwrap(), location);
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     let email_server = MockServer::start().await;
 
     let configuration = {
@@ -280,6 +705,7 @@ pub async fn spawn_app() -> TestApp {
             Uuid::new_v4().as_simple(),
         );
         conf.email.base_url = email_server.uri();
+        configure(&mut conf);
         conf
     };
     let db_configuration = configuration.database.clone();
@@ -319,19 +745,16 @@ pub async fn spawn_app() -> TestApp {
 
     test_user.persist(&db_pool).await;
 
-    let api_client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
-
     TestApp {
         address,
+        base_url: configuration.application.base_url.clone(),
         db_pool,
         email_server,
         port,
         test_user,
-        api_client,
+        api_client: build_api_client(),
         email_client: configuration.email.client(),
+        rate_limiter: configuration.worker.rate_limiter(),
+        webhook_client: configuration.webhooks.client(),
     }
 }
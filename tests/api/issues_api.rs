@@ -0,0 +1,253 @@
+use crate::helpers::{spawn_app, TestApp};
+use chrono::Utc;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+async fn spawn_app_with_token() -> (TestApp, String) {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["newsletters:publish"]).await;
+
+    (test_app, token)
+}
+
+fn issue_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+    })
+}
+
+async fn create_issue(test_app: &TestApp, token: &str) -> serde_json::Value {
+    let response = test_app.post_api_issue(token, &issue_body(), None).await;
+    assert_eq!(201, response.status().as_u16());
+
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn requests_without_a_token_are_rejected_with_a_json_error() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.get_api(None, "/api/v1/issues").await;
+
+    assert_eq!(401, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("unauthorized", body["error"]["code"]);
+}
+
+#[tokio::test]
+async fn creating_an_issue_stores_a_draft_without_sending_it() {
+    let (test_app, token) = spawn_app_with_token().await;
+    test_app
+        .add_subscriber(
+            &format!("{}@example.com", Uuid::new_v4()),
+            "Subscriber",
+            "confirmed",
+            Utc::now(),
+        )
+        .await;
+
+    let issue = create_issue(&test_app, &token).await;
+
+    assert_eq!("draft", issue["status"]);
+    assert_eq!("Newsletter title", issue["title"]);
+    assert!(issue["published_at"].is_null());
+    let queued: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM `issue_delivery_queue`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(0, queued);
+}
+
+#[tokio::test]
+async fn invalid_issues_are_rejected_with_a_json_error() {
+    let (test_app, token) = spawn_app_with_token().await;
+    let test_cases = vec![
+        (serde_json::json!({ "title": "Title" }), "missing content"),
+        (
+            serde_json::json!({ "title": "", "text_content": "text", "html_content": "html" }),
+            "empty title",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = test_app.post_api_issue(&token, &body, None).await;
+
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not reject an issue with {}",
+            description
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!("invalid_request", body["error"]["code"]);
+    }
+}
+
+#[tokio::test]
+async fn publishing_an_issue_enqueues_deliveries_once() {
+    let (test_app, token) = spawn_app_with_token().await;
+    test_app
+        .add_subscriber(
+            &format!("{}@example.com", Uuid::new_v4()),
+            "Subscriber",
+            "confirmed",
+            Utc::now(),
+        )
+        .await;
+    let issue = create_issue(&test_app, &token).await;
+    let publish_path = format!("/api/v1/issues/{}/publish", issue["id"].as_str().unwrap());
+
+    let response = test_app.post_api(&token, &publish_path, None).await;
+    assert_eq!(200, response.status().as_u16());
+    let published: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("published", published["status"]);
+
+    let response = test_app.post_api(&token, &publish_path, None).await;
+    assert_eq!(409, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("conflict", body["error"]["code"]);
+
+    let queued: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM `issue_delivery_queue`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(1, queued);
+}
+
+#[tokio::test]
+async fn created_and_published_issues_report_their_stored_timestamps() {
+    let (test_app, token) = spawn_app_with_token().await;
+    let created = create_issue(&test_app, &token).await;
+    let issue_path = format!("/api/v1/issues/{}", created["id"].as_str().unwrap());
+
+    let response = test_app
+        .post_api(&token, &format!("{}/publish", issue_path), None)
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let published: serde_json::Value = response.json().await.unwrap();
+
+    let stored: serde_json::Value = test_app
+        .get_api(Some(&token), &issue_path)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stored["created_at"], created["created_at"]);
+    assert_eq!(stored["created_at"], published["created_at"]);
+    assert_eq!(stored["published_at"], published["published_at"]);
+}
+
+#[tokio::test]
+async fn delivery_status_reports_pending_and_delivered_emails() {
+    let (test_app, token) = spawn_app_with_token().await;
+    test_app
+        .add_subscriber(
+            &format!("{}@example.com", Uuid::new_v4()),
+            "Subscriber",
+            "confirmed",
+            Utc::now(),
+        )
+        .await;
+    let issue = create_issue(&test_app, &token).await;
+    let issue_id = issue["id"].as_str().unwrap();
+    test_app
+        .post_api(
+            &token,
+            &format!("/api/v1/issues/{}/publish", issue_id),
+            None,
+        )
+        .await;
+    let delivery_path = format!("/api/v1/issues/{}/delivery", issue_id);
+
+    let status: serde_json::Value = test_app
+        .get_api(Some(&token), &delivery_path)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(1, status["pending"]);
+    assert_eq!(0, status["delivered"]);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    let status: serde_json::Value = test_app
+        .get_api(Some(&token), &delivery_path)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(0, status["pending"]);
+    assert_eq!(1, status["delivered"]);
+}
+
+#[tokio::test]
+async fn missing_issue_is_reported_as_not_found() {
+    let (test_app, token) = spawn_app_with_token().await;
+
+    let response = test_app
+        .get_api(Some(&token), &format!("/api/v1/issues/{}", Uuid::new_v4()))
+        .await;
+
+    assert_eq!(404, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("not_found", body["error"]["code"]);
+}
+
+#[tokio::test]
+async fn issues_are_listed_page_by_page() {
+    let (test_app, token) = spawn_app_with_token().await;
+    for _ in 0..3 {
+        create_issue(&test_app, &token).await;
+    }
+
+    let first_page: serde_json::Value = test_app
+        .get_api(Some(&token), "/api/v1/issues?per_page=2")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let second_page: serde_json::Value = test_app
+        .get_api(Some(&token), "/api/v1/issues?per_page=2&page=2")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(3, first_page["total"]);
+    assert_eq!(2, first_page["items"].as_array().unwrap().len());
+    assert_eq!(1, second_page["items"].as_array().unwrap().len());
+}
+
+#[tokio::test]
+async fn creating_an_issue_is_idempotent() {
+    let (test_app, token) = spawn_app_with_token().await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    let first = test_app
+        .post_api_issue(&token, &issue_body(), Some(&idempotency_key))
+        .await;
+    let second = test_app
+        .post_api_issue(&token, &issue_body(), Some(&idempotency_key))
+        .await;
+
+    assert_eq!(201, first.status().as_u16());
+    assert_eq!(201, second.status().as_u16());
+    assert_eq!(first.text().await.unwrap(), second.text().await.unwrap());
+    let issues: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM `newsletter_issues`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(1, issues);
+}
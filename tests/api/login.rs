@@ -1,11 +1,15 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 
 #[tokio::test]
 async fn an_error_message_is_set_on_failure() {
     let test_app = spawn_app().await;
 
+    // Failed attempts are tracked per username, so every run needs a fresh one
     let login_body = serde_json::json!({
-        "username": "random-user",
+        "username": Uuid::new_v4().to_string(),
         "password": "wrong-password",
     });
 
@@ -37,3 +41,131 @@ async fn redirect_to_admin_dashboard_after_successful_login() {
 
     assert!(html_page.contains(&format!("Welcome {}", test_app.test_user.username)));
 }
+
+#[tokio::test]
+async fn account_is_locked_out_after_too_many_failed_attempts() {
+    let test_app = spawn_app().await;
+
+    let wrong_login_body = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": "wrong-password",
+    });
+    for _ in 0..4 {
+        let response = test_app.post_login(&wrong_login_body).await;
+        assert_is_redirect_to(&response, "/login");
+
+        let html_page = test_app.get_login_html().await;
+        assert!(html_page.contains(r#"<p><i>Authentication failed</i></p>"#));
+    }
+
+    let response = test_app.post_login(&wrong_login_body).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts"));
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts"));
+
+    let audit_entry = sqlx::query!("SELECT `event`, `subject` FROM `audit_log`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch the audit log entry");
+    assert_eq!(audit_entry.event, "account_locked_out");
+    assert_eq!(audit_entry.subject, test_app.test_user.username);
+}
+
+#[tokio::test]
+async fn concurrent_attempts_are_delayed_progressively() {
+    let test_app = spawn_app_with(|configuration| {
+        configuration
+            .application
+            .login_throttle
+            .base_delay_milliseconds = 200;
+    })
+    .await;
+    let wrong_login_body = serde_json::json!({
+        "username": Uuid::new_v4().to_string(),
+        "password": "wrong-password",
+    });
+
+    let started_at = Instant::now();
+    futures_util::future::join_all((0..3).map(|_| test_app.post_login(&wrong_login_body))).await;
+
+    // The third attempt waits for the two before it, even though none of them has failed yet
+    assert!(started_at.elapsed() >= Duration::from_millis(400));
+}
+
+#[tokio::test]
+async fn successful_login_resets_failed_attempts() {
+    let test_app = spawn_app().await;
+
+    let wrong_login_body = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": "wrong-password",
+    });
+    for _ in 0..4 {
+        test_app.post_login(&wrong_login_body).await;
+    }
+
+    test_app.test_user.login(&test_app).await;
+    test_app.post_logout().await;
+
+    for _ in 0..4 {
+        test_app.post_login(&wrong_login_body).await;
+    }
+    test_app.test_user.login(&test_app).await;
+}
+
+#[tokio::test]
+async fn weak_password_hash_is_upgraded_after_login() {
+    let test_app = spawn_app().await;
+
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let weak_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(test_app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE `users` SET `password_hash` = ? WHERE `id` = ?",
+        weak_hash,
+        test_app.test_user.user_id
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    test_app.test_user.login(&test_app).await;
+
+    // The upgrade happens in the background, after the login response has been sent
+    let mut upgraded_hash = None;
+    for _ in 0..50 {
+        let row = sqlx::query!(
+            "SELECT `password_hash` FROM `users` WHERE `id` = ?",
+            test_app.test_user.user_id
+        )
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+        if row.password_hash.as_deref() != Some(weak_hash.as_str()) {
+            upgraded_hash = row.password_hash;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let upgraded_hash = upgraded_hash.expect("The password hash was not upgraded");
+    assert!(upgraded_hash.contains("m=15000,t=2,p=1"));
+
+    test_app.post_logout().await;
+    test_app.test_user.login(&test_app).await;
+}
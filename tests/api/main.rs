@@ -1,10 +1,23 @@
 // test modules
 mod admin_dashboard;
+mod admin_subscribers;
+mod admin_users;
+mod api_tokens;
 mod change_password;
+mod csrf;
+mod exports;
 mod health_check;
+mod issues_api;
 mod login;
 mod newsletters;
+mod openapi;
+mod password_reset;
+mod sessions;
+mod subscriber_import;
+mod subscribers_api;
 mod subscriptions;
 mod subscriptions_confirm;
+mod two_factor;
+mod webhooks;
 // support modules
 mod helpers;
@@ -244,6 +244,7 @@ async fn create_unconfirmed_subscriber(test_app: &TestApp) -> ConfirmationLinks
         .await
         .error_for_status()
         .unwrap();
+    test_app.dispatch_all_pending_confirmation_emails().await;
 
     let email_request = test_app
         .email_server
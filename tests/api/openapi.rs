@@ -0,0 +1,50 @@
+use crate::helpers::spawn_app;
+use std::collections::BTreeSet;
+use zero2prod::startup::app_routes;
+
+/// Lists the `(method, path)` pairs served by the application, as mounted by `startup::run`.
+fn registered_routes() -> BTreeSet<(String, String)> {
+    app_routes()
+        .iter()
+        .map(|route| (route.method.as_str().to_lowercase(), route.full_path()))
+        .collect()
+}
+
+#[tokio::test]
+async fn openapi_document_is_served() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.get_api(None, "/api/openapi.json").await;
+
+    assert_eq!(200, response.status().as_u16());
+    let spec: serde_json::Value = response.json().await.unwrap();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(spec["components"]["schemas"]["IssueResponse"].is_object());
+    assert!(spec["components"]["securitySchemes"]["api_token"].is_object());
+}
+
+#[tokio::test]
+async fn every_registered_route_is_documented() {
+    let test_app = spawn_app().await;
+    let routes = registered_routes();
+    assert!(routes.contains(&("get".into(), "/health_check".into())));
+    assert!(routes.contains(&("post".into(), "/api/v1/issues/{issue_id}/publish".into())));
+    assert!(routes.contains(&("post".into(), "/admin/sessions/{session_id}/revoke".into())));
+
+    let spec: serde_json::Value = test_app
+        .get_api(None, "/api/openapi.json")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    let undocumented: Vec<_> = routes
+        .iter()
+        .filter(|(method, path)| !spec["paths"][path][method].is_object())
+        .collect();
+    assert!(
+        undocumented.is_empty(),
+        "Routes missing from the OpenAPI document: {:?}",
+        undocumented
+    );
+}
@@ -0,0 +1,194 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use reqwest::{StatusCode, Url};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+const RESET_REQUESTED_MESSAGE: &str =
+    "<p><i>If the username is known, a password reset link has been sent to its email address</i></p>";
+
+fn token_from_link(link: &Url) -> String {
+    link.query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+async fn request_reset_link(test_app: &TestApp) -> Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_forgot_password(&serde_json::json!({
+            "username": test_app.test_user.username,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let email_request = &test_app.wait_for_emails(1).await[0];
+    let reset_link = test_app.get_confirmation_links(email_request).html;
+    assert_eq!(reset_link.path(), "/login/reset");
+
+    reset_link
+}
+
+#[tokio::test]
+async fn forgot_password_does_not_reveal_unknown_usernames() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_forgot_password(&serde_json::json!({ "username": "nobody" }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains(RESET_REQUESTED_MESSAGE));
+}
+
+#[tokio::test]
+async fn forgot_password_does_not_wait_for_the_email_to_be_sent() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(5)))
+        .mount(&test_app.email_server)
+        .await;
+
+    let started_at = std::time::Instant::now();
+    let response = test_app
+        .post_forgot_password(&serde_json::json!({
+            "username": test_app.test_user.username,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(started_at.elapsed() < std::time::Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn forgot_password_sends_a_reset_link_to_known_users() {
+    let test_app = spawn_app().await;
+
+    let reset_link = request_reset_link(&test_app).await;
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains(RESET_REQUESTED_MESSAGE));
+
+    let response = reqwest::get(reset_link).await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn user_can_log_in_with_the_new_password_after_a_reset() {
+    let test_app = spawn_app().await;
+    let reset_link = request_reset_link(&test_app).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    let response = test_app
+        .post_reset_password(&serde_json::json!({
+            "token": token_from_link(&reset_link),
+            "new_password": new_password,
+            "new_password_check": new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your password has been reset, you can now log in</i></p>"));
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": test_app.test_user.username,
+            "password": test_app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": test_app.test_user.username,
+            "password": new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn reset_token_can_only_be_used_once() {
+    let test_app = spawn_app().await;
+    let reset_link = request_reset_link(&test_app).await;
+    let form_data = serde_json::json!({
+        "token": token_from_link(&reset_link),
+        "new_password": "a-long-enough-password",
+        "new_password_check": "a-long-enough-password",
+    });
+
+    let response = test_app.post_reset_password(&form_data).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = test_app.post_reset_password(&form_data).await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+    let response = reqwest::get(reset_link).await.unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn new_passwords_must_match_to_reset() {
+    let test_app = spawn_app().await;
+    let reset_link = request_reset_link(&test_app).await;
+    let token = token_from_link(&reset_link);
+
+    let response = test_app
+        .post_reset_password(&serde_json::json!({
+            "token": token,
+            "new_password": "a-long-enough-password",
+            "new_password_check": "another-long-enough-password",
+        }))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/login/reset?token={}", urlencoding::encode(&token)),
+    );
+
+    let response = test_app
+        .post_reset_password(&serde_json::json!({
+            "token": token,
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn password_reset_invalidates_existing_sessions() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    let reset_link = request_reset_link(&test_app).await;
+    test_app
+        .post_reset_password(&serde_json::json!({
+            "token": token_from_link(&reset_link),
+            "new_password": "a-long-enough-password",
+            "new_password_check": "a-long-enough-password",
+        }))
+        .await;
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
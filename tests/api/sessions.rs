@@ -0,0 +1,173 @@
+use crate::helpers::{
+    assert_is_redirect_to, build_api_client, get_csrf_token, spawn_app, spawn_app_with, TestApp,
+};
+use std::time::Duration;
+
+async fn log_in_from_another_client(test_app: &TestApp) -> reqwest::Client {
+    let client = build_api_client();
+    let csrf_token = get_csrf_token(&client, &test_app.address).await;
+    let response = client
+        .post(format!("{}/login", test_app.address))
+        .header("User-Agent", "second-browser")
+        .form(&serde_json::json!({
+            "username": test_app.test_user.username,
+            "password": test_app.test_user.password,
+            "csrf_token": csrf_token,
+        }))
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    client
+}
+
+async fn get_dashboard_with(client: &reqwest::Client, test_app: &TestApp) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", test_app.address))
+        .send()
+        .await
+        .expect("Failed to send a request to the app")
+}
+
+#[tokio::test]
+async fn user_must_be_logged_in_to_see_sessions() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .api_client
+        .get(format!("{}/admin/sessions", test_app.address))
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn active_sessions_are_listed() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    log_in_from_another_client(&test_app).await;
+
+    let html_page = test_app.get_sessions_html().await;
+
+    assert!(html_page.contains("This session"));
+    assert!(html_page.contains("second-browser"));
+}
+
+#[tokio::test]
+async fn revoking_a_session_logs_the_other_client_out() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let other_client = log_in_from_another_client(&test_app).await;
+    let session_id: String = sqlx::query_scalar(
+        r#"SELECT `id` FROM `user_sessions` WHERE `user_agent` = 'second-browser'"#,
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    let response = test_app
+        .api_client
+        .post(format!(
+            "{}/admin/sessions/{}/revoke",
+            test_app.address, session_id
+        ))
+        .form(&serde_json::json!({ "csrf_token": test_app.get_csrf_token().await }))
+        .send()
+        .await
+        .expect("Failed to send a request to the app");
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    let response = get_dashboard_with(&other_client, &test_app).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn revoking_other_sessions_keeps_the_current_one() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let other_client = log_in_from_another_client(&test_app).await;
+
+    let response = test_app.post_revoke_other_sessions().await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    let html_page = test_app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>All other sessions have been revoked</i></p>"));
+    assert!(!html_page.contains("second-browser"));
+
+    let response = get_dashboard_with(&other_client, &test_app).await;
+    assert_is_redirect_to(&response, "/login");
+    // The revoked session cookie is removed from the browser
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == "id" && cookie.value().is_empty()));
+}
+
+#[tokio::test]
+async fn changing_the_password_revokes_other_sessions() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let other_client = log_in_from_another_client(&test_app).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    let response = test_app
+        .post_change_password(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let response = get_dashboard_with(&other_client, &test_app).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn idle_sessions_expire() {
+    let test_app = spawn_app_with(|c| c.application.session.idle_timeout_seconds = 1).await;
+    test_app.test_user.login(&test_app).await;
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Your session has expired, please log in again"));
+}
+
+#[tokio::test]
+async fn active_sessions_do_not_expire_before_the_idle_timeout() {
+    let test_app = spawn_app_with(|c| c.application.session.idle_timeout_seconds = 3).await;
+    test_app.test_user.login(&test_app).await;
+
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let response = test_app.get_admin_dashboard().await;
+        assert_eq!(200, response.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn sessions_expire_after_their_maximum_lifetime() {
+    let test_app = spawn_app_with(|c| c.application.session.max_lifetime_seconds = 1).await;
+    test_app.test_user.login(&test_app).await;
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Your session has expired, please log in again"));
+}
@@ -0,0 +1,259 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+async fn subscribers(test_app: &TestApp) -> Vec<(String, String, String)> {
+    sqlx::query!("SELECT `email`, `name`, `status` FROM `subscriptions` ORDER BY `email`")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.email, row.name, row.status))
+        .collect()
+}
+
+fn rejected_rows(html_page: &str) -> String {
+    let start = html_page
+        .find("data:text/csv;base64,")
+        .expect("No report was offered")
+        + "data:text/csv;base64,".len();
+    let end = start + html_page[start..].find('"').unwrap();
+
+    String::from_utf8(STANDARD.decode(&html_page[start..end]).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn user_must_be_logged_in_to_import_subscribers() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_subscriber_import("email,name\nalice@example.com,Alice\n", false)
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn imported_subscribers_are_pending_confirmation_by_default() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .post_subscriber_import(
+            "name,email\nAlice,alice@example.com\n\"Doe, Bob\",bob@example.com\n",
+            false,
+        )
+        .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("2 subscriber(s) imported, 0 row(s) rejected."));
+    assert!(!html_page.contains("data:text/csv"));
+    assert_eq!(
+        vec![
+            (
+                "alice@example.com".into(),
+                "Alice".into(),
+                "pending_confirmation".into()
+            ),
+            (
+                "bob@example.com".into(),
+                "Doe, Bob".into(),
+                "pending_confirmation".into()
+            ),
+        ],
+        subscribers(&test_app).await
+    );
+}
+
+#[tokio::test]
+async fn imported_pending_subscribers_are_sent_a_confirmation_link() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_subscriber_import("email,name\nalice@example.com,Alice\n", false)
+        .await;
+    test_app.dispatch_all_pending_confirmation_emails().await;
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        vec![(
+            "alice@example.com".into(),
+            "Alice".into(),
+            "confirmed".into()
+        )],
+        subscribers(&test_app).await
+    );
+}
+
+#[tokio::test]
+async fn subscribers_can_be_imported_as_confirmed() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    test_app
+        .post_subscriber_import("email,name\nalice@example.com,Alice\n", true)
+        .await;
+
+    assert_eq!(
+        vec![(
+            "alice@example.com".into(),
+            "Alice".into(),
+            "confirmed".into()
+        )],
+        subscribers(&test_app).await
+    );
+}
+
+#[tokio::test]
+async fn rejected_rows_are_reported_with_their_reason() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    test_app
+        .add_subscriber("existing@example.com", "Existing", "confirmed", Utc::now())
+        .await;
+    test_app
+        .add_subscriber("gone@example.com", "Existing", "unsubscribed", Utc::now())
+        .await;
+
+    let csv = "email,name\n\
+        new@example.com,New\n\
+        not-an-email,Invalid\n\
+        nameless@example.com,\n\
+        NEW@example.com,Again\n\
+        existing@example.com,Existing\n\
+        gone@example.com,Gone\n";
+    let html_page = test_app
+        .post_subscriber_import(csv, false)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains("1 subscriber(s) imported, 5 row(s) rejected."));
+    let report = rejected_rows(&html_page);
+    let lines: Vec<_> = report.lines().collect();
+    assert_eq!("line,email,name,error", lines[0]);
+    assert!(lines[1].starts_with("3,not-an-email,Invalid,"));
+    assert!(lines[2].starts_with("4,nameless@example.com,,"));
+    assert_eq!(
+        "5,NEW@example.com,Again,The email appears earlier in the file",
+        lines[3]
+    );
+    assert_eq!(
+        "6,existing@example.com,Existing,The address is already subscribed",
+        lines[4]
+    );
+    assert_eq!(
+        "7,gone@example.com,Gone,The address has unsubscribed and is suppressed",
+        lines[5]
+    );
+    let statuses: Vec<_> = subscribers(&test_app)
+        .await
+        .into_iter()
+        .map(|(email, _, status)| (email, status))
+        .collect();
+    assert!(statuses.contains(&("gone@example.com".into(), "unsubscribed".into())));
+    assert!(statuses.contains(&("new@example.com".into(), "pending_confirmation".into())));
+}
+
+#[tokio::test]
+async fn large_files_are_imported_in_batches() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let mut csv = String::from("email,name\n");
+    for i in 0..1200 {
+        csv.push_str(&format!("subscriber{}@example.com,Subscriber {}\n", i, i));
+    }
+
+    let html_page = test_app
+        .post_subscriber_import(&csv, true)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains("1200 subscriber(s) imported, 0 row(s) rejected."));
+    assert_eq!(1200, subscribers(&test_app).await.len());
+}
+
+#[tokio::test]
+async fn files_without_the_expected_columns_are_refused() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .post_subscriber_import("address,full_name\nalice@example.com,Alice\n", false)
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers/import");
+    let html_page = test_app
+        .api_client
+        .get(format!("{}/admin/subscribers/import", test_app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The CSV file has no `email` column</i></p>"));
+    assert!(subscribers(&test_app).await.is_empty());
+}
+
+#[tokio::test]
+async fn uploads_without_a_csrf_token_are_rejected() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let form = reqwest::multipart::Form::new().text("file", "email,name\na@example.com,A\n");
+
+    let response = test_app
+        .api_client
+        .post(format!("{}/admin/subscribers/import", test_app.address))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert!(subscribers(&test_app).await.is_empty());
+}
+
+#[tokio::test]
+async fn csrf_tokens_are_not_accepted_in_the_query_string() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let form = reqwest::multipart::Form::new().text("file", "email,name\na@example.com,A\n");
+
+    let response = test_app
+        .api_client
+        .post(format!(
+            "{}/admin/subscribers/import?csrf_token={}",
+            test_app.address,
+            test_app.get_csrf_token().await
+        ))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert!(subscribers(&test_app).await.is_empty());
+}
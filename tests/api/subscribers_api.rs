@@ -0,0 +1,203 @@
+use crate::helpers::{spawn_app, TestApp};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+async fn spawn_app_with_token() -> (TestApp, String) {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["subscribers:manage"]).await;
+
+    (test_app, token)
+}
+
+async fn get_json(test_app: &TestApp, token: &str, path: &str) -> serde_json::Value {
+    let response = test_app.get_api(Some(token), path).await;
+    assert_eq!(200, response.status().as_u16());
+
+    response.json().await.unwrap()
+}
+
+fn emails(page: &serde_json::Value) -> Vec<&str> {
+    page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["email"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated_with_a_cursor_from_the_most_recent() {
+    let (test_app, token) = spawn_app_with_token().await;
+    for age_in_days in 0..5 {
+        let email = format!("subscriber{}@example.com", age_in_days);
+        test_app
+            .add_subscriber(
+                &email,
+                "Subscriber",
+                "confirmed",
+                Utc::now() - Duration::days(age_in_days),
+            )
+            .await;
+    }
+
+    let first_page = get_json(&test_app, &token, "/api/v1/subscribers?limit=2").await;
+    let cursor = first_page["next_cursor"].as_str().unwrap();
+    let second_page = get_json(
+        &test_app,
+        &token,
+        &format!("/api/v1/subscribers?limit=2&cursor={}", cursor),
+    )
+    .await;
+    let cursor = second_page["next_cursor"].as_str().unwrap();
+    let last_page = get_json(
+        &test_app,
+        &token,
+        &format!("/api/v1/subscribers?limit=2&cursor={}", cursor),
+    )
+    .await;
+
+    assert_eq!(
+        vec!["subscriber0@example.com", "subscriber1@example.com"],
+        emails(&first_page)
+    );
+    assert_eq!(
+        vec!["subscriber2@example.com", "subscriber3@example.com"],
+        emails(&second_page)
+    );
+    assert_eq!(vec!["subscriber4@example.com"], emails(&last_page));
+    assert!(last_page["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status_and_email() {
+    let (test_app, token) = spawn_app_with_token().await;
+    test_app
+        .add_subscriber("alice@example.com", "Subscriber", "confirmed", Utc::now())
+        .await;
+    test_app
+        .add_subscriber(
+            "bob@example.com",
+            "Subscriber",
+            "pending_confirmation",
+            Utc::now(),
+        )
+        .await;
+    test_app
+        .add_subscriber("carol@example.org", "Subscriber", "confirmed", Utc::now())
+        .await;
+
+    let pending = get_json(
+        &test_app,
+        &token,
+        "/api/v1/subscribers?status=pending_confirmation",
+    )
+    .await;
+    let example_com = get_json(
+        &test_app,
+        &token,
+        "/api/v1/subscribers?status=confirmed&email=example.com",
+    )
+    .await;
+
+    assert_eq!(vec!["bob@example.com"], emails(&pending));
+    assert_eq!(vec!["alice@example.com"], emails(&example_com));
+}
+
+#[tokio::test]
+async fn invalid_filters_are_rejected() {
+    let (test_app, token) = spawn_app_with_token().await;
+
+    for path in [
+        "/api/v1/subscribers?status=subscribed",
+        "/api/v1/subscribers?cursor=not-a-cursor",
+        "/api/v1/subscribers?limit=0",
+    ] {
+        let response = test_app.get_api(Some(&token), path).await;
+
+        assert_eq!(400, response.status().as_u16(), "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn a_single_subscriber_can_be_fetched() {
+    let (test_app, token) = spawn_app_with_token().await;
+    let subscriber_id = test_app
+        .add_subscriber("alice@example.com", "Subscriber", "confirmed", Utc::now())
+        .await;
+
+    let subscriber = get_json(
+        &test_app,
+        &token,
+        &format!("/api/v1/subscribers/{}", subscriber_id),
+    )
+    .await;
+    let missing = test_app
+        .get_api(
+            Some(&token),
+            &format!("/api/v1/subscribers/{}", Uuid::new_v4()),
+        )
+        .await;
+
+    assert_eq!(subscriber_id.to_string(), subscriber["id"]);
+    assert_eq!("alice@example.com", subscriber["email"]);
+    assert_eq!("Subscriber", subscriber["name"]);
+    assert_eq!("confirmed", subscriber["status"]);
+    assert_eq!(404, missing.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribers_can_be_confirmed_and_unsubscribed() {
+    let (test_app, token) = spawn_app_with_token().await;
+    let subscriber_id = test_app
+        .add_subscriber(
+            "alice@example.com",
+            "Subscriber",
+            "pending_confirmation",
+            Utc::now(),
+        )
+        .await;
+
+    let confirmed: serde_json::Value = test_app
+        .post_api(
+            &token,
+            &format!("/api/v1/subscribers/{}/confirm", subscriber_id),
+            None,
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    let unsubscribed: serde_json::Value = test_app
+        .post_api(
+            &token,
+            &format!("/api/v1/subscribers/{}/unsubscribe", subscriber_id),
+            None,
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!("confirmed", confirmed["status"]);
+    assert_eq!("unsubscribed", unsubscribed["status"]);
+    let saved = sqlx::query!(
+        "SELECT `status` FROM `subscriptions` WHERE `id` = ?",
+        subscriber_id
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!("unsubscribed", saved.status);
+}
+
+#[tokio::test]
+async fn tokens_without_the_subscribers_scope_are_forbidden() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["newsletters:publish"]).await;
+
+    let response = test_app.get_api(Some(&token), "/api/v1/subscribers").await;
+
+    assert_eq!(403, response.status().as_u16());
+}
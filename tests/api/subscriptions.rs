@@ -126,6 +126,7 @@ async fn subscribe_sends_a_confirmation_email_for_valid_data() {
         .await;
 
     test_app.post_subscriptions(body.into()).await;
+    test_app.dispatch_all_pending_confirmation_emails().await;
 }
 
 #[tokio::test]
@@ -140,6 +141,7 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
         .await;
 
     test_app.post_subscriptions(body.into()).await;
+    test_app.dispatch_all_pending_confirmation_emails().await;
 
     let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
     let confirmation_links = test_app.get_confirmation_links(email_request);
@@ -147,6 +149,68 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
     assert_eq!(confirmation_links.html, confirmation_links.text);
 }
 
+#[tokio::test]
+async fn subscribe_succeeds_while_the_email_service_is_down_and_retries_later() {
+    let test_app = spawn_app().await;
+    let body = "name=Jane%20Doe&email=jane.doe%40example.com";
+
+    let outage = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+
+    let response = test_app.post_subscriptions(body.into()).await;
+    assert_eq!(StatusCode::OK, response.status());
+    test_app.dispatch_all_pending_confirmation_emails().await;
+
+    let queued = sqlx::query!("SELECT `n_attempts`, `last_error` FROM `confirmation_email_outbox`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("The confirmation email should still be queued");
+    assert_eq!(1, queued.n_attempts);
+    assert!(queued.last_error.is_some());
+    drop(outage);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    sqlx::query!("UPDATE `confirmation_email_outbox` SET `next_attempt_at` = CURRENT_TIMESTAMP()")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    test_app.dispatch_all_pending_confirmation_emails().await;
+
+    let queued = sqlx::query!("SELECT COUNT(*) AS `n` FROM `confirmation_email_outbox`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(0, queued.n);
+}
+
+#[tokio::test]
+async fn no_confirmation_email_is_queued_when_the_subscription_is_rolled_back() {
+    let test_app = spawn_app().await;
+    let body = "name=Jeremy&email=jeremy@gmail.com";
+    sqlx::query!(r#"ALTER TABLE `webhook_events` DROP COLUMN `payload`;"#)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let response = test_app.post_subscriptions(body.into()).await;
+
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+    let queued = sqlx::query!("SELECT COUNT(*) AS `n` FROM `confirmation_email_outbox`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(0, queued.n);
+}
+
 #[tokio::test]
 async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     let test_app = spawn_app().await;
@@ -160,3 +224,81 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
 
     assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
 }
+
+#[tokio::test]
+async fn subscribe_accepts_json_and_answers_with_json() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "John Snow",
+            "email": "john.snow@stark.house",
+        }))
+        .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("pending_confirmation", body["status"]);
+    let saved = sqlx::query!("SELECT `email`, `name` FROM `subscriptions`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch persisted subscription");
+    assert_eq!(saved.email, "john.snow@stark.house");
+    assert_eq!(saved.name, "John Snow");
+    test_app.dispatch_all_pending_confirmation_emails().await;
+}
+
+#[tokio::test]
+async fn subscribe_reports_every_invalid_json_field() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": " ",
+            "email": "definitely-not-an-email",
+        }))
+        .await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("invalid_request", body["error"]["code"]);
+    assert!(body["error"]["fields"]["name"].is_string());
+    assert!(body["error"]["fields"]["email"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_only_reports_the_invalid_json_fields() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "John Snow",
+            "email": "definitely-not-an-email",
+        }))
+        .await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"]["fields"].get("name").is_none());
+    assert!(body["error"]["fields"]["email"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_rejects_incomplete_json_with_a_json_error() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_subscriptions_json(&serde_json::json!({ "name": "John Snow" }))
+        .await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!("invalid_request", body["error"]["code"]);
+}
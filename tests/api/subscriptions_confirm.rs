@@ -28,6 +28,7 @@ async fn the_link_returned_by_subscribe_returns_200_status_when_called() {
         .await;
 
     test_app.post_subscriptions(body.into()).await;
+    test_app.dispatch_all_pending_confirmation_emails().await;
 
     let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
     let confirmation_links = test_app.get_confirmation_links(email_request);
@@ -49,6 +50,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
         .await;
 
     test_app.post_subscriptions(body.into()).await;
+    test_app.dispatch_all_pending_confirmation_emails().await;
 
     let received_request = &test_app.email_server.received_requests().await.unwrap()[0];
     let confirmation_links = test_app.get_confirmation_links(received_request);
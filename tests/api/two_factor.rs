@@ -0,0 +1,202 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, Secret};
+use zero2prod::authentication::totp;
+
+fn extract_between<'a>(html: &'a str, start: &str, end: &str) -> Vec<&'a str> {
+    html.split(start)
+        .skip(1)
+        .map(|chunk| chunk.split(end).next().unwrap())
+        .collect()
+}
+
+fn current_code(secret: &Secret<String>) -> String {
+    totp(secret, "").unwrap().generate_current().unwrap()
+}
+
+/// The code of the next time step, still accepted thanks to the allowed clock skew, but newer
+/// than the code used to enable two-factor authentication.
+fn next_code(secret: &Secret<String>) -> String {
+    let totp = totp(secret, "").unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    totp.generate(now + totp.step)
+}
+
+struct Enrolment {
+    secret: Secret<String>,
+    code: String,
+    recovery_codes: Vec<String>,
+}
+
+async fn enable_two_factor(test_app: &TestApp) -> Enrolment {
+    let html_page = test_app.get_two_factor_html().await;
+    let secret = extract_between(&html_page, "the secret <code>", "</code>")[0];
+    let secret = Secret::new(secret.to_owned());
+
+    let code = current_code(&secret);
+
+    let response = test_app
+        .post_enable_two_factor(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let html_page = response.text().await.unwrap();
+    let recovery_codes: Vec<String> = extract_between(&html_page, "<li><code>", "</code></li>")
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect();
+    assert_eq!(10, recovery_codes.len());
+
+    Enrolment {
+        secret,
+        code,
+        recovery_codes,
+    }
+}
+
+async fn log_in_with_password(test_app: &TestApp) {
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": test_app.test_user.username,
+            "password": test_app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn wrong_code_does_not_enable_two_factor() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+
+    let response = test_app
+        .post_enable_two_factor(&serde_json::json!({ "code": "not-a-code" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+
+    let html_page = test_app.get_two_factor_html().await;
+    assert!(html_page.contains("<p><i>The authentication code is incorrect</i></p>"));
+
+    test_app.post_logout().await;
+    test_app.test_user.login(&test_app).await;
+}
+
+#[tokio::test]
+async fn login_requires_a_second_factor_once_enabled() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let enrolment = enable_two_factor(&test_app).await;
+    test_app.post_logout().await;
+
+    log_in_with_password(&test_app).await;
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": next_code(&enrolment.secret) }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = test_app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", test_app.test_user.username)));
+}
+
+#[tokio::test]
+async fn authentication_codes_cannot_be_replayed() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let enrolment = enable_two_factor(&test_app).await;
+    test_app.post_logout().await;
+
+    // The code used to enable two-factor authentication is already spent
+    log_in_with_password(&test_app).await;
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": enrolment.code }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let code = next_code(&enrolment.secret);
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": code }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    test_app.post_logout().await;
+
+    log_in_with_password(&test_app).await;
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": code }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn totp_secrets_are_not_stored_in_plain_text() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let enrolment = enable_two_factor(&test_app).await;
+
+    let stored = sqlx::query!(
+        "SELECT `totp_secret` FROM `users` WHERE `id` = ?",
+        test_app.test_user.user_id
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+    let stored_secret = stored.totp_secret.unwrap();
+    assert!(!stored_secret.contains(enrolment.secret.expose_secret().as_str()));
+}
+
+#[tokio::test]
+async fn wrong_second_factor_is_rejected() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    enable_two_factor(&test_app).await;
+    test_app.post_logout().await;
+
+    log_in_with_password(&test_app).await;
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": "000000-wrong" }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn recovery_code_can_only_be_used_once() {
+    let test_app = spawn_app().await;
+    test_app.test_user.login(&test_app).await;
+    let enrolment = enable_two_factor(&test_app).await;
+    let recovery_code = &enrolment.recovery_codes[0];
+    test_app.post_logout().await;
+
+    log_in_with_password(&test_app).await;
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": recovery_code }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    test_app.post_logout().await;
+
+    log_in_with_password(&test_app).await;
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": recovery_code }))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn second_factor_step_requires_a_valid_password_first() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_two_factor_login(&serde_json::json!({ "code": "123456" }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
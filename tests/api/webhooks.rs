@@ -0,0 +1,282 @@
+use crate::helpers::{spawn_app_with, TestApp};
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use secrecy::Secret;
+use sha2::Sha256;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+use zero2prod::{configuration::WebhookEndpointSettings, webhooks::WebhookEvent};
+
+const SECRET: &str = "shared-with-the-crm";
+
+async fn spawn_app_with_endpoint(crm: &MockServer, events: Vec<WebhookEvent>) -> TestApp {
+    let url = format!("{}/webhooks", crm.uri());
+    let test_app = spawn_app_with(|configuration| {
+        configuration.webhooks.endpoints = vec![WebhookEndpointSettings {
+            url,
+            secret: Secret::new(SECRET.to_owned()),
+            events,
+        }];
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+}
+
+async fn mount_crm(crm: &MockServer, status: u16) {
+    Mock::given(path("/webhooks"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(crm)
+        .await;
+}
+
+async fn received_events(crm: &MockServer) -> Vec<serde_json::Value> {
+    crm.received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+fn header<'a>(request: &'a wiremock::Request, name: &str) -> &'a str {
+    request
+        .headers
+        .iter()
+        .find(|(header_name, _)| header_name.as_str().eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.last().as_str())
+        .unwrap_or_else(|| panic!("The {} header is missing", name))
+}
+
+async fn subscribe(test_app: &TestApp) {
+    test_app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    test_app.dispatch_all_pending_confirmation_emails().await;
+}
+
+#[tokio::test]
+async fn subscribing_sends_a_signed_webhook() {
+    let crm = MockServer::start().await;
+    mount_crm(&crm, 200).await;
+    let test_app = spawn_app_with_endpoint(&crm, vec![]).await;
+
+    subscribe(&test_app).await;
+    test_app.dispatch_all_pending_webhooks().await;
+
+    let requests = crm.received_requests().await.unwrap();
+    assert_eq!(1, requests.len());
+    let request = &requests[0];
+    assert_eq!("subscriber.created", header(request, "X-Webhook-Event"));
+    let timestamp = header(request, "X-Webhook-Timestamp");
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(&request.body);
+    assert_eq!(
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes())),
+        header(request, "X-Webhook-Signature")
+    );
+
+    let event: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!("subscriber.created", event["type"]);
+    assert_eq!(header(request, "X-Webhook-Id"), event["id"]);
+    assert_eq!("ursula_le_guin@gmail.com", event["data"]["email"]);
+    assert_eq!("pending_confirmation", event["data"]["status"]);
+}
+
+#[tokio::test]
+async fn following_the_confirmation_link_twice_notifies_the_confirmation_once() {
+    let crm = MockServer::start().await;
+    mount_crm(&crm, 200).await;
+    let test_app = spawn_app_with_endpoint(&crm, vec![]).await;
+    subscribe(&test_app).await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+
+    for _ in 0..2 {
+        reqwest::get(confirmation_links.html.clone())
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    test_app.dispatch_all_pending_webhooks().await;
+
+    let event_types: Vec<_> = received_events(&crm)
+        .await
+        .iter()
+        .map(|event| event["type"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(
+        vec!["subscriber.created", "subscriber.confirmed"],
+        event_types
+    );
+}
+
+#[tokio::test]
+async fn endpoints_only_receive_the_events_they_subscribed_to() {
+    let crm = MockServer::start().await;
+    mount_crm(&crm, 200).await;
+    let test_app = spawn_app_with_endpoint(&crm, vec![WebhookEvent::SubscriberUnsubscribed]).await;
+    subscribe(&test_app).await;
+    let subscriber_id: String = sqlx::query_scalar("SELECT `id` FROM `subscriptions`")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    test_app.test_user.login(&test_app).await;
+    let token = test_app.create_api_token(&["subscribers:manage"]).await;
+
+    let response = test_app
+        .post_api(
+            &token,
+            &format!("/api/v1/subscribers/{}/unsubscribe", subscriber_id),
+            None,
+        )
+        .await;
+    assert_eq!(StatusCode::OK, response.status());
+    test_app.dispatch_all_pending_webhooks().await;
+
+    let events = received_events(&crm).await;
+    assert_eq!(1, events.len());
+    assert_eq!("subscriber.unsubscribed", events[0]["type"]);
+    assert_eq!("unsubscribed", events[0]["data"]["status"]);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_then_reported() {
+    let crm = MockServer::start().await;
+    mount_crm(&crm, 500).await;
+    let test_app = spawn_app_with_endpoint(&crm, vec![]).await;
+
+    subscribe(&test_app).await;
+    test_app.dispatch_all_pending_webhooks().await;
+
+    // `max_attempts` is 3 in the test configuration, without any delay between attempts
+    assert_eq!(3, crm.received_requests().await.unwrap().len());
+    test_app.test_user.login(&test_app).await;
+    let html_page = test_app.get_webhook_deliveries_html("status=failed").await;
+    assert!(html_page.contains("subscriber.created"));
+    assert!(html_page.contains("500 Internal Server Error"));
+    let html_page = test_app
+        .get_webhook_deliveries_html("status=delivered")
+        .await;
+    assert!(!html_page.contains("subscriber.created"));
+}
+
+#[tokio::test]
+async fn an_issue_is_notified_once_every_delivery_has_been_attempted() {
+    let crm = MockServer::start().await;
+    mount_crm(&crm, 200).await;
+    let test_app = spawn_app_with_endpoint(&crm, vec![WebhookEvent::IssueSent]).await;
+    let issue_id = Uuid::new_v4();
+    sqlx::query(
+        r#"INSERT INTO `newsletter_issues`
+               (`newsletter_issue_id`, `title`, `text_content`, `html_content`, `published_at`)
+           VALUES (?, 'Weekly', 'Text', '<p>Html</p>', CURRENT_TIMESTAMP())"#,
+    )
+    .bind(issue_id)
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"INSERT INTO `issue_delivery_queue` (`newsletter_issue_id`, `subscriber_email`)
+           VALUES (?, 'reader@example.com')"#,
+    )
+    .bind(issue_id)
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    test_app.dispatch_all_pending_webhooks().await;
+    assert!(received_events(&crm).await.is_empty());
+
+    test_app.dispatch_all_pending_emails().await;
+    test_app.dispatch_all_pending_webhooks().await;
+
+    let events = received_events(&crm).await;
+    assert_eq!(1, events.len());
+    assert_eq!("issue.sent", events[0]["type"]);
+    assert_eq!(issue_id.to_string(), events[0]["data"]["id"]);
+    assert_eq!(1, events[0]["data"]["delivered"]);
+    assert_eq!(0, events[0]["data"]["failed"]);
+}
+
+#[tokio::test]
+async fn non_owners_cannot_see_webhook_deliveries() {
+    let crm = MockServer::start().await;
+    let test_app = spawn_app_with_endpoint(&crm, vec![]).await;
+    let test_user = test_app.add_test_user("editor").await;
+    test_user.login(&test_app).await;
+
+    let response = test_app
+        .api_client
+        .get(format!("{}/admin/webhooks", test_app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+}
+
+#[tokio::test]
+async fn settled_deliveries_are_deleted_after_the_retention_period() {
+    let crm = MockServer::start().await;
+    let test_app = spawn_app_with_endpoint(&crm, vec![]).await;
+    let url = format!("{}/webhooks", crm.uri());
+    let (settled_event_id, pending_event_id) = (Uuid::new_v4(), Uuid::new_v4());
+    for event_id in [settled_event_id, pending_event_id] {
+        sqlx::query(
+            r#"INSERT INTO `webhook_events` (`event_id`, `event`, `payload`, `created_at`, `dispatched_at`)
+               VALUES (?, 'subscriber.created', '{}', CURRENT_TIMESTAMP() - INTERVAL 40 DAY,
+                       CURRENT_TIMESTAMP() - INTERVAL 40 DAY)"#,
+        )
+        .bind(event_id)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    }
+    // `retention_days` is 30 in the test configuration
+    sqlx::query(
+        r#"INSERT INTO `webhook_deliveries`
+               (`delivery_id`, `event_id`, `endpoint_url`, `status`, `created_at`, `next_attempt_at`)
+           VALUES (?, ?, ?, 'delivered', CURRENT_TIMESTAMP() - INTERVAL 40 DAY, CURRENT_TIMESTAMP()),
+                  (?, ?, ?, 'pending', CURRENT_TIMESTAMP() - INTERVAL 40 DAY,
+                   CURRENT_TIMESTAMP() + INTERVAL 1 DAY)"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(settled_event_id)
+    .bind(&url)
+    .bind(Uuid::new_v4())
+    .bind(pending_event_id)
+    .bind(&url)
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    test_app.dispatch_all_pending_webhooks().await;
+
+    let remaining_events: Vec<String> =
+        sqlx::query_scalar("SELECT `event_id` FROM `webhook_events`")
+            .fetch_all(&test_app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(vec![pending_event_id.to_string()], remaining_events);
+    let remaining_deliveries: Vec<String> =
+        sqlx::query_scalar("SELECT `status` FROM `webhook_deliveries`")
+            .fetch_all(&test_app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(vec!["pending".to_owned()], remaining_deliveries);
+}